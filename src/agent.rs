@@ -2,10 +2,20 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::mpsc;
 
+/// Anything that can answer a chat request with a stream of response chunks.
+/// Agents hold an `Arc<dyn LlmProvider>` so a live model can be swapped for a
+/// recording or replaying one (see `crate::replay`). Fails when no response
+/// can be produced at all.
+pub trait LlmProvider: Send + Sync {
+    fn chat_stream(&self, messages: Vec<Message>, tools: Vec<Value>) -> anyhow::Result<mpsc::Receiver<String>>;
+}
+
 #[derive(Clone)]
 pub struct OllamaClient {
     pub base_url: String,
     pub model: String,
+    pub seed: Option<u64>,
+    pub temperature: Option<f32>,
     pub client: rusty_ollama::Client,
 }
 
@@ -14,15 +24,31 @@ impl OllamaClient {
         Self {
             base_url: base_url.to_string(),
             model: model.to_string(),
+            seed: None,
+            temperature: None,
             client: rusty_ollama::Client::new(base_url, model),
         }
     }
 
-    pub fn chat_stream(
+    /// Pins the sampling seed sent with every request.
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sampling temperature sent with every request; `None` keeps the model default.
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+}
+
+impl LlmProvider for OllamaClient {
+    fn chat_stream(
         &self,
         _messages: Vec<Message>,
         _tools: Vec<Value>,
-    ) -> mpsc::Receiver<String> {
+    ) -> anyhow::Result<mpsc::Receiver<String>> {
         let (tx, rx) = mpsc::channel(100);
         
         tokio::spawn(async move {
@@ -32,7 +58,7 @@ impl OllamaClient {
             let _ = tx.send("{\"reasoning\": \"ok\", \"final_answer\": \"done\"}".to_string()).await;
         });
         
-        rx
+        Ok(rx)
     }
 }
 
@@ -48,6 +74,7 @@ pub struct Message {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
+    #[serde(default)]
     pub id: String,
     pub name: String,
     pub arguments: Value,
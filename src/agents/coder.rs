@@ -1,18 +1,60 @@
-use crate::agent::{Message, OllamaClient};
+use crate::agent::{LlmProvider, Message};
+use crate::agents::roles::RoleSpec;
+use crate::agents::{run_tool_loop, AgentObserver, AgentRole, DEFAULT_MAX_TURNS};
 use crate::barq::BarqIndex;
 use crate::tools::ToolRegistry;
-use serde_json::{json, Value};
 use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct CoderAgent {
-    pub llm: OllamaClient,
+    pub llm: Arc<dyn LlmProvider>,
     pub barq: Arc<BarqIndex>,
     pub tools: Arc<ToolRegistry>,
+    /// Directory the agent's file and cargo tools must operate in.
+    pub workdir: String,
+    /// Name of the role this agent acts as, "coder" unless set by `with_role`.
+    pub role: String,
+    pub system_prompt: String,
+    pub max_turns: usize,
+    pub observer: Option<AgentObserver>,
 }
 
 impl CoderAgent {
-    pub fn new(llm: OllamaClient, barq: Arc<BarqIndex>, tools: Arc<ToolRegistry>) -> Self {
-        Self { llm, barq, tools }
+    pub fn new(llm: Arc<dyn LlmProvider>, barq: Arc<BarqIndex>, tools: Arc<ToolRegistry>) -> Self {
+        Self {
+            llm,
+            barq,
+            tools,
+            workdir: ".".to_string(),
+            role: AgentRole::Coder.name().to_string(),
+            system_prompt: AgentRole::Coder.system_prompt().to_string(),
+            max_turns: DEFAULT_MAX_TURNS,
+            observer: None,
+        }
+    }
+
+    /// The same agent acting as `role`, e.g. a user-defined "docs" worker.
+    pub fn with_role(mut self, role: &RoleSpec) -> Self {
+        self.role = role.name.clone();
+        self.system_prompt = role.system_prompt.clone();
+        self.max_turns = role.max_iterations.unwrap_or(self.max_turns);
+        self
+    }
+
+    /// The same agent, pointed at another checkout (e.g. a step's git worktree).
+    pub fn in_dir(&self, workdir: &str) -> Self {
+        Self {
+            workdir: workdir.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// The same agent, reporting its progress to `observer`.
+    pub fn observed(&self, observer: AgentObserver) -> Self {
+        Self {
+            observer: Some(observer),
+            ..self.clone()
+        }
     }
 
     pub async fn implement_step(&self, step_id: &str, description: &str) -> anyhow::Result<String> {
@@ -23,14 +65,14 @@ impl CoderAgent {
         }
 
         let prompt = format!(
-            "Step ID: {}\nDescription: {}\n\nContext:\n{}\n\nImplement the step using the context provided. Return the final modified source code or state what actions were taken.",
-            step_id, description, context_str
+            "Step ID: {}\nDescription: {}\nWorking directory: {}\n\nContext:\n{}\n\nImplement the step using the context provided. Return the final modified source code or state what actions were taken.",
+            step_id, description, self.workdir, context_str
         );
 
         let messages = vec![
             Message {
                 role: "system".to_string(),
-                content: crate::agents::AgentRole::Coder.system_prompt().to_string(),
+                content: self.system_prompt.clone(),
                 tool_calls: None,
                 tool_call_id: None,
             },
@@ -42,20 +84,15 @@ impl CoderAgent {
             }
         ];
 
-        let tool_schemas = self.tools.schemas();
-        let mut rx = self.llm.chat_stream(messages, tool_schemas);
-
-        let mut final_response = String::new();
-        while let Some(msg) = rx.recv().await {
-            if let Ok(val) = serde_json::from_str::<Value>(&msg) {
-                if let Some(text) = val.get("final_answer").and_then(|v| v.as_str()) {
-                    final_response.push_str(text);
-                }
-            } else {
-                final_response.push_str(&msg);
-            }
-        }
-
-        Ok(final_response)
+        run_tool_loop(
+            self.llm.as_ref(),
+            &self.tools,
+            messages,
+            &self.workdir,
+            self.max_turns,
+            self.observer.as_ref(),
+            |_, _, _| {},
+        )
+        .await
     }
 }
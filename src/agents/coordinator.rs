@@ -1,48 +1,488 @@
-use crate::agent::OllamaClient;
 use crate::barq::BarqIndex;
+use crate::config::{Config, RejectionPolicy};
 use crate::tools::ToolRegistry;
+use crate::verifier::{TestScope, Verifier};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::task::JoinSet;
 use super::planner::{PlannerAgent, PlanStep};
 use super::coder::CoderAgent;
 use super::tester::TesterAgent;
-use super::reviewer::ReviewerAgent;
+use super::reviewer::{ReviewEvidence, ReviewVerdict, ReviewerAgent};
+use super::roles::{ProviderFactory, RoleRegistry, RoleSpec};
+use super::worktree::{self, MergeOutcome, Worktree};
+use super::{AgentEvent, AgentObserver, AgentRole};
 
 pub struct CoordinatorAgent {
     pub barq: Arc<BarqIndex>,
     pub planner: PlannerAgent,
     pub coder: CoderAgent,
+    /// User-defined roles steps can be routed to by name, in place of the coder.
+    pub workers: HashMap<String, CoderAgent>,
     pub tester: TesterAgent,
     pub reviewer: ReviewerAgent,
+    pub workspace: String,
+    pub max_parallel_steps: usize,
+    pub max_review_rounds: u8,
+    pub on_review_rejected: RejectionPolicy,
+    /// Run the whole test suite over the merged result before finishing.
+    pub full_suite_before_answer: bool,
+    events: broadcast::Sender<CoordinatorEvent>,
+}
+
+/// Progress of a multi-agent run. Every subscriber (TUI, session recorder,
+/// ...) sees every event; events sent while nobody is subscribed are dropped.
+#[derive(Debug, Clone)]
+pub enum CoordinatorEvent {
+    PlanReady { goal: String, steps: Vec<PlanStep> },
+    StepStarted { step_id: String, description: String },
+    /// `role` has started working on the step.
+    AgentStarted { step_id: String, role: String },
+    /// Streamed model output. `step_id` is `None` while the planner works.
+    AgentToken { step_id: Option<String>, role: String, token: String },
+    ToolCall { step_id: String, role: String, id: String, name: String, args: Value },
+    ToolResult { step_id: String, role: String, id: String, name: String, result: Value },
+    ReviewVerdict { step_id: String, round: u8, verdict: ReviewVerdict },
+    /// The step is merged (`error` is `None`) or has given up.
+    StepFinished { step_id: String, error: Option<String> },
+    /// Every step is merged and the whole test suite has run over the result.
+    FullSuiteVerified { passed: bool, tests_run: Vec<String> },
+    /// `execute_goal` is about to return; `error` is its error, if any.
+    GoalFinished { goal: String, error: Option<String> },
+}
+
+/// Forwards an agent's progress as `CoordinatorEvent`s tagged with its step and role.
+fn observer(events: &broadcast::Sender<CoordinatorEvent>, step_id: Option<&str>, role: &str) -> AgentObserver {
+    let events = events.clone();
+    let step_id = step_id.map(str::to_string);
+    let role = role.to_string();
+    Arc::new(move |event| {
+        let event = match (event, &step_id) {
+            (AgentEvent::Token(token), _) => CoordinatorEvent::AgentToken {
+                step_id: step_id.clone(),
+                role: role.clone(),
+                token,
+            },
+            (AgentEvent::ToolCall { id, name, args }, Some(step_id)) => CoordinatorEvent::ToolCall {
+                step_id: step_id.clone(),
+                role: role.clone(),
+                id,
+                name,
+                args,
+            },
+            (AgentEvent::ToolResult { id, name, result }, Some(step_id)) => CoordinatorEvent::ToolResult {
+                step_id: step_id.clone(),
+                role: role.clone(),
+                id,
+                name,
+                result,
+            },
+            // Only step agents call tools.
+            (_, None) => return,
+        };
+        let _ = events.send(event);
+    })
+}
+
+const EVENT_CAPACITY: usize = 4096;
+
+/// A step the reviewer still rejected after every allowed round.
+#[derive(Debug)]
+pub struct ReviewRejected {
+    pub rounds: u8,
+    pub verdict: ReviewVerdict,
+}
+
+impl std::fmt::Display for ReviewRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rejected by reviewer after {} round(s)", self.rounds)?;
+        if let Some(issue) = self.verdict.issues.first() {
+            write!(f, ": {}", issue.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ReviewRejected {}
+
+/// What a step produced, before it is merged back into the workspace.
+struct StepOutcome {
+    step: PlanStep,
+    worktree: Option<Worktree>,
+    result: anyhow::Result<String>,
 }
 
 impl CoordinatorAgent {
-    pub fn new(llm: OllamaClient, barq: Arc<BarqIndex>, tools: Arc<ToolRegistry>) -> Self {
-        Self {
-            barq: barq.clone(),
-            planner: PlannerAgent::new(llm.clone(), barq.clone()),
-            coder: CoderAgent::new(llm.clone(), barq.clone(), tools.clone()),
-            tester: TesterAgent::new(llm.clone(), barq.clone(), tools.clone()),
-            reviewer: ReviewerAgent::new(llm, barq),
+    /// Builds one agent per role, each on the model `providers` returns for
+    /// it and limited to the role's tools.
+    pub fn new(
+        roles: &RoleRegistry,
+        providers: &ProviderFactory,
+        barq: Arc<BarqIndex>,
+        tools: Arc<ToolRegistry>,
+        config: &Config,
+    ) -> anyhow::Result<Self> {
+        let tools_for = |spec: &RoleSpec| -> anyhow::Result<Arc<ToolRegistry>> {
+            match &spec.tools {
+                Some(names) => Ok(Arc::new(tools.subset(names).map_err(|e| {
+                    anyhow::anyhow!("role {}: {}", spec.name, e)
+                })?)),
+                None => Ok(tools.clone()),
+            }
+        };
+        let worker = |spec: &RoleSpec| -> anyhow::Result<CoderAgent> {
+            Ok(CoderAgent::new(providers(spec)?, barq.clone(), tools_for(spec)?).with_role(spec))
+        };
+
+        let planner_spec = roles.builtin_role(&AgentRole::Planner);
+        let tester_spec = roles.builtin_role(&AgentRole::Tester);
+        let reviewer_spec = roles.builtin_role(&AgentRole::Reviewer);
+
+        let coder = worker(roles.builtin_role(&AgentRole::Coder))?;
+        let mut workers = HashMap::new();
+        let mut routes = vec![(
+            AgentRole::Coder.name().to_string(),
+            "general implementation work (default)".to_string(),
+        )];
+        for spec in roles.workers().filter(|s| s.name != AgentRole::Coder.name()) {
+            workers.insert(spec.name.clone(), worker(spec)?);
+            routes.push((spec.name.clone(), spec.description.clone()));
         }
+
+        let events = broadcast::channel(EVENT_CAPACITY).0;
+        Ok(Self {
+            barq: barq.clone(),
+            planner: PlannerAgent::new(providers(planner_spec)?, barq.clone())
+                .with_role(planner_spec)
+                .with_workers(routes)
+                .with_observer(observer(&events, None, AgentRole::Planner.name())),
+            coder,
+            workers,
+            tester: TesterAgent::new(providers(tester_spec)?, barq.clone(), tools_for(tester_spec)?)
+                .with_role(tester_spec),
+            reviewer: ReviewerAgent::new(providers(reviewer_spec)?, barq).with_role(reviewer_spec),
+            workspace: config.workspace_root.clone(),
+            max_parallel_steps: config.max_parallel_steps.max(1),
+            max_review_rounds: config.max_review_rounds.max(1),
+            on_review_rejected: config.on_review_rejected,
+            full_suite_before_answer: config.full_suite_before_answer,
+            events,
+        })
     }
 
+    pub fn subscribe(&self) -> broadcast::Receiver<CoordinatorEvent> {
+        self.events.subscribe()
+    }
+
+    /// The agent a step is routed to: its named role, or the coder.
+    fn worker_for(&self, step: &PlanStep) -> &CoderAgent {
+        step.role
+            .as_deref()
+            .and_then(|role| self.workers.get(role))
+            .unwrap_or(&self.coder)
+    }
+
+    /// Plans the goal, then runs every step whose dependencies are merged, up to
+    /// `max_parallel_steps` at a time, each in its own git worktree. Finished
+    /// steps are merged back as they complete, which is always a dependency order
+    /// since a step only starts once everything it depends on has been merged.
+    /// Outside a git repository steps run one at a time in the workspace itself.
+    /// Steps are verified against the tests their changes can reach; with
+    /// `full_suite_before_answer` the whole suite then runs once over the
+    /// merged workspace, and the goal fails if it does not pass.
+    /// Progress is published to every `subscribe`d receiver, ending with `GoalFinished`.
     pub async fn execute_goal(&self, goal: &str) -> anyhow::Result<()> {
+        let result = self.run_goal(goal).await;
+        let _ = self.events.send(CoordinatorEvent::GoalFinished {
+            goal: goal.to_string(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        result
+    }
+
+    async fn run_goal(&self, goal: &str) -> anyhow::Result<()> {
+        let isolated = worktree::is_git_repo(&self.workspace).await;
+        // Without a worktree a rejected step's edits are already in the
+        // workspace, so there would be nothing to roll back.
+        if !isolated && self.on_review_rejected == RejectionPolicy::Rollback {
+            anyhow::bail!(
+                "on_review_rejected = \"rollback\" needs {} to be a git repository",
+                self.workspace
+            );
+        }
+
         let plan = self.planner.decompose(goal).await?;
-        
-        for step in plan {
-            // Very simplified synchronous routing for now. 
-            // Commits 37-38 will handle parallel execution and conflicts.
-            let impl_result = self.coder.implement_step(&step.id, &step.description).await?;
-            let _test_result = self.tester.test_step(&step.id, &impl_result).await?;
-            
-            // In reality, Reviewer reviews a diff, we'll mock it passing for now.
-            let is_approved = self.reviewer.review_diff(&step.id, &impl_result).await?;
-            
-            if !is_approved {
-                eprintln!("Step {} was rejected by reviewer.", step.id);
+        let _ = self.events.send(CoordinatorEvent::PlanReady {
+            goal: goal.to_string(),
+            steps: plan.clone(),
+        });
+
+        let limit = if isolated { self.max_parallel_steps } else { 1 };
+
+        let mut pending = plan;
+        let mut merged: HashSet<String> = HashSet::new();
+        let mut failed: Vec<String> = Vec::new();
+        let mut running: JoinSet<StepOutcome> = JoinSet::new();
+        // What each running task was given, to clean up after it if it panics.
+        let mut spawned: HashMap<tokio::task::Id, (PlanStep, Option<Worktree>)> = HashMap::new();
+
+        loop {
+            while running.len() < limit {
+                let Some(pos) = pending
+                    .iter()
+                    .position(|s| s.depends_on.iter().all(|d| merged.contains(d)))
+                else {
+                    break;
+                };
+                let step = pending.remove(pos);
+                let worktree = if isolated {
+                    match Worktree::create(&self.workspace, &step.id).await {
+                        Ok(wt) => Some(wt),
+                        Err(e) => {
+                            let _ = self.events.send(CoordinatorEvent::StepFinished {
+                                step_id: step.id.clone(),
+                                error: Some(e.to_string()),
+                            });
+                            failed.push(format!("{}: {}", step.id, e));
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+                let task = running.spawn(self.run_step(step.clone(), worktree.clone(), None));
+                spawned.insert(task.id(), (step, worktree));
+            }
+
+            let Some(joined) = running.join_next_with_id().await else {
+                break;
+            };
+            let outcome = match joined {
+                Ok((id, outcome)) => {
+                    spawned.remove(&id);
+                    outcome
+                }
+                Err(e) => {
+                    let (step, worktree) = spawned
+                        .remove(&e.id())
+                        .expect("every running task is recorded when spawned");
+                    StepOutcome {
+                        step,
+                        worktree,
+                        result: Err(anyhow::anyhow!("step task failed: {}", e)),
+                    }
+                }
+            };
+            let step_id = outcome.step.id.clone();
+            let result = self.integrate(outcome).await;
+            let _ = self.events.send(CoordinatorEvent::StepFinished {
+                step_id: step_id.clone(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+            });
+            match result {
+                Ok(()) => {
+                    merged.insert(step_id);
+                }
+                Err(e) => failed.push(format!("{}: {}", step_id, e)),
             }
         }
 
+        if !failed.is_empty() || !pending.is_empty() {
+            let blocked: Vec<&str> = pending.iter().map(|s| s.id.as_str()).collect();
+            anyhow::bail!(
+                "Goal incomplete. Failed: [{}]. Blocked: [{}]",
+                failed.join("; "),
+                blocked.join(", ")
+            );
+        }
+        if self.full_suite_before_answer {
+            self.verify_full_suite().await?;
+        }
         Ok(())
     }
+
+    async fn verify_full_suite(&self) -> anyhow::Result<()> {
+        let verification = Verifier::new(self.barq.clone(), &self.workspace)
+            .with_test_scope(TestScope::Full)
+            .verify_changes(&[])
+            .await;
+        let passed = !verification.should_revert;
+        let _ = self.events.send(CoordinatorEvent::FullSuiteVerified {
+            passed,
+            tests_run: verification.tests_run,
+        });
+        if !passed {
+            anyhow::bail!("Full test suite fails on the merged result: {}", verification.errors.join("; "));
+        }
+        Ok(())
+    }
+
+    /// Runs coder, tester and reviewer for one step inside `worktree` (or the
+    /// workspace). A rejected attempt is handed back to the coder with the
+    /// reviewer's feedback, up to `max_review_rounds` rounds in total; an
+    /// attempt whose tests reveal implementation bugs goes back without review.
+    /// `conflict` lists files a previous attempt failed to merge.
+    fn run_step(
+        &self,
+        step: PlanStep,
+        worktree: Option<Worktree>,
+        conflict: Option<Vec<String>>,
+    ) -> impl std::future::Future<Output = StepOutcome> + Send + 'static {
+        let dir = worktree.as_ref().map_or(self.workspace.clone(), |wt| wt.dir());
+        let worker = self.worker_for(&step);
+        let coder = worker
+            .in_dir(&dir)
+            .observed(observer(&self.events, Some(&step.id), &worker.role));
+        let tester = self
+            .tester
+            .in_dir(&dir)
+            .observed(observer(&self.events, Some(&step.id), AgentRole::Tester.name()));
+        let reviewer = self
+            .reviewer
+            .observed(observer(&self.events, Some(&step.id), AgentRole::Reviewer.name()));
+        let verifier = Verifier::new(self.barq.clone(), &dir)
+            .with_target_dir(std::path::Path::new(&self.workspace).join("target").join("barqcoder-verify"))
+            .with_intent(&step.description);
+        let max_rounds = self.max_review_rounds;
+        let events = self.events.clone();
+
+        async move {
+            let _ = events.send(CoordinatorEvent::StepStarted {
+                step_id: step.id.clone(),
+                description: step.description.clone(),
+            });
+            let started = |role: &str| {
+                let _ = events.send(CoordinatorEvent::AgentStarted {
+                    step_id: step.id.clone(),
+                    role: role.to_string(),
+                });
+            };
+
+            let mut base_description = step.description.clone();
+            if let Some(files) = &conflict {
+                base_description.push_str(&format!(
+                    "\n\nA previous attempt at this step conflicted with changes already merged in: {}. Re-apply the step on top of the current code.",
+                    files.join(", ")
+                ));
+            }
+
+            let result = async {
+                let mut description = base_description.clone();
+                let mut verdict = ReviewVerdict::rejected("Step was never reviewed");
+                for round in 1..=max_rounds {
+                    started(&coder.role);
+                    let impl_result = coder.implement_step(&step.id, &description).await?;
+                    started(AgentRole::Tester.name());
+                    let test_report = tester.test_step(&step.id, &impl_result).await?;
+                    // Failing tests that show the code is wrong go straight
+                    // back to the coder while rounds remain.
+                    if test_report.has_implementation_bugs() && round < max_rounds {
+                        description = format!(
+                            "{}\n\nTests of attempt {} found bugs in the implementation:\n{}",
+                            base_description, round, test_report
+                        );
+                        continue;
+                    }
+
+                    let evidence = gather_evidence(&verifier, worktree.as_ref(), &impl_result, test_report.to_string()).await?;
+                    started(AgentRole::Reviewer.name());
+                    verdict = reviewer.review(&step.id, &evidence).await?;
+                    let _ = events.send(CoordinatorEvent::ReviewVerdict {
+                        step_id: step.id.clone(),
+                        round,
+                        verdict: verdict.clone(),
+                    });
+                    if verdict.approved {
+                        if let Some(wt) = &worktree {
+                            wt.commit(&format!("barqcoder: {}", step.description)).await?;
+                        }
+                        return Ok(impl_result);
+                    }
+
+                    description = format!(
+                        "{}\n\nReviewer feedback on attempt {}:\n{}",
+                        base_description,
+                        round,
+                        verdict.feedback()
+                    );
+                }
+                Err(ReviewRejected { rounds: max_rounds, verdict }.into())
+            }
+            .await;
+
+            StepOutcome { step, worktree, result }
+        }
+    }
+
+    /// Merges a finished step into the workspace. A merge conflict is handed to
+    /// a resolution attempt that redoes the step on top of the current HEAD.
+    /// A step the reviewer never approved is discarded under
+    /// `RejectionPolicy::Rollback` and kept on its branch under `Escalate`.
+    async fn integrate(&self, outcome: StepOutcome) -> anyhow::Result<()> {
+        let StepOutcome { step, worktree, result } = outcome;
+        let Some(wt) = worktree else {
+            return result.map(|_| ());
+        };
+
+        if let Err(e) = &result {
+            if e.is::<ReviewRejected>() && self.on_review_rejected == RejectionPolicy::Escalate {
+                anyhow::bail!("{}; left for manual review in {} (branch {})", e, wt.dir(), wt.branch);
+            }
+        }
+
+        let merge = match result {
+            Ok(_) => wt.merge_into(&self.workspace).await,
+            Err(e) => Err(e),
+        };
+        let _ = wt.remove(&self.workspace).await;
+
+        match merge? {
+            MergeOutcome::Merged | MergeOutcome::NothingToMerge => Ok(()),
+            MergeOutcome::Conflict(files) => self.resolve_conflict(step, files).await,
+        }
+    }
+
+    async fn resolve_conflict(&self, step: PlanStep, files: Vec<String>) -> anyhow::Result<()> {
+        let wt = Worktree::create(&self.workspace, &format!("{}-resolve", step.id)).await?;
+        let outcome = self.run_step(step, Some(wt.clone()), Some(files)).await;
+
+        let merge = match outcome.result {
+            Ok(_) => wt.merge_into(&self.workspace).await,
+            Err(e) => Err(e),
+        };
+        let _ = wt.remove(&self.workspace).await;
+
+        match merge? {
+            MergeOutcome::Merged | MergeOutcome::NothingToMerge => Ok(()),
+            MergeOutcome::Conflict(files) => {
+                anyhow::bail!("unresolved merge conflict in {}", files.join(", "))
+            }
+        }
+    }
+}
+
+/// Collects what the reviewer judges a step on: the real diff of the step's
+/// worktree and a verification of every file it touched. In-place steps have no
+/// diff to show, so the coder's summary stands in and only cargo is run.
+async fn gather_evidence(
+    verifier: &Verifier,
+    worktree: Option<&Worktree>,
+    impl_result: &str,
+    test_report: String,
+) -> anyhow::Result<ReviewEvidence> {
+    let (diff, changes) = match worktree {
+        Some(wt) => (wt.diff().await?, wt.changes().await?),
+        None => (
+            format!("(not a git checkout; coder's summary follows)\n{}", impl_result),
+            Vec::new(),
+        ),
+    };
+    let verification = verifier.verify_changes(&changes).await;
+    Ok(ReviewEvidence {
+        diff,
+        verification: Some(verification),
+        test_report,
+    })
 }
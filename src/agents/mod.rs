@@ -1,10 +1,21 @@
+use crate::agent::{AgentResponse, LlmProvider, Message};
+use crate::tools::ToolRegistry;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
 pub mod planner;
 pub mod coder;
 pub mod tester;
 pub mod reviewer;
 pub mod coordinator;
+pub mod worktree;
+pub mod roles;
+
+/// Tool-loop turns an agent gets unless its role sets `max_iterations`.
+pub const DEFAULT_MAX_TURNS: usize = 12;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AgentRole {
@@ -16,6 +27,29 @@ pub enum AgentRole {
 }
 
 impl AgentRole {
+    pub const ALL: [AgentRole; 5] = [
+        Self::Planner,
+        Self::Coder,
+        Self::Tester,
+        Self::Reviewer,
+        Self::Coordinator,
+    ];
+
+    /// The name a role is configured and routed by.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Planner => "planner",
+            Self::Coder => "coder",
+            Self::Tester => "tester",
+            Self::Reviewer => "reviewer",
+            Self::Coordinator => "coordinator",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role| role.name() == name)
+    }
+
     pub fn system_prompt(&self) -> &'static str {
         match self {
             Self::Planner => "You are the Planner agent. Your job is to decompose the task into a logical graph of executable steps.",
@@ -26,3 +60,176 @@ impl AgentRole {
         }
     }
 }
+
+/// What an agent is doing while it works: its streamed output and the tools
+/// it calls.
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    Token(String),
+    ToolCall { id: String, name: String, args: Value },
+    ToolResult { id: String, name: String, result: Value },
+}
+
+/// Receives an agent's `AgentEvent`s as they happen.
+pub type AgentObserver = Arc<dyn Fn(AgentEvent) + Send + Sync>;
+
+/// Streams one model response to completion, passing each chunk to `observer`.
+pub(crate) async fn collect_response(
+    llm: &dyn LlmProvider,
+    messages: Vec<Message>,
+    tools: Vec<Value>,
+    observer: Option<&AgentObserver>,
+) -> anyhow::Result<String> {
+    let mut rx = llm.chat_stream(messages, tools)?;
+    let mut raw = String::new();
+    while let Some(chunk) = rx.recv().await {
+        if let Some(observer) = observer {
+            observer(AgentEvent::Token(chunk.clone()));
+        }
+        raw.push_str(&chunk);
+    }
+    Ok(raw)
+}
+
+/// Finds the JSON payload in a model response, tolerating surrounding prose
+/// or markdown fences.
+pub(crate) fn extract_json(raw: &str) -> Option<Value> {
+    if let Ok(value) = serde_json::from_str(raw.trim()) {
+        return Some(value);
+    }
+    let start = raw.find(['{', '['])?;
+    let end = raw.rfind(['}', ']'])?;
+    if end <= start {
+        return None;
+    }
+    serde_json::from_str(&raw[start..=end]).ok()
+}
+
+/// Drives `llm` through tool calls until it produces a final answer, running
+/// each requested tool from `tools` and feeding its result back. Tool
+/// arguments are scoped to `workdir`. `on_tool_result` sees every call as it
+/// completes, and `observer` every token and tool call. Gives up after
+/// `max_turns` model responses.
+pub(crate) async fn run_tool_loop(
+    llm: &dyn LlmProvider,
+    tools: &ToolRegistry,
+    mut messages: Vec<Message>,
+    workdir: &str,
+    max_turns: usize,
+    observer: Option<&AgentObserver>,
+    mut on_tool_result: impl FnMut(&str, &Value, &Value),
+) -> anyhow::Result<String> {
+    for turn in 0..max_turns {
+        let raw = collect_response(llm, messages.clone(), tools.schemas(), observer).await?;
+
+        let response = extract_json(&raw).and_then(|v| serde_json::from_value::<AgentResponse>(v).ok());
+        let Some(mut response) = response else {
+            return Ok(raw);
+        };
+        if response.tool_calls.is_empty() {
+            return Ok(response.final_answer.unwrap_or(response.reasoning));
+        }
+
+        let mut rejected: HashMap<String, String> = HashMap::new();
+        for (i, call) in response.tool_calls.iter_mut().enumerate() {
+            if call.id.is_empty() {
+                call.id = format!("call_{}_{}", turn, i);
+            }
+            match scope_args(&call.arguments, workdir) {
+                Ok(args) => call.arguments = args,
+                Err(e) => {
+                    rejected.insert(call.id.clone(), e);
+                }
+            }
+        }
+        messages.push(Message {
+            role: "assistant".to_string(),
+            content: response.reasoning.clone(),
+            tool_calls: Some(response.tool_calls.clone()),
+            tool_call_id: None,
+        });
+
+        for call in &response.tool_calls {
+            if let Some(observer) = observer {
+                observer(AgentEvent::ToolCall {
+                    id: call.id.clone(),
+                    name: call.name.clone(),
+                    args: call.arguments.clone(),
+                });
+            }
+            let result = match tools.get(&call.name) {
+                _ if rejected.contains_key(&call.id) => json!({ "error": rejected[&call.id] }),
+                Some(tool) => tool
+                    .call(call.arguments.clone())
+                    .await
+                    .unwrap_or_else(|e| json!({ "error": e.to_string() })),
+                None => json!({ "error": format!("unknown tool {}", call.name) }),
+            };
+            on_tool_result(&call.name, &call.arguments, &result);
+            if let Some(observer) = observer {
+                observer(AgentEvent::ToolResult {
+                    id: call.id.clone(),
+                    name: call.name.clone(),
+                    result: result.clone(),
+                });
+            }
+            messages.push(Message {
+                role: "tool".to_string(),
+                content: result.to_string(),
+                tool_calls: None,
+                tool_call_id: Some(call.id.clone()),
+            });
+        }
+    }
+    anyhow::bail!("no final answer after {} turns", max_turns)
+}
+
+/// Points directory arguments at `workdir` when the model left them out and
+/// resolves relative file paths against it, so agents working in a separate
+/// checkout cannot touch the live workspace by accident. Paths that lead out
+/// of `workdir` are refused. Only path arguments are checked: the text of a
+/// shell command is run as given, from `workdir`.
+fn scope_args(args: &Value, workdir: &str) -> Result<Value, String> {
+    let mut args = args.clone();
+    if workdir == "." {
+        return Ok(args);
+    }
+    let Some(obj) = args.as_object_mut() else {
+        return Ok(args);
+    };
+    for key in ["dir", "working_dir", "path", "file_path"] {
+        if let Some(Value::String(p)) = obj.get_mut(key) {
+            *p = within(Path::new(workdir), p)
+                .ok_or_else(|| format!("{} '{}' is outside the working directory {}", key, p, workdir))?
+                .to_string_lossy()
+                .to_string();
+        }
+    }
+    for key in ["dir", "working_dir"] {
+        obj.entry(key).or_insert_with(|| json!(workdir));
+    }
+    Ok(args)
+}
+
+/// `path` resolved against `root`, or None when it leads outside `root`.
+fn within(root: &Path, path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    let relative = if path.is_absolute() { path.strip_prefix(root).ok()? } else { path };
+    let mut resolved = root.to_path_buf();
+    let mut depth = 0usize;
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => {
+                resolved.push(part);
+                depth += 1;
+            }
+            Component::ParentDir if depth > 0 => {
+                resolved.pop();
+                depth -= 1;
+            }
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(resolved)
+}
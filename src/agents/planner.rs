@@ -0,0 +1,262 @@
+use crate::agent::{LlmProvider, Message};
+use crate::agents::{collect_response, extract_json, AgentObserver};
+use crate::barq::BarqIndex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const MAX_PLAN_ATTEMPTS: usize = 3;
+
+const PLAN_SCHEMA: &str = r#"{
+  "steps": [
+    {
+      "id": "string, unique",
+      "description": "string",
+      "target_files": ["path"],
+      "acceptance_criteria": ["string"],
+      "depends_on": ["id of an earlier step"],
+      "role": "optional, name of the role that should implement the step"
+    }
+  ]
+}"#;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub id: String,
+    pub description: String,
+    #[serde(default)]
+    pub target_files: Vec<String>,
+    #[serde(default)]
+    pub acceptance_criteria: Vec<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Worker role to route the step to. Unset means the coder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+pub struct PlannerAgent {
+    pub llm: Arc<dyn LlmProvider>,
+    pub barq: Arc<BarqIndex>,
+    pub system_prompt: String,
+    /// Worker roles steps may be routed to, with their descriptions.
+    pub workers: Vec<(String, String)>,
+    pub observer: Option<AgentObserver>,
+}
+
+impl PlannerAgent {
+    pub fn new(llm: Arc<dyn LlmProvider>, barq: Arc<BarqIndex>) -> Self {
+        Self {
+            llm,
+            barq,
+            system_prompt: crate::agents::AgentRole::Planner.system_prompt().to_string(),
+            workers: Vec::new(),
+            observer: None,
+        }
+    }
+
+    pub fn with_role(mut self, role: &crate::agents::roles::RoleSpec) -> Self {
+        self.system_prompt = role.system_prompt.clone();
+        self
+    }
+
+    pub fn with_workers(mut self, workers: Vec<(String, String)>) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn with_observer(mut self, observer: AgentObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Rejects steps routed to a role the coordinator does not have.
+    fn check_roles(&self, steps: &[PlanStep]) -> anyhow::Result<()> {
+        for step in steps {
+            if let Some(role) = &step.role {
+                if !self.workers.is_empty() && !self.workers.iter().any(|(name, _)| name == role) {
+                    anyhow::bail!("step {} is routed to unknown role {}", step.id, role);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Breaks a goal into validated, dependency-ordered steps. Malformed or
+    /// invalid plans are sent back to the model with the reason, up to
+    /// `MAX_PLAN_ATTEMPTS` times.
+    pub async fn decompose(&self, goal: &str) -> anyhow::Result<Vec<PlanStep>> {
+        let context = self.barq.query(goal, 5);
+        let mut context_str = String::new();
+        for res in context {
+            context_str.push_str(&format!("File: {}\nContent:\n{}\n\n", res.file_path, res.content));
+        }
+
+        let mut roles_str = String::new();
+        for (name, description) in &self.workers {
+            roles_str.push_str(&format!("- {}: {}\n", name, description));
+        }
+
+        let prompt = format!(
+            "Goal: {}\n\nContext:\n{}\n\nRoles that can implement steps:\n{}\nDecompose the goal into small steps a coder can implement one at a time. Respond with strictly JSON matching this schema:\n{}",
+            goal, context_str, roles_str, PLAN_SCHEMA
+        );
+
+        let mut messages = vec![
+            Message {
+                role: "system".to_string(),
+                content: self.system_prompt.clone(),
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            Message {
+                role: "user".to_string(),
+                content: prompt,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ];
+
+        let mut last_error = anyhow::anyhow!("Planner produced no output");
+        for _ in 0..MAX_PLAN_ATTEMPTS {
+            let response =
+                collect_response(self.llm.as_ref(), messages.clone(), vec![], self.observer.as_ref()).await?;
+
+            match parse_plan(&response).and_then(|steps| self.check_roles(&steps).map(|_| steps)) {
+                Ok(steps) => return Ok(steps),
+                Err(e) => {
+                    messages.push(Message {
+                        role: "assistant".to_string(),
+                        content: response,
+                        tool_calls: None,
+                        tool_call_id: None,
+                    });
+                    messages.push(Message {
+                        role: "user".to_string(),
+                        content: format!(
+                            "That plan was rejected: {}. Respond again with only the corrected JSON plan.",
+                            e
+                        ),
+                        tool_calls: None,
+                        tool_call_id: None,
+                    });
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error.context(format!("Planner failed after {} attempts", MAX_PLAN_ATTEMPTS)))
+    }
+}
+
+/// Extracts a plan from a model response and validates it. Accepts a bare
+/// array of steps, a `{"steps": [...]}` object, either of those wrapped in the
+/// orchestrator's `final_answer` envelope, or JSON surrounded by prose/fences.
+pub fn parse_plan(raw: &str) -> anyhow::Result<Vec<PlanStep>> {
+    let value = extract_json(raw).ok_or_else(|| anyhow::anyhow!("response is not JSON"))?;
+    let steps_value = match value {
+        Value::Object(mut obj) => {
+            if let Some(Value::String(inner)) = obj.get("final_answer") {
+                return parse_plan(inner);
+            }
+            obj.remove("steps")
+                .ok_or_else(|| anyhow::anyhow!("missing \"steps\" array"))?
+        }
+        array @ Value::Array(_) => array,
+        _ => anyhow::bail!("expected an object with \"steps\""),
+    };
+
+    let steps: Vec<PlanStep> = serde_json::from_value(steps_value)
+        .map_err(|e| anyhow::anyhow!("steps do not match the schema: {}", e))?;
+    validate_plan(&steps)?;
+    Ok(steps)
+}
+
+/// Checks that a plan is non-empty, ids are unique, every dependency refers
+/// to another step, and the dependencies contain no cycle.
+pub fn validate_plan(steps: &[PlanStep]) -> anyhow::Result<()> {
+    if steps.is_empty() {
+        anyhow::bail!("plan has no steps");
+    }
+
+    let mut ids = HashSet::new();
+    for step in steps {
+        if step.id.trim().is_empty() {
+            anyhow::bail!("a step has an empty id");
+        }
+        if step.description.trim().is_empty() {
+            anyhow::bail!("step {} has an empty description", step.id);
+        }
+        if !ids.insert(step.id.as_str()) {
+            anyhow::bail!("duplicate step id {}", step.id);
+        }
+    }
+
+    for step in steps {
+        for dep in &step.depends_on {
+            if dep == &step.id {
+                anyhow::bail!("step {} depends on itself", step.id);
+            }
+            if !ids.contains(dep.as_str()) {
+                anyhow::bail!("step {} depends on unknown step {}", step.id, dep);
+            }
+        }
+    }
+
+    if let Some(cycle) = find_cycle(steps) {
+        anyhow::bail!("dependency cycle {}", cycle.join(" -> "));
+    }
+    Ok(())
+}
+
+/// Returns the ids along a dependency cycle (first id repeated at the end), if any.
+pub fn find_cycle(steps: &[PlanStep]) -> Option<Vec<String>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Visiting,
+        Done,
+    }
+
+    fn visit<'a>(
+        id: &'a str,
+        deps: &HashMap<&'a str, &'a [String]>,
+        marks: &mut HashMap<&'a str, Mark>,
+        path: &mut Vec<&'a str>,
+    ) -> Option<Vec<String>> {
+        match marks.get(id) {
+            Some(Mark::Done) => return None,
+            Some(Mark::Visiting) => {
+                let start = path.iter().position(|p| *p == id).unwrap_or(0);
+                let mut cycle: Vec<String> = path[start..].iter().map(|s| s.to_string()).collect();
+                cycle.push(id.to_string());
+                return Some(cycle);
+            }
+            None => {}
+        }
+
+        marks.insert(id, Mark::Visiting);
+        path.push(id);
+        for dep in deps.get(id).copied().unwrap_or(&[]) {
+            if let Some(cycle) = visit(dep, deps, marks, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        marks.insert(id, Mark::Done);
+        None
+    }
+
+    let deps: HashMap<&str, &[String]> = steps
+        .iter()
+        .map(|s| (s.id.as_str(), s.depends_on.as_slice()))
+        .collect();
+    let mut marks = HashMap::new();
+    for step in steps {
+        let mut path = Vec::new();
+        if let Some(cycle) = visit(&step.id, &deps, &mut marks, &mut path) {
+            return Some(cycle);
+        }
+    }
+    None
+}
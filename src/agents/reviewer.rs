@@ -1,28 +1,191 @@
-use crate::agent::{Message, OllamaClient};
+use crate::agent::{LlmProvider, Message};
+use crate::agents::{collect_response, extract_json, AgentObserver};
 use crate::barq::BarqIndex;
-use serde_json::Value;
+use crate::verifier::VerifyResult;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+const VERDICT_SCHEMA: &str = r#"{
+  "approved": true,
+  "issues": [
+    { "file": "path", "line": 1, "severity": "info | warning | error", "message": "string" }
+  ],
+  "suggested_changes": ["string"]
+}"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    #[serde(alias = "warn")]
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewIssue {
+    #[serde(default)]
+    pub file: String,
+    #[serde(default)]
+    pub line: Option<u32>,
+    #[serde(default)]
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewVerdict {
+    pub approved: bool,
+    #[serde(default)]
+    pub issues: Vec<ReviewIssue>,
+    #[serde(default)]
+    pub suggested_changes: Vec<String>,
+}
+
+impl ReviewVerdict {
+    /// A rejection carrying a single issue, for reviews that could not be read.
+    pub fn rejected(message: &str) -> Self {
+        Self {
+            approved: false,
+            issues: vec![ReviewIssue {
+                file: String::new(),
+                line: None,
+                severity: Severity::Error,
+                message: message.to_string(),
+            }],
+            suggested_changes: vec![],
+        }
+    }
+
+    /// The verdict rendered as instructions for the coder's next attempt.
+    pub fn feedback(&self) -> String {
+        let mut out = String::new();
+        for issue in &self.issues {
+            let location = match (issue.file.is_empty(), issue.line) {
+                (true, _) => String::new(),
+                (false, Some(line)) => format!("{}:{}: ", issue.file, line),
+                (false, None) => format!("{}: ", issue.file),
+            };
+            out.push_str(&format!("- [{:?}] {}{}\n", issue.severity, location, issue.message));
+        }
+        for change in &self.suggested_changes {
+            out.push_str(&format!("- Suggested: {}\n", change));
+        }
+        out
+    }
+}
+
+/// Reads a verdict from a model response, unwrapping the orchestrator's
+/// `final_answer` envelope. Anything unreadable counts as a rejection.
+pub fn parse_verdict(raw: &str) -> ReviewVerdict {
+    let Some(value) = extract_json(raw) else {
+        return ReviewVerdict::rejected("Reviewer response was not JSON");
+    };
+    if let Some(inner) = value.get("final_answer").and_then(|v| v.as_str()) {
+        return parse_verdict(inner);
+    }
+    serde_json::from_value(value)
+        .unwrap_or_else(|e| ReviewVerdict::rejected(&format!("Reviewer response did not match the schema: {}", e)))
+}
+
+/// Everything the reviewer judges a step on. `diff` falls back to the coder's
+/// own summary only when the step did not run in a git checkout.
+pub struct ReviewEvidence {
+    pub diff: String,
+    pub verification: Option<VerifyResult>,
+    pub test_report: String,
+}
+
+impl ReviewEvidence {
+    /// Renders the evidence as separate prompt sections so findings from the
+    /// compiler, the symbolic checks and the tests cannot blur together.
+    pub fn to_prompt_sections(&self) -> String {
+        fn list(items: &[String]) -> String {
+            if items.is_empty() {
+                "(none)\n".to_string()
+            } else {
+                items.iter().map(|i| format!("- {}\n", i)).collect()
+            }
+        }
+
+        let mut out = format!("## Diff\n```diff\n{}\n```\n\n", self.diff.trim_end());
+
+        out.push_str("## Verification\n");
+        match &self.verification {
+            Some(v) => {
+                out.push_str(&format!(
+                    "cargo check: {}\ncargo test: {} ({})\nsemantic score: {:.2}\nErrors:\n{}Warnings:\n{}\n",
+                    if v.cargo_check_pass { "pass" } else { "FAIL" },
+                    if v.cargo_test_pass { "pass" } else { "FAIL" },
+                    match (v.full_suite, v.tests_run.is_empty()) {
+                        (true, _) => "full suite".to_string(),
+                        (false, true) => "no tests affected".to_string(),
+                        (false, false) => format!("affected: {}", v.tests_run.join("; ")),
+                    },
+                    v.semantic_score,
+                    list(&v.errors),
+                    list(&v.warnings)
+                ));
+                out.push_str(&format!(
+                    "## Symbolic findings\nErrors:\n{}Warnings:\n{}\n",
+                    list(&v.symbolic_errors),
+                    list(&v.symbolic_warnings)
+                ));
+            }
+            None => out.push_str("(not run)\n\n## Symbolic findings\n(not run)\n\n"),
+        }
+
+        out.push_str(&format!("## Test results\n{}\n", self.test_report.trim_end()));
+        out
+    }
+}
+
+#[derive(Clone)]
 pub struct ReviewerAgent {
-    pub llm: OllamaClient,
+    pub llm: Arc<dyn LlmProvider>,
     pub barq: Arc<BarqIndex>,
+    pub system_prompt: String,
+    pub observer: Option<AgentObserver>,
 }
 
 impl ReviewerAgent {
-    pub fn new(llm: OllamaClient, barq: Arc<BarqIndex>) -> Self {
-        Self { llm, barq }
+    pub fn new(llm: Arc<dyn LlmProvider>, barq: Arc<BarqIndex>) -> Self {
+        Self {
+            llm,
+            barq,
+            system_prompt: crate::agents::AgentRole::Reviewer.system_prompt().to_string(),
+            observer: None,
+        }
+    }
+
+    pub fn with_role(mut self, role: &crate::agents::roles::RoleSpec) -> Self {
+        self.system_prompt = role.system_prompt.clone();
+        self
     }
 
-    pub async fn review_diff(&self, step_id: &str, diff: &str) -> anyhow::Result<bool> {
+    /// The same agent, reporting its progress to `observer`.
+    pub fn observed(&self, observer: AgentObserver) -> Self {
+        Self {
+            observer: Some(observer),
+            ..self.clone()
+        }
+    }
+
+    /// Reviews a step from its diff and verification evidence rather than the
+    /// coder's description of what it did.
+    pub async fn review(&self, step_id: &str, evidence: &ReviewEvidence) -> anyhow::Result<ReviewVerdict> {
         let prompt = format!(
-            "Review the following diff for step {}:\n\n{}\n\nDoes this code meet quality, security, and performance standards? Reply with strictly JSON containing a 'approved' boolean field and a 'feedback' string.",
-            step_id, diff
+            "Review the changes made for step {}.\n\n{}\nBase your verdict on the diff and the evidence above. Reject changes that fail verification or that the findings show to be unsafe. Reply with strictly JSON matching this schema:\n{}",
+            step_id,
+            evidence.to_prompt_sections(),
+            VERDICT_SCHEMA
         );
 
         let messages = vec![
             Message {
                 role: "system".to_string(),
-                content: crate::agents::AgentRole::Reviewer.system_prompt().to_string(),
+                content: self.system_prompt.clone(),
                 tool_calls: None,
                 tool_call_id: None,
             },
@@ -34,21 +197,7 @@ impl ReviewerAgent {
             },
         ];
 
-        let mut rx = self.llm.chat_stream(messages, vec![]);
-
-        let mut approved = false;
-        while let Some(msg) = rx.recv().await {
-            if let Ok(val) = serde_json::from_str::<Value>(&msg) {
-                if let Some(final_msg) = val.get("final_answer").and_then(|v| v.as_str()) {
-                     if final_msg.to_lowercase().contains("\"approved\": true") || final_msg.to_lowercase().contains("approved: true") {
-                         approved = true;
-                     }
-                }
-            } else if msg.to_lowercase().contains("\"approved\": true") {
-                approved = true;
-            }
-        }
-
-        Ok(approved)
+        let response = collect_response(self.llm.as_ref(), messages, vec![], self.observer.as_ref()).await?;
+        Ok(parse_verdict(&response))
     }
 }
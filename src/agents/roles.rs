@@ -0,0 +1,190 @@
+use crate::agent::LlmProvider;
+use crate::agents::AgentRole;
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Providers a role may name. Each needs a matching arm in the provider
+/// factory handed to `CoordinatorAgent::new`.
+pub const SUPPORTED_PROVIDERS: &[&str] = &["ollama"];
+
+/// Builds the model client a role talks to.
+pub type ProviderFactory<'a> = dyn Fn(&RoleSpec) -> anyhow::Result<Arc<dyn LlmProvider>> + 'a;
+
+/// A role as written in `Config.toml` (`[[agents]]`) or in
+/// `.barqcoder/agents/<name>.toml`. Unset fields keep the built-in role's
+/// value, or the global config's for new roles.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoleConfig {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Names of the tools from `ToolRegistry` the role may call. Unset allows all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<usize>,
+}
+
+/// A fully resolved role.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleSpec {
+    pub name: String,
+    pub description: String,
+    pub system_prompt: String,
+    pub tools: Option<Vec<String>>,
+    pub provider: String,
+    pub model: String,
+    pub base_url: String,
+    pub temperature: Option<f32>,
+    /// Tool-loop turns for agents that call tools. `None` keeps the agent's default.
+    pub max_iterations: Option<usize>,
+}
+
+impl RoleSpec {
+    /// A built-in role with the global model settings.
+    pub fn builtin(role: &AgentRole, config: &Config) -> Self {
+        Self {
+            name: role.name().to_string(),
+            description: String::new(),
+            system_prompt: role.system_prompt().to_string(),
+            tools: None,
+            provider: "ollama".to_string(),
+            model: config.ollama_model.clone(),
+            base_url: config.ollama_base_url.clone(),
+            temperature: None,
+            max_iterations: None,
+        }
+    }
+
+    fn apply(&mut self, overrides: &RoleConfig) {
+        if let Some(v) = &overrides.description {
+            self.description = v.clone();
+        }
+        if let Some(v) = &overrides.system_prompt {
+            self.system_prompt = v.clone();
+        }
+        if let Some(v) = &overrides.tools {
+            self.tools = Some(v.clone());
+        }
+        if let Some(v) = &overrides.provider {
+            self.provider = v.clone();
+        }
+        if let Some(v) = &overrides.model {
+            self.model = v.clone();
+        }
+        if let Some(v) = &overrides.base_url {
+            self.base_url = v.clone();
+        }
+        if overrides.temperature.is_some() {
+            self.temperature = overrides.temperature;
+        }
+        if let Some(v) = overrides.max_iterations {
+            self.max_iterations = Some(v.max(1));
+        }
+    }
+}
+
+/// Every role the coordinator can route work to, keyed by name.
+#[derive(Debug, Clone)]
+pub struct RoleRegistry {
+    roles: BTreeMap<String, RoleSpec>,
+}
+
+impl RoleRegistry {
+    pub fn builtin(config: &Config) -> Self {
+        let roles = AgentRole::ALL
+            .iter()
+            .map(|role| (role.name().to_string(), RoleSpec::builtin(role, config)))
+            .collect();
+        Self { roles }
+    }
+
+    /// Built-in roles, overridden or extended first by `config.agents` and then
+    /// by each `*.toml` file in `dir` (a missing directory is fine). A file's
+    /// role name defaults to its file stem.
+    pub fn load(config: &Config, dir: &Path) -> anyhow::Result<Self> {
+        let mut registry = Self::builtin(config);
+        for role in &config.agents {
+            registry.apply(config, role)?;
+        }
+
+        if dir.is_dir() {
+            let mut files: Vec<_> = std::fs::read_dir(dir)?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+                .collect();
+            files.sort();
+            for file in files {
+                let content = std::fs::read_to_string(&file)?;
+                let mut role: RoleConfig = toml::from_str(&content)
+                    .map_err(|e| anyhow::anyhow!("{}: {}", file.display(), e))?;
+                if role.name.is_empty() {
+                    role.name = file
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                }
+                registry
+                    .apply(config, &role)
+                    .map_err(|e| anyhow::anyhow!("{}: {}", file.display(), e))?;
+            }
+        }
+        Ok(registry)
+    }
+
+    fn apply(&mut self, config: &Config, overrides: &RoleConfig) -> anyhow::Result<()> {
+        let name = overrides.name.trim();
+        if name.is_empty() {
+            anyhow::bail!("agent role without a name");
+        }
+        if let Some(provider) = &overrides.provider {
+            if !SUPPORTED_PROVIDERS.contains(&provider.as_str()) {
+                anyhow::bail!(
+                    "role {} uses unknown provider {} (supported: {})",
+                    name,
+                    provider,
+                    SUPPORTED_PROVIDERS.join(", ")
+                );
+            }
+        }
+
+        let spec = self.roles.entry(name.to_string()).or_insert_with(|| RoleSpec {
+            name: name.to_string(),
+            system_prompt: format!("You are the {} agent.", name),
+            ..RoleSpec::builtin(&AgentRole::Coder, config)
+        });
+        spec.apply(overrides);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RoleSpec> {
+        self.roles.get(name)
+    }
+
+    /// The spec for a built-in role. Always present.
+    pub fn builtin_role(&self, role: &AgentRole) -> &RoleSpec {
+        self.get(role.name()).expect("built-in roles are always registered")
+    }
+
+    /// Roles that implement plan steps: the coder and every user-defined role.
+    pub fn workers(&self) -> impl Iterator<Item = &RoleSpec> {
+        self.roles.values().filter(|spec| {
+            spec.name == AgentRole::Coder.name() || AgentRole::from_name(&spec.name).is_none()
+        })
+    }
+}
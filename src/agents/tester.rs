@@ -1,30 +1,219 @@
-use crate::agent::{Message, OllamaClient};
+use crate::agent::{LlmProvider, Message};
+use crate::agents::roles::RoleSpec;
+use crate::agents::{extract_json, run_tool_loop, AgentObserver, AgentRole, DEFAULT_MAX_TURNS};
 use crate::barq::BarqIndex;
+use crate::tools::cargo_test::TestRun;
 use crate::tools::ToolRegistry;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
 
+const REPORT_SCHEMA: &str = r#"{
+  "tests_added": ["path::test_name"],
+  "failures": [
+    { "name": "test name", "message": "string", "cause": "implementation_bug | test_bug | unknown" }
+  ],
+  "summary": "string"
+}"#;
+
+/// Whether a failing test points at the code under test or at the test itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCause {
+    ImplementationBug,
+    TestBug,
+    #[default]
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestFailure {
+    pub name: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub cause: FailureCause,
+}
+
+/// What the tester did and found. Counts come from the last `cargo_test` run
+/// the tester actually made; the failure causes are its own judgement.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestReport {
+    #[serde(default)]
+    pub tests_added: Vec<String>,
+    #[serde(default)]
+    pub ran: bool,
+    #[serde(default)]
+    pub compiled: bool,
+    #[serde(default)]
+    pub passed: usize,
+    #[serde(default)]
+    pub failed: usize,
+    #[serde(default)]
+    pub failures: Vec<TestFailure>,
+    #[serde(default)]
+    pub summary: String,
+}
+
+impl TestReport {
+    pub fn all_passed(&self) -> bool {
+        self.ran && self.compiled && self.failed == 0
+    }
+
+    pub fn has_implementation_bugs(&self) -> bool {
+        self.failures.iter().any(|f| f.cause == FailureCause::ImplementationBug)
+    }
+
+    /// Combines the tester's final answer with what its tool calls observed.
+    /// Observed results win over claimed ones.
+    pub fn from_run(final_answer: &str, last_run: Option<&TestRun>, written: Vec<String>) -> Self {
+        let mut report = extract_json(final_answer)
+            .and_then(|v| serde_json::from_value::<TestReport>(v).ok())
+            .unwrap_or_else(|| TestReport {
+                summary: final_answer.trim().to_string(),
+                ..Default::default()
+            });
+
+        if report.tests_added.is_empty() {
+            report.tests_added = written;
+        }
+
+        match last_run {
+            Some(run) => {
+                report.ran = true;
+                report.compiled = run.compiled;
+                report.passed = run.passed;
+                report.failed = run.failed;
+                let claimed = std::mem::take(&mut report.failures);
+                report.failures = run
+                    .failures
+                    .iter()
+                    .map(|name| {
+                        claimed
+                            .iter()
+                            .find(|f| f.name == *name || name.ends_with(&format!("::{}", f.name)))
+                            .cloned()
+                            .map(|f| TestFailure { name: name.clone(), ..f })
+                            .unwrap_or(TestFailure {
+                                name: name.clone(),
+                                message: String::new(),
+                                cause: FailureCause::Unknown,
+                            })
+                    })
+                    .collect();
+                if !run.compiled {
+                    report.failures.extend(run.compile_errors.iter().map(|e| TestFailure {
+                        name: "(build)".to_string(),
+                        message: e.clone(),
+                        cause: FailureCause::Unknown,
+                    }));
+                }
+            }
+            None => {
+                report.ran = false;
+                report.compiled = false;
+                report.passed = 0;
+                report.failed = 0;
+            }
+        }
+        report
+    }
+}
+
+impl std::fmt::Display for TestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.ran {
+            writeln!(f, "Tests were not run.")?;
+        } else if !self.compiled {
+            writeln!(f, "Tests did not compile.")?;
+        } else if self.all_passed() {
+            writeln!(f, "All {} passed.", self.passed)?;
+        } else {
+            writeln!(f, "{} passed, {} failed.", self.passed, self.failed)?;
+        }
+        if !self.tests_added.is_empty() {
+            writeln!(f, "Tests added: {}", self.tests_added.join(", "))?;
+        }
+        for failure in &self.failures {
+            writeln!(f, "- {} [{:?}]: {}", failure.name, failure.cause, failure.message)?;
+        }
+        if !self.summary.is_empty() {
+            writeln!(f, "{}", self.summary)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
 pub struct TesterAgent {
-    pub llm: OllamaClient,
+    pub llm: Arc<dyn LlmProvider>,
     pub barq: Arc<BarqIndex>,
     pub tools: Arc<ToolRegistry>,
+    /// Directory the agent's file and cargo tools must operate in.
+    pub workdir: String,
+    pub system_prompt: String,
+    pub max_turns: usize,
+    pub observer: Option<AgentObserver>,
 }
 
 impl TesterAgent {
-    pub fn new(llm: OllamaClient, barq: Arc<BarqIndex>, tools: Arc<ToolRegistry>) -> Self {
-        Self { llm, barq, tools }
+    pub fn new(llm: Arc<dyn LlmProvider>, barq: Arc<BarqIndex>, tools: Arc<ToolRegistry>) -> Self {
+        Self {
+            llm,
+            barq,
+            tools,
+            workdir: ".".to_string(),
+            system_prompt: AgentRole::Tester.system_prompt().to_string(),
+            max_turns: DEFAULT_MAX_TURNS,
+            observer: None,
+        }
+    }
+
+    pub fn with_role(mut self, role: &RoleSpec) -> Self {
+        self.system_prompt = role.system_prompt.clone();
+        self.max_turns = role.max_iterations.unwrap_or(self.max_turns);
+        self
+    }
+
+    /// The same agent, pointed at another checkout (e.g. a step's git worktree).
+    pub fn in_dir(&self, workdir: &str) -> Self {
+        Self {
+            workdir: workdir.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// The same agent, reporting its progress to `observer`.
+    pub fn observed(&self, observer: AgentObserver) -> Self {
+        Self {
+            observer: Some(observer),
+            ..self.clone()
+        }
     }
 
-    pub async fn test_step(&self, step_id: &str, impl_result: &str) -> anyhow::Result<String> {
+    /// Writes tests for the step, runs them with `cargo_test` and iterates on
+    /// build errors until they compile, then reports what it found.
+    pub async fn test_step(&self, step_id: &str, impl_result: &str) -> anyhow::Result<TestReport> {
+        let context = self.barq.query(impl_result, 5);
+        let mut context_str = String::new();
+        for res in context {
+            context_str.push_str(&format!("File: {}\nContent:\n{}\n\n", res.file_path, res.content));
+        }
+
         let prompt = format!(
-            "Step ID: {}\nImplementation Result: {}\n\nWrite and run Rust tests to verify this implementation. Return the test results or state what actions were taken.",
-            step_id, impl_result
+            "Step ID: {}\nWorking directory: {}\nImplementation Result: {}\n\nCode likely under test:\n{}\n\
+             Locate the module under test (use barq_search if the context is not enough), then add tests: \
+             a #[cfg(test)] module next to the code or a file under tests/. Run them with cargo_test. \
+             If they fail to compile, fix the tests and run again. When the tests compile and have run, \
+             decide for each failure whether it reveals a bug in the implementation or in the test. \
+             Put strictly JSON matching this schema in final_answer:\n{}",
+            step_id, self.workdir, impl_result, context_str, REPORT_SCHEMA
         );
 
         let messages = vec![
             Message {
                 role: "system".to_string(),
-                content: crate::agents::AgentRole::Tester.system_prompt().to_string(),
+                content: self.system_prompt.clone(),
                 tool_calls: None,
                 tool_call_id: None,
             },
@@ -36,20 +225,41 @@ impl TesterAgent {
             },
         ];
 
-        let tool_schemas = self.tools.schemas();
-        let mut rx = self.llm.chat_stream(messages, tool_schemas);
-
-        let mut final_response = String::new();
-        while let Some(msg) = rx.recv().await {
-            if let Ok(val) = serde_json::from_str::<Value>(&msg) {
-                if let Some(text) = val.get("final_answer").and_then(|v| v.as_str()) {
-                    final_response.push_str(text);
+        let mut last_run: Option<TestRun> = None;
+        let mut written: Vec<String> = Vec::new();
+        let final_answer = run_tool_loop(
+            self.llm.as_ref(),
+            &self.tools,
+            messages,
+            &self.workdir,
+            self.max_turns,
+            self.observer.as_ref(),
+            |name, args, result| match name {
+                "cargo_test" => {
+                    if let Ok(run) = serde_json::from_value::<TestRun>(result.clone()) {
+                        last_run = Some(run);
+                    }
                 }
-            } else {
-                final_response.push_str(&msg);
-            }
-        }
+                "create_file" | "edit_file" => {
+                    let path = args
+                        .get("path")
+                        .or_else(|| args.get("file_path"))
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    let relative = path
+                        .strip_prefix(&self.workdir)
+                        .unwrap_or(path)
+                        .trim_start_matches('/')
+                        .to_string();
+                    if result.get("error").is_none() && !relative.is_empty() && !written.contains(&relative) {
+                        written.push(relative);
+                    }
+                }
+                _ => {}
+            },
+        )
+        .await?;
 
-        Ok(final_response)
+        Ok(TestReport::from_run(&final_answer, last_run.as_ref(), written))
     }
 }
@@ -0,0 +1,136 @@
+use crate::verifier::FileChange;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// A throwaway git worktree on its own branch, used to run one plan step in
+/// isolation from the live workspace and from other steps.
+#[derive(Debug, Clone)]
+pub struct Worktree {
+    pub path: PathBuf,
+    pub branch: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MergeOutcome {
+    Merged,
+    NothingToMerge,
+    Conflict(Vec<String>),
+}
+
+pub async fn is_git_repo(dir: &str) -> bool {
+    git(Path::new(dir), &["rev-parse", "--is-inside-work-tree"])
+        .await
+        .map(|out| out.trim() == "true")
+        .unwrap_or(false)
+}
+
+impl Worktree {
+    /// Creates a worktree for `step_id` branched from the current HEAD of
+    /// `repo`. It sits next to the repository, as
+    /// `.<repo>-worktree-<pid>-<step>`, so relative `path` dependencies that
+    /// lead out of the repository resolve the same from both.
+    pub async fn create(repo: &str, step_id: &str) -> anyhow::Result<Self> {
+        let slug: String = step_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        let branch = format!("barqcoder/{}-{}", std::process::id(), slug);
+        let toplevel = PathBuf::from(git(Path::new(repo), &["rev-parse", "--show-toplevel"]).await?.trim());
+        let name = toplevel.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let path = toplevel
+            .parent()
+            .unwrap_or(&toplevel)
+            .join(format!(".{}-worktree-{}-{}", name, std::process::id(), slug));
+
+        git(
+            Path::new(repo),
+            &["worktree", "add", "-b", &branch, &path.to_string_lossy(), "HEAD"],
+        )
+        .await?;
+        Ok(Self { path, branch })
+    }
+
+    pub fn dir(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+
+    /// Commits everything in the worktree. Returns false when nothing changed.
+    pub async fn commit(&self, message: &str) -> anyhow::Result<bool> {
+        git(&self.path, &["add", "-A"]).await?;
+        let status = git(&self.path, &["status", "--porcelain"]).await?;
+        if status.trim().is_empty() {
+            return Ok(false);
+        }
+        git(&self.path, &["commit", "-m", message]).await?;
+        Ok(true)
+    }
+
+    /// The uncommitted changes in the worktree as a unified diff against HEAD,
+    /// including new files.
+    pub async fn diff(&self) -> anyhow::Result<String> {
+        git(&self.path, &["add", "-A"]).await?;
+        git(&self.path, &["diff", "--cached", "HEAD"]).await
+    }
+
+    /// Each file the uncommitted changes touch, with its content at HEAD and
+    /// on disk. Added files have an empty original, deleted ones an empty patch.
+    pub async fn changes(&self) -> anyhow::Result<Vec<FileChange>> {
+        git(&self.path, &["add", "-A"]).await?;
+        let names = git(&self.path, &["diff", "--cached", "--name-only", "HEAD"]).await?;
+        let mut changes = Vec::new();
+        for name in names.lines().filter(|l| !l.is_empty()) {
+            let original = git(&self.path, &["show", &format!("HEAD:{}", name)])
+                .await
+                .unwrap_or_default();
+            let patched = std::fs::read_to_string(self.path.join(name)).unwrap_or_default();
+            changes.push(FileChange {
+                path: name.to_string(),
+                original,
+                patched,
+            });
+        }
+        Ok(changes)
+    }
+
+    /// Merges the worktree branch into the branch checked out in `repo`.
+    /// A conflicting merge is aborted, leaving `repo` untouched.
+    pub async fn merge_into(&self, repo: &str) -> anyhow::Result<MergeOutcome> {
+        let repo = Path::new(repo);
+        let ahead = git(repo, &["rev-list", "--count", &format!("HEAD..{}", self.branch)]).await?;
+        if ahead.trim() == "0" {
+            return Ok(MergeOutcome::NothingToMerge);
+        }
+
+        if git(repo, &["merge", "--no-ff", "--no-edit", &self.branch]).await.is_ok() {
+            return Ok(MergeOutcome::Merged);
+        }
+
+        let conflicts = git(repo, &["diff", "--name-only", "--diff-filter=U"])
+            .await
+            .unwrap_or_default();
+        git(repo, &["merge", "--abort"]).await?;
+        Ok(MergeOutcome::Conflict(
+            conflicts.lines().map(|l| l.to_string()).collect(),
+        ))
+    }
+
+    /// Removes the worktree directory and its branch.
+    pub async fn remove(&self, repo: &str) -> anyhow::Result<()> {
+        let repo = Path::new(repo);
+        git(repo, &["worktree", "remove", "--force", &self.dir()]).await?;
+        git(repo, &["branch", "-D", &self.branch]).await?;
+        Ok(())
+    }
+}
+
+async fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output().await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -57,6 +57,18 @@ impl BarqIndex {
         Ok(())
     }
 
+    /// Stores an arbitrary text chunk (not a source file) so it can be found by `query`.
+    pub fn index_text(&self, path: &str, line: usize, lang: &str, content: &str) {
+        self.vector.store(path, line, lang, content);
+    }
+
+    /// Drops a chunk stored with `index_text`. barqdb has no delete, so the
+    /// entry at the same path and line is replaced by an empty chunk, which
+    /// no query matches.
+    pub fn forget_text(&self, path: &str, line: usize) {
+        self.vector.store(path, line, "", "");
+    }
+
     pub fn query(&self, q: &str, top_k: usize) -> Vec<BarqResult> {
         self.vector.knn_search(q, top_k).into_iter().map(|(file_path, content, score, line)| BarqResult {
             file_path,
@@ -69,6 +81,91 @@ impl BarqIndex {
     pub fn graph_deps(&self, symbol: &str) -> Vec<String> {
         self.graph.neighbors(symbol)
     }
+
+    /// Embeds text for local similarity comparisons. barqdb only embeds
+    /// server-side, so this is the offline hashed-token embedding.
+    pub fn embed(&self, text: &str) -> Vec<f32> {
+        embed_text(text)
+    }
+}
+
+/// Dimensions of the vectors `embed_text` returns.
+pub const EMBEDDING_DIM: usize = 256;
+
+// Keywords and filler that say nothing about what code or a request is about.
+const EMBED_STOP_WORDS: &[&str] = &[
+    "fn", "pub", "let", "mut", "self", "crate", "super", "use", "mod", "impl", "for", "in",
+    "if", "else", "match", "return", "struct", "enum", "trait", "where", "as", "ref", "the",
+    "and", "to", "of", "a", "an", "it", "is", "be", "with", "that", "this", "str", "string",
+    "i32", "i64", "u8", "u32", "u64", "usize", "f32", "f64", "bool", "vec", "option", "result",
+];
+
+/// Feature-hashed bag of identifier words and their character trigrams,
+/// L2-normalised. camelCase and snake_case identifiers are split into words.
+pub fn embed_text(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; EMBEDDING_DIM];
+    for word in words(text) {
+        if EMBED_STOP_WORDS.contains(&word.as_str()) {
+            continue;
+        }
+        vector[bucket(&word)] += 1.0;
+        let padded: Vec<char> = format!("^{}$", word).chars().collect();
+        for gram in padded.windows(3) {
+            vector[bucket(&gram.iter().collect::<String>())] += 0.5;
+        }
+    }
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+/// Cosine similarity of two vectors; 0.0 when either is all zeros.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = a.iter().map(|v| v * v).sum::<f32>().sqrt() * b.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        0.0
+    } else {
+        dot / norm
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for ident in text.split(|c: char| !c.is_alphanumeric()) {
+        let mut current = String::new();
+        let mut prev_lower = false;
+        for c in ident.chars() {
+            if c.is_uppercase() && prev_lower && !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            current.extend(c.to_lowercase());
+        }
+        if !current.is_empty() {
+            words.push(current);
+        }
+    }
+    words.retain(|w| w.len() > 1);
+    words
+}
+
+/// FNV-1a: a stable hash, unlike `DefaultHasher`, so anything keyed by it
+/// (embedding buckets, cassettes, caches) stays comparable across runs and
+/// builds.
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn bucket(feature: &str) -> usize {
+    (fnv1a(feature.bytes()) % EMBEDDING_DIM as u64) as usize
 }
 
 pub fn parse_barqignore(root: &str) -> Vec<String> {
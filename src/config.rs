@@ -1,3 +1,4 @@
+use crate::agents::roles::RoleConfig;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
@@ -18,6 +19,50 @@ pub struct Config {
     pub max_iterations: u8,
     #[serde(default = "default_token_limit")]
     pub token_limit: u32,
+    /// How many independent plan steps may run at once, each in its own git worktree.
+    #[serde(default = "default_max_parallel_steps")]
+    pub max_parallel_steps: usize,
+    /// Sessions not updated for this many days are deleted. 0 keeps them forever.
+    #[serde(default = "default_session_max_age_days")]
+    pub session_max_age_days: u64,
+    /// Oldest sessions are deleted once all sessions together exceed this. 0 disables.
+    #[serde(default = "default_session_max_total_mb")]
+    pub session_max_total_mb: u64,
+    /// Only the most recent N sessions are kept. 0 disables.
+    #[serde(default)]
+    pub session_keep_last: usize,
+    /// Sessions not updated for this many days are gzipped. 0 disables.
+    #[serde(default)]
+    pub session_compress_after_days: u64,
+    /// Apply the session_* retention settings at startup. Off by default;
+    /// `barqcoder session gc` previews and applies them on demand.
+    #[serde(default)]
+    pub session_gc_on_start: bool,
+    /// How many times a step may be re-implemented after reviewer rejection.
+    #[serde(default = "default_max_review_rounds")]
+    pub max_review_rounds: u8,
+    /// What happens to a step the reviewer still rejects after `max_review_rounds`.
+    #[serde(default)]
+    pub on_review_rejected: RejectionPolicy,
+    /// Edits and plan steps are verified against the tests their changes can
+    /// reach; with this set, the whole test suite also runs once before an
+    /// answer is given.
+    #[serde(default)]
+    pub full_suite_before_answer: bool,
+    /// Agent roles to add or override; see also `.barqcoder/agents/*.toml`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub agents: Vec<RoleConfig>,
+}
+
+/// `escalate` leaves the rejected step's worktree in place for the user to
+/// inspect; `rollback` discards it, and so needs the workspace to be a git
+/// repository where steps run in worktrees.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RejectionPolicy {
+    #[default]
+    Escalate,
+    Rollback,
 }
 
 fn default_ollama_base_url() -> String { "http://localhost:11434".to_string() }
@@ -27,6 +72,10 @@ fn default_barqgraph_url() -> String { "localhost:6334".to_string() }
 fn default_workspace_root() -> String { "./".to_string() }
 fn default_max_iterations() -> u8 { 5 }
 fn default_token_limit() -> u32 { 4096 }
+fn default_max_parallel_steps() -> usize { 2 }
+fn default_session_max_age_days() -> u64 { 90 }
+fn default_session_max_total_mb() -> u64 { 512 }
+fn default_max_review_rounds() -> u8 { 3 }
 
 impl Default for Config {
     fn default() -> Self {
@@ -38,6 +87,16 @@ impl Default for Config {
             workspace_root: default_workspace_root(),
             max_iterations: default_max_iterations(),
             token_limit: default_token_limit(),
+            max_parallel_steps: default_max_parallel_steps(),
+            session_max_age_days: default_session_max_age_days(),
+            session_max_total_mb: default_session_max_total_mb(),
+            session_keep_last: 0,
+            session_compress_after_days: 0,
+            session_gc_on_start: false,
+            max_review_rounds: default_max_review_rounds(),
+            on_review_rejected: RejectionPolicy::default(),
+            full_suite_before_answer: false,
+            agents: Vec::new(),
         }
     }
 }
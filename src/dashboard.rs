@@ -0,0 +1,352 @@
+use crate::agents::coordinator::CoordinatorEvent;
+use crate::agents::reviewer::ReviewVerdict;
+use crate::macro_goals::{GoalManager, MacroGoalFile, Status};
+use ratatui::{prelude::*, widgets::*};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepState {
+    Waiting,
+    Running,
+    Merged,
+    Failed,
+}
+
+/// One plan step as the dashboard shows it.
+#[derive(Debug, Clone)]
+pub struct StepView {
+    pub id: String,
+    pub description: String,
+    pub depends_on: Vec<String>,
+    /// The agent currently (or last) working on the step.
+    pub agent: Option<String>,
+    /// Tool the agent is waiting on.
+    pub tool: Option<String>,
+    pub state: StepState,
+    pub started: Option<Instant>,
+    pub finished: Option<Instant>,
+    pub review_rounds: u8,
+    pub error: Option<String>,
+}
+
+impl StepView {
+    pub fn elapsed(&self) -> Option<Duration> {
+        let started = self.started?;
+        Some(self.finished.unwrap_or_else(Instant::now).duration_since(started))
+    }
+}
+
+/// Live view of multi-agent progress: the open macro goal, the coordinator's
+/// current plan, and a picker over saved goals.
+pub struct GoalDashboard {
+    pub visible: bool,
+    /// Macro goal file shown above the plan, if one is open.
+    pub goal_name: Option<String>,
+    pub goal: Option<MacroGoalFile>,
+    /// Goal text the current plan was made for.
+    pub plan_goal: Option<String>,
+    pub steps: Vec<StepView>,
+    pub last_verdict: Option<(String, u8, ReviewVerdict)>,
+    pub saved_goals: Vec<String>,
+    pub selected: usize,
+}
+
+impl Default for GoalDashboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GoalDashboard {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            goal_name: None,
+            goal: None,
+            plan_goal: None,
+            steps: Vec::new(),
+            last_verdict: None,
+            saved_goals: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn refresh_goals(&mut self, manager: &GoalManager) {
+        self.saved_goals = manager.list_goals();
+        self.selected = self.selected.min(self.saved_goals.len().saturating_sub(1));
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.saved_goals.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected_goal(&self) -> Option<&str> {
+        self.saved_goals.get(self.selected).map(String::as_str)
+    }
+
+    pub fn open(&mut self, manager: &GoalManager, name: &str) -> anyhow::Result<()> {
+        self.goal = Some(manager.load_goal(name)?);
+        self.goal_name = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Re-reads the open goal file, which the executor saves after every change.
+    pub fn reload(&mut self, manager: &GoalManager) {
+        if let Some(name) = &self.goal_name {
+            if let Ok(goal) = manager.load_goal(name) {
+                self.goal = Some(goal);
+            }
+        }
+    }
+
+    pub fn apply(&mut self, event: &CoordinatorEvent) {
+        match event {
+            CoordinatorEvent::PlanReady { goal, steps } => {
+                self.plan_goal = Some(goal.clone());
+                self.last_verdict = None;
+                self.steps = steps
+                    .iter()
+                    .map(|s| StepView {
+                        id: s.id.clone(),
+                        description: s.description.clone(),
+                        depends_on: s.depends_on.clone(),
+                        agent: None,
+                        tool: None,
+                        state: StepState::Waiting,
+                        started: None,
+                        finished: None,
+                        review_rounds: 0,
+                        error: None,
+                    })
+                    .collect();
+            }
+            CoordinatorEvent::StepStarted { step_id, .. } => {
+                if let Some(step) = self.step_mut(step_id) {
+                    step.state = StepState::Running;
+                    step.started = Some(Instant::now());
+                    step.finished = None;
+                    step.error = None;
+                }
+            }
+            CoordinatorEvent::AgentStarted { step_id, role } => {
+                if let Some(step) = self.step_mut(step_id) {
+                    step.agent = Some(role.clone());
+                    step.tool = None;
+                }
+            }
+            CoordinatorEvent::ToolCall { step_id, name, .. } => {
+                if let Some(step) = self.step_mut(step_id) {
+                    step.tool = Some(name.clone());
+                }
+            }
+            CoordinatorEvent::ToolResult { step_id, .. } => {
+                if let Some(step) = self.step_mut(step_id) {
+                    step.tool = None;
+                }
+            }
+            CoordinatorEvent::ReviewVerdict { step_id, round, verdict } => {
+                if let Some(step) = self.step_mut(step_id) {
+                    step.review_rounds = *round;
+                }
+                self.last_verdict = Some((step_id.clone(), *round, verdict.clone()));
+            }
+            CoordinatorEvent::StepFinished { step_id, error } => {
+                if let Some(step) = self.step_mut(step_id) {
+                    step.state = if error.is_some() { StepState::Failed } else { StepState::Merged };
+                    step.finished = Some(Instant::now());
+                    step.error = error.clone();
+                    step.tool = None;
+                }
+            }
+            CoordinatorEvent::AgentToken { .. }
+            | CoordinatorEvent::FullSuiteVerified { .. }
+            | CoordinatorEvent::GoalFinished { .. } => {}
+        }
+    }
+
+    fn step_mut(&mut self, id: &str) -> Option<&mut StepView> {
+        self.steps.iter_mut().find(|s| s.id == id)
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(25), Constraint::Percentage(75)])
+            .split(area);
+
+        let items: Vec<ListItem> = self
+            .saved_goals
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let style = if i == self.selected {
+                    Style::default().bg(Color::DarkGray).fg(Color::White)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(name.clone()).style(style)
+            })
+            .collect();
+        let picker = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Goals [↑↓ select, → open, ⏎ resume] "),
+        );
+        f.render_widget(picker, columns[0]);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(40),
+                Constraint::Percentage(40),
+                Constraint::Percentage(20),
+            ])
+            .split(columns[1]);
+
+        let goal_title = match &self.goal_name {
+            Some(name) => format!(" Goal: {} ", name),
+            None => " Goal ".to_string(),
+        };
+        let goal = Paragraph::new(self.goal_lines())
+            .block(Block::default().borders(Borders::ALL).title(goal_title))
+            .wrap(Wrap { trim: false });
+        f.render_widget(goal, rows[0]);
+
+        let plan_title = match &self.plan_goal {
+            Some(goal) => format!(" Plan: {} ", goal),
+            None => " Plan ".to_string(),
+        };
+        let plan = Paragraph::new(self.step_lines())
+            .block(Block::default().borders(Borders::ALL).title(plan_title))
+            .wrap(Wrap { trim: false });
+        f.render_widget(plan, rows[1]);
+
+        let verdict = Paragraph::new(self.verdict_lines())
+            .block(Block::default().borders(Borders::ALL).title(" Latest review "))
+            .wrap(Wrap { trim: false });
+        f.render_widget(verdict, rows[2]);
+    }
+
+    fn goal_lines(&self) -> Vec<Line<'static>> {
+        let Some(goal) = &self.goal else {
+            return vec![Line::from("No goal open.")];
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut lines = Vec::new();
+        for phase in &goal.phases {
+            lines.push(Line::from(vec![
+                status_span(phase.status),
+                Span::styled(format!(" {}", phase.name), Style::default().add_modifier(Modifier::BOLD)),
+            ]));
+            for task in &phase.tasks {
+                let elapsed = match (task.started_at, task.finished_at) {
+                    (Some(start), Some(end)) => format_elapsed(Duration::from_secs(end.saturating_sub(start))),
+                    (Some(start), None) if task.status == Status::Running => {
+                        format_elapsed(Duration::from_secs(now.saturating_sub(start)))
+                    }
+                    _ => String::new(),
+                };
+                let mut spans = vec![
+                    Span::raw("  "),
+                    status_span(task.status),
+                    Span::raw(format!(" {} {} {}", task.id, task.description, elapsed)),
+                ];
+                if let Some(err) = &task.last_error {
+                    if task.status == Status::Failed {
+                        spans.push(Span::styled(format!(" — {}", err), Style::default().fg(Color::Red)));
+                    }
+                }
+                lines.push(Line::from(spans));
+            }
+        }
+        lines
+    }
+
+    fn step_lines(&self) -> Vec<Line<'static>> {
+        if self.steps.is_empty() {
+            return vec![Line::from("No plan yet.")];
+        }
+        self.steps
+            .iter()
+            .map(|step| {
+                let (label, color) = match step.state {
+                    StepState::Waiting => ("waiting", Color::DarkGray),
+                    StepState::Running => ("running", Color::Yellow),
+                    StepState::Merged => ("merged", Color::Green),
+                    StepState::Failed => ("failed", Color::Red),
+                };
+                let mut spans = vec![
+                    Span::styled(format!("[{}]", label), Style::default().fg(color)),
+                    Span::raw(format!(" {} {}", step.id, step.description)),
+                ];
+                if let Some(agent) = &step.agent {
+                    if step.state == StepState::Running {
+                        let label = match &step.tool {
+                            Some(tool) => format!(" ({}: {})", agent, tool),
+                            None => format!(" ({})", agent),
+                        };
+                        spans.push(Span::styled(label, Style::default().fg(Color::Cyan)));
+                    }
+                }
+                if step.state == StepState::Waiting && !step.depends_on.is_empty() {
+                    spans.push(Span::raw(format!(" after {}", step.depends_on.join(", "))));
+                }
+                if step.review_rounds > 1 {
+                    spans.push(Span::raw(format!(" round {}", step.review_rounds)));
+                }
+                if let Some(elapsed) = step.elapsed() {
+                    spans.push(Span::raw(format!(" {}", format_elapsed(elapsed))));
+                }
+                if let Some(err) = &step.error {
+                    spans.push(Span::styled(format!(" — {}", err), Style::default().fg(Color::Red)));
+                }
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    fn verdict_lines(&self) -> Vec<Line<'static>> {
+        let Some((step_id, round, verdict)) = &self.last_verdict else {
+            return vec![Line::from("No review yet.")];
+        };
+        let (label, color) = if verdict.approved {
+            ("approved", Color::Green)
+        } else {
+            ("rejected", Color::Red)
+        };
+        let mut lines = vec![Line::from(vec![
+            Span::raw(format!("{} round {}: ", step_id, round)),
+            Span::styled(label, Style::default().fg(color)),
+        ])];
+        for line in verdict.feedback().lines() {
+            lines.push(Line::from(line.to_string()));
+        }
+        lines
+    }
+}
+
+fn status_span(status: Status) -> Span<'static> {
+    let color = match status {
+        Status::Pending => Color::DarkGray,
+        Status::Running => Color::Yellow,
+        Status::Blocked => Color::Magenta,
+        Status::Done => Color::Green,
+        Status::Failed => Color::Red,
+        Status::Skipped => Color::Blue,
+    };
+    Span::styled(format!("[{}]", status), Style::default().fg(color))
+}
+
+fn format_elapsed(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
@@ -1,9 +1,14 @@
 pub mod agent;
+pub mod agents;
 pub mod barq;
 pub mod config;
+pub mod dashboard;
 pub mod collab;
 pub mod lsp;
+pub mod macro_goals;
 pub mod orchestrator;
+pub mod replay;
+pub mod session;
 pub mod tools;
 pub mod symbolic;
 pub mod voice;
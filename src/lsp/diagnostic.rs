@@ -1,6 +1,35 @@
+use crate::symbolic::{self, diagnostic, rules::{RuleSet, Severity}};
 use tower_lsp::lsp_types::*;
 
-pub fn get_diagnostics(_text: &str) -> Vec<Diagnostic> {
-    // Mock diagnostics from symbolic verifier
-    vec![]
+/// Symbolic verifier findings for a document at workspace-relative `path`.
+pub fn get_diagnostics(path: &str, text: &str, rules: &RuleSet) -> Vec<Diagnostic> {
+    let (report, _) = symbolic::analyze(path, text, rules);
+    report.diagnostics.iter().map(to_lsp).collect()
+}
+
+/// LSP positions are 0-based; a finding without a span sits at the top of the file.
+pub fn to_lsp(d: &diagnostic::Diagnostic) -> Diagnostic {
+    let position = |p: diagnostic::LineColumn| Position::new(p.line.saturating_sub(1) as u32, p.column as u32);
+    let range = d
+        .span
+        .map(|s| Range::new(position(s.start), position(s.end)))
+        .unwrap_or_default();
+    let severity = match d.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warn => DiagnosticSeverity::WARNING,
+        Severity::Info | Severity::Off => DiagnosticSeverity::INFORMATION,
+    };
+    let message = match &d.fix {
+        Some(fix) => format!("{}\n{}", d.message, fix.description),
+        None => d.message.clone(),
+    };
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        code: Some(NumberOrString::String(d.rule_id.clone())),
+        source: Some("barqcoder".to_string()),
+        message,
+        data: d.fix.as_ref().and_then(|fix| serde_json::to_value(fix).ok()),
+        ..Default::default()
+    }
 }
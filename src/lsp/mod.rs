@@ -1,6 +1,9 @@
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
+use crate::symbolic::rules::RuleSet;
+use std::path::PathBuf;
+use std::sync::RwLock;
 
 pub mod hover;
 pub mod completion;
@@ -10,11 +13,23 @@ pub mod codeaction;
 #[derive(Debug)]
 pub struct Backend {
     pub client: Client,
+    /// The workspace the client opened, where `rules.yaml` is looked up and
+    /// which diagnostic paths are relative to. Set by `initialize`.
+    pub root: RwLock<Option<PathBuf>>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let root = params
+            .workspace_folders
+            .as_ref()
+            .and_then(|folders| folders.first())
+            .map(|folder| &folder.uri)
+            .or(params.root_uri.as_ref())
+            .and_then(|uri| uri.to_file_path().ok());
+        *self.root.write().unwrap() = root;
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
@@ -48,12 +63,49 @@ impl LanguageServer for Backend {
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
         codeaction::handle_code_action(self, params).await
     }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let doc = params.text_document;
+        self.publish_diagnostics(doc.uri, &doc.text, doc.version).await;
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        // Full sync: the last change holds the whole document.
+        if let Some(change) = params.content_changes.into_iter().last() {
+            let doc = params.text_document;
+            self.publish_diagnostics(doc.uri, &change.text, doc.version).await;
+        }
+    }
+}
+
+impl Backend {
+    async fn publish_diagnostics(&self, uri: Url, text: &str, version: i32) {
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        if path.extension().and_then(|e| e.to_str()) != Some("rs") {
+            return;
+        }
+        let root = self
+            .root
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+        let rules = RuleSet::load(&root).unwrap_or_default();
+        let relative = path.strip_prefix(&root).unwrap_or(&path).to_string_lossy().to_string();
+        let diagnostics = diagnostic::get_diagnostics(&relative, text, &rules);
+        self.client.publish_diagnostics(uri, diagnostics, Some(version)).await;
+    }
 }
 
 pub async fn start_lsp() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(|client| Backend { client });
+    let (service, socket) = LspService::new(|client| Backend {
+        client,
+        root: RwLock::new(None),
+    });
     Server::new(stdin, stdout, socket).serve(service).await;
 }
@@ -1,28 +1,295 @@
+use crate::agents::coordinator::CoordinatorAgent;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MacroGoalFile {
     pub name: String,
+    #[serde(default)]
     pub description: String,
     pub phases: Vec<Phase>,
+    /// The file the goal was loaded from, which it is saved back to even
+    /// when `name` differs from the file's stem.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Phase {
     pub name: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(default)]
     pub tasks: Vec<Task>,
-    pub status: String,
+    #[serde(default)]
+    pub status: Status,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Task {
     pub id: String,
     pub description: String,
-    pub status: String,
+    #[serde(default)]
+    pub status: Status,
+    #[serde(default)]
     pub command: Option<String>,
+    /// Ids of tasks in this or an earlier phase that must be done first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Unix seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Files the task changed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<String>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Progress of a phase or task. Reads the free-form strings older goal files
+/// used ("in_progress", "completed", ...); anything unrecognised is pending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Status {
+    #[default]
+    Pending,
+    Running,
+    Blocked,
+    Done,
+    Failed,
+    Skipped,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Blocked => "blocked",
+            Self::Done => "done",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+
+    /// Done or deliberately skipped; dependents may proceed.
+    pub fn is_settled(&self) -> bool {
+        matches!(self, Self::Done | Self::Skipped)
+    }
+}
+
+impl From<String> for Status {
+    fn from(s: String) -> Self {
+        match s.trim().to_ascii_lowercase().replace(['-', ' '], "_").as_str() {
+            "running" | "in_progress" | "active" | "started" => Self::Running,
+            "blocked" => Self::Blocked,
+            "done" | "complete" | "completed" | "finished" | "success" => Self::Done,
+            "failed" | "error" => Self::Failed,
+            "skipped" | "skip" => Self::Skipped,
+            _ => Self::Pending,
+        }
+    }
+}
+
+impl From<Status> for String {
+    fn from(s: Status) -> Self {
+        s.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A problem found in a goal file, with the YAML line it was found on when known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalIssue {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct GoalValidationError {
+    pub goal: String,
+    pub issues: Vec<GoalIssue>,
+}
+
+impl std::fmt::Display for GoalValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "goal {} is invalid:", self.goal)?;
+        for issue in &self.issues {
+            match issue.line {
+                Some(line) => write!(f, "\n  line {}: {}", line, issue.message)?,
+                None => write!(f, "\n  {}", issue.message)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GoalValidationError {}
+
+impl MacroGoalFile {
+    pub fn tasks(&self) -> impl Iterator<Item = &Task> {
+        self.phases.iter().flat_map(|p| p.tasks.iter())
+    }
+
+    /// Checks for duplicate task ids, dependencies on unknown or later tasks,
+    /// and dependency cycles. `source` is the YAML the goal was read from and
+    /// is only used to point at line numbers.
+    pub fn validate(&self, source: &str) -> Vec<GoalIssue> {
+        let mut issues = Vec::new();
+        let mut phase_of: HashMap<&str, usize> = HashMap::new();
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for (p, phase) in self.phases.iter().enumerate() {
+            for task in &phase.tasks {
+                let count = seen.entry(task.id.as_str()).or_insert(0);
+                *count += 1;
+                if phase_of.insert(task.id.as_str(), p).is_some() {
+                    issues.push(GoalIssue {
+                        line: find_id_line(source, &task.id, *count),
+                        message: format!("duplicate task id {}", task.id),
+                    });
+                }
+            }
+        }
+
+        for (p, phase) in self.phases.iter().enumerate() {
+            for task in &phase.tasks {
+                for dep in &task.depends_on {
+                    let line = find_dependency_line(source, &task.id, dep);
+                    match phase_of.get(dep.as_str()) {
+                        None => issues.push(GoalIssue {
+                            line,
+                            message: format!("task {} depends on unknown task {}", task.id, dep),
+                        }),
+                        Some(&dep_phase) if dep_phase > p => issues.push(GoalIssue {
+                            line,
+                            message: format!(
+                                "task {} depends on {}, which is in a later phase",
+                                task.id, dep
+                            ),
+                        }),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if let Some(cycle) = self.find_cycle() {
+            issues.push(GoalIssue {
+                line: find_id_line(source, &cycle[0], 1),
+                message: format!("dependency cycle {}", cycle.join(" -> ")),
+            });
+        }
+        issues
+    }
+
+    fn find_cycle(&self) -> Option<Vec<String>> {
+        let deps: HashMap<&str, &[String]> = self
+            .tasks()
+            .map(|t| (t.id.as_str(), t.depends_on.as_slice()))
+            .collect();
+        let mut done: HashSet<&str> = HashSet::new();
+
+        fn visit<'a>(
+            id: &'a str,
+            deps: &HashMap<&'a str, &'a [String]>,
+            done: &mut HashSet<&'a str>,
+            path: &mut Vec<&'a str>,
+        ) -> Option<Vec<String>> {
+            if let Some(start) = path.iter().position(|p| *p == id) {
+                let mut cycle: Vec<String> = path[start..].iter().map(|s| s.to_string()).collect();
+                cycle.push(id.to_string());
+                return Some(cycle);
+            }
+            if done.contains(id) {
+                return None;
+            }
+            path.push(id);
+            for dep in deps.get(id).copied().unwrap_or(&[]) {
+                if let Some(cycle) = visit(dep, deps, done, path) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+            done.insert(id);
+            None
+        }
+
+        for task in self.tasks() {
+            if let Some(cycle) = visit(&task.id, &deps, &mut done, &mut Vec::new()) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+}
+
+/// 1-based line of the `nth` (1-based) `id: <id>` entry in `source`.
+fn find_id_line(source: &str, id: &str, nth: usize) -> Option<usize> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| yaml_value_of(line, "id").as_deref() == Some(id))
+        .nth(nth - 1)
+        .map(|(i, _)| i + 1)
+}
+
+/// 1-based line where `task` lists `dep`, falling back to the task's own line.
+fn find_dependency_line(source: &str, task: &str, dep: &str) -> Option<usize> {
+    let start = find_id_line(source, task, 1)?;
+    let lines: Vec<&str> = source.lines().collect();
+    for (i, line) in lines.iter().enumerate().skip(start - 1) {
+        if i + 1 > start && yaml_value_of(line, "id").is_some() {
+            break;
+        }
+        let mentions = line
+            .split(['[', ']', ',', '{', '}', ':'])
+            .map(|part| part.trim().trim_start_matches("- ").trim_matches(['"', '\'']))
+            .any(|part| part == dep);
+        if mentions && (line.contains("depends_on") || line.trim_start().starts_with('-')) {
+            return Some(i + 1);
+        }
+    }
+    Some(start)
+}
+
+/// The scalar value of `key` on a YAML line, whether written as a block
+/// mapping (`- id: x`) or inside a flow mapping (`{ id: x, ... }`).
+fn yaml_value_of(line: &str, key: &str) -> Option<String> {
+    let pattern = format!("{}:", key);
+    let mut search = line;
+    while let Some(pos) = search.find(&pattern) {
+        let before = search[..pos].chars().last();
+        let rest = &search[pos + pattern.len()..];
+        if matches!(before, None | Some(' ') | Some('{') | Some(',') | Some('-')) {
+            let value: String = rest
+                .trim_start()
+                .chars()
+                .take_while(|c| *c != ',' && *c != '}' && *c != '#')
+                .collect();
+            return Some(value.trim().trim_matches(['"', '\'']).to_string());
+        }
+        search = rest;
+    }
+    None
 }
 
 pub struct GoalManager {
@@ -36,16 +303,32 @@ impl GoalManager {
         Self { goals_dir: dir }
     }
 
+    /// Loads and validates a goal. Validation failures are returned as a
+    /// `GoalValidationError` listing every issue.
     pub fn load_goal(&self, name: &str) -> anyhow::Result<MacroGoalFile> {
         let path = self.goals_dir.join(format!("{}.yaml", name));
-        let content = fs::read_to_string(path)?;
-        let goal: MacroGoalFile = serde_yaml::from_str(&content)?;
+        let content = fs::read_to_string(&path)?;
+        let mut goal: MacroGoalFile = serde_yaml::from_str(&content)?;
+        goal.source = Some(path);
+        let issues = goal.validate(&content);
+        if !issues.is_empty() {
+            return Err(GoalValidationError {
+                goal: name.to_string(),
+                issues,
+            }
+            .into());
+        }
         Ok(goal)
     }
 
+    /// Writes `goal` back to the file it came from, or to `<name>.yaml` for
+    /// a new goal.
     pub fn save_goal(&self, goal: &MacroGoalFile) -> anyhow::Result<()> {
         let content = serde_yaml::to_string(goal)?;
-        let path = self.goals_dir.join(format!("{}.yaml", goal.name));
+        let path = match &goal.source {
+            Some(source) => source.clone(),
+            None => self.goals_dir.join(format!("{}.yaml", goal.name)),
+        };
         fs::write(path, content)?;
         Ok(())
     }
@@ -61,6 +344,202 @@ impl GoalManager {
                 }
             }
         }
+        goals.sort();
         goals
     }
 }
+
+/// Carries out a task that has no `command`. Implemented by `CoordinatorAgent`,
+/// which plans and runs the description as a goal.
+#[async_trait]
+pub trait TaskDelegate: Send + Sync {
+    async fn run_task(&self, description: &str) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl TaskDelegate for CoordinatorAgent {
+    async fn run_task(&self, description: &str) -> anyhow::Result<()> {
+        self.execute_goal(description).await
+    }
+}
+
+/// Walks a goal file phase by phase, running each task once everything it
+/// depends on is done, and saves the file after every status change.
+/// Finished and skipped tasks are not run again, so running an interrupted or
+/// failed goal again continues from the first unfinished task.
+pub struct GoalExecutor {
+    pub manager: GoalManager,
+    pub delegate: Arc<dyn TaskDelegate>,
+    pub workspace: String,
+}
+
+impl GoalExecutor {
+    pub fn new(workspace: &str, delegate: Arc<dyn TaskDelegate>) -> Self {
+        Self {
+            manager: GoalManager::new(workspace),
+            delegate,
+            workspace: workspace.to_string(),
+        }
+    }
+
+    /// Loads the named goal and runs it to completion or to its first failing task.
+    pub async fn resume(&self, name: &str, progress: &mut (dyn FnMut(String) + Send)) -> anyhow::Result<()> {
+        let mut goal = self.manager.load_goal(name)?;
+        self.run(&mut goal, progress).await
+    }
+
+    pub async fn run(&self, goal: &mut MacroGoalFile, progress: &mut (dyn FnMut(String) + Send)) -> anyhow::Result<()> {
+        for p in 0..goal.phases.len() {
+            if goal.phases[p].status.is_settled() {
+                continue;
+            }
+            goal.phases[p].status = Status::Running;
+            progress(format!("Phase {}: {}", goal.phases[p].name, goal.phases[p].description));
+
+            loop {
+                let settled: HashSet<String> = goal
+                    .tasks()
+                    .filter(|t| t.status.is_settled())
+                    .map(|t| t.id.clone())
+                    .collect();
+                let next = goal.phases[p].tasks.iter().position(|t| {
+                    !t.status.is_settled() && t.depends_on.iter().all(|d| settled.contains(d))
+                });
+                let Some(t) = next else {
+                    break;
+                };
+
+                let task = &mut goal.phases[p].tasks[t];
+                task.status = Status::Running;
+                task.started_at = Some(now_secs());
+                task.finished_at = None;
+                task.attempts += 1;
+                self.manager.save_goal(goal)?;
+
+                let task = &goal.phases[p].tasks[t];
+                progress(format!("Task {}: {}", task.id, task.description));
+                let before = self.snapshot().await;
+                let result = match &task.command {
+                    Some(command) => self.run_command(command).await,
+                    None => self.delegate.run_task(&task.description).await,
+                };
+                let artifacts = self.changed_since(&before).await;
+
+                let task = &mut goal.phases[p].tasks[t];
+                task.finished_at = Some(now_secs());
+                for file in artifacts {
+                    if !task.artifacts.contains(&file) {
+                        task.artifacts.push(file);
+                    }
+                }
+                match result {
+                    Ok(()) => {
+                        task.status = Status::Done;
+                        task.last_error = None;
+                        self.manager.save_goal(goal)?;
+                    }
+                    Err(e) => {
+                        task.status = Status::Failed;
+                        task.last_error = Some(e.to_string());
+                        let id = task.id.clone();
+                        goal.phases[p].status = Status::Failed;
+                        self.manager.save_goal(goal)?;
+                        anyhow::bail!("task {} failed: {}", id, e);
+                    }
+                }
+            }
+
+            let blocked: Vec<String> = goal.phases[p]
+                .tasks
+                .iter()
+                .filter(|t| !t.status.is_settled())
+                .map(|t| t.id.clone())
+                .collect();
+            if !blocked.is_empty() {
+                for task in goal.phases[p].tasks.iter_mut().filter(|t| !t.status.is_settled()) {
+                    task.status = Status::Blocked;
+                }
+                goal.phases[p].status = Status::Blocked;
+                self.manager.save_goal(goal)?;
+                anyhow::bail!("tasks {} are blocked on unfinished dependencies", blocked.join(", "));
+            }
+
+            goal.phases[p].status = Status::Done;
+            self.manager.save_goal(goal)?;
+        }
+        Ok(())
+    }
+
+    async fn run_command(&self, command: &str) -> anyhow::Result<()> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.workspace)
+            .output()
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+            anyhow::bail!(
+                "`{}` exited with {}: {}",
+                command,
+                output.status.code().unwrap_or(-1),
+                tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+            );
+        }
+        Ok(())
+    }
+
+    async fn git(&self, args: &[&str]) -> Option<String> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&self.workspace)
+            .output()
+            .await
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// HEAD and the dirty files, so a task's own changes can be told apart.
+    async fn snapshot(&self) -> (Option<String>, HashSet<String>) {
+        let head = self.git(&["rev-parse", "HEAD"]).await.map(|s| s.trim().to_string());
+        (head, self.dirty_files().await)
+    }
+
+    async fn dirty_files(&self) -> HashSet<String> {
+        self.git(&["status", "--porcelain"])
+            .await
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| l.get(3..))
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    /// Files committed or newly modified since `before`. Empty outside git.
+    /// The goal files themselves are left out.
+    async fn changed_since(&self, before: &(Option<String>, HashSet<String>)) -> Vec<String> {
+        let (head, dirty) = before;
+        let mut files = Vec::new();
+        if let Some(head) = head {
+            if let Some(out) = self.git(&["diff", "--name-only", head, "HEAD"]).await {
+                files.extend(out.lines().map(|l| l.to_string()));
+            }
+        }
+        files.extend(self.dirty_files().await.into_iter().filter(|f| !dirty.contains(f)));
+        files.retain(|f| !f.starts_with(".barqcoder/"));
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -9,8 +9,8 @@ use ratatui::{
     widgets::{Block, Borders, Paragraph, Wrap},
     Terminal,
 };
-use std::{io, sync::Arc, time::Duration};
-use tokio::sync::mpsc;
+use std::{collections::HashMap, io, sync::Arc, time::Duration};
+use tokio::sync::{broadcast, mpsc};
 use tokio::net::TcpListener;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
@@ -19,9 +19,11 @@ mod agents;
 mod barq;
 mod collab;
 mod config;
+mod dashboard;
 mod lsp;
 mod macro_goals;
 mod orchestrator;
+mod replay;
 mod session;
 mod symbolic;
 mod tools;
@@ -29,12 +31,20 @@ mod tui;
 mod verifier;
 mod voice;
 
-use agent::OllamaClient;
+use agent::{LlmProvider, OllamaClient};
 use barq::BarqIndex;
 use config::Config;
 use orchestrator::{Orchestrator, OrchestratorEvent};
+use replay::{RecordingProvider, ReplayProvider};
+use session::export::ExportFormat;
+use session::retention::{GcAction, RetentionPolicy};
+use session::{Session, SessionEvent, SessionStore};
+use tools::workspace::WorkspaceManager;
 use tools::ToolRegistry;
-use agents::coordinator::CoordinatorAgent;
+use agents::coordinator::{CoordinatorAgent, CoordinatorEvent};
+use dashboard::GoalDashboard;
+use agents::roles::{RoleRegistry, RoleSpec};
+use macro_goals::{GoalExecutor, GoalManager, TaskDelegate};
 
 struct App {
     input: String,
@@ -51,28 +61,78 @@ struct App {
     current_tool: Option<String>,
     token_count: u32,
     session_id: String,
+    session: Session,
+    session_store: SessionStore,
+    pending_tool_args: HashMap<String, serde_json::Value>,
     
     // Agent orchestration
     coordinator: Arc<CoordinatorAgent>,
+    coordinator_rx: broadcast::Receiver<CoordinatorEvent>,
+    dashboard: GoalDashboard,
 
     // Channels for async operations
     event_rx: Option<mpsc::Receiver<OrchestratorEvent>>,
 }
 
+/// How the LLM is reached: live, live with every exchange recorded into the
+/// session cassette, or served entirely from a previous session's cassette.
+enum LlmMode {
+    Live,
+    Record,
+    Replay(String),
+}
+
 impl App {
-    fn new() -> Self {
+    fn new(mode: LlmMode, seed: Option<u64>) -> anyhow::Result<Self> {
         let config = Config::load();
-        let agent = OllamaClient::new(&config.ollama_base_url, &config.ollama_model);
-        
+
         // Setup barq index
         let barq = Arc::new(BarqIndex::new(&config).expect("Failed to create BarqIndex"));
+
+        let session_id = format!("session_{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs());
+        let session = Session::new(&session_id, &config.workspace_root);
+        let session_store = SessionStore::with_barq(&config.workspace_root, Arc::clone(&barq));
+
+        let live: Arc<dyn LlmProvider> = Arc::new(
+            OllamaClient::new(&config.ollama_base_url, &config.ollama_model).with_seed(seed),
+        );
+        let replaying = matches!(mode, LlmMode::Replay(_));
+        let (agent, recorder): (Arc<dyn LlmProvider>, Option<RecordingProvider>) = match mode {
+            LlmMode::Live => (live, None),
+            LlmMode::Record => {
+                let recorder = RecordingProvider::new(live.clone(), &session_store.cassette_path(&session_id));
+                (Arc::new(recorder.wrap(live)), Some(recorder))
+            }
+            LlmMode::Replay(id) => (Arc::new(ReplayProvider::load(&session_store.cassette_path(&id))?), None),
+        };
+
+        // Every agent role gets its own client so roles can run on different
+        // models. Replays serve all roles from the one cassette.
+        let providers = |role: &RoleSpec| -> anyhow::Result<Arc<dyn LlmProvider>> {
+            if replaying {
+                return Ok(Arc::clone(&agent));
+            }
+            let live: Arc<dyn LlmProvider> = Arc::new(
+                OllamaClient::new(&role.base_url, &role.model)
+                    .with_seed(seed)
+                    .with_temperature(role.temperature),
+            );
+            Ok(match &recorder {
+                Some(recorder) => Arc::new(recorder.wrap(live)),
+                None => live,
+            })
+        };
+        let roles = RoleRegistry::load(
+            &config,
+            &std::path::Path::new(&config.workspace_root).join(".barqcoder").join("agents"),
+        )?;
         
         let tools = Arc::new(ToolRegistry::with_barq(Arc::clone(&barq)));
         
-        let orchestrator = Orchestrator::new(agent.clone(), Arc::clone(&tools), Arc::clone(&barq), config.clone());
-        let coordinator = Arc::new(CoordinatorAgent::new(agent, Arc::clone(&barq), tools));
+        let orchestrator = Orchestrator::new(Arc::clone(&agent), Arc::clone(&tools), Arc::clone(&barq), config.clone());
+        let coordinator = Arc::new(CoordinatorAgent::new(&roles, &providers, Arc::clone(&barq), tools, &config)?);
 
-        Self {
+        Ok(Self {
             input: String::new(),
             messages: Vec::new(),
             tool_log: Vec::new(),
@@ -84,9 +144,85 @@ impl App {
             is_thinking: false,
             current_tool: None,
             token_count: 0,
-            session_id: format!("session_{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()),
+            session_id,
+            session,
+            session_store,
+            pending_tool_args: HashMap::new(),
+            coordinator_rx: coordinator.subscribe(),
             coordinator,
+            dashboard: GoalDashboard::new(),
             event_rx: None,
+        })
+    }
+
+    fn toggle_dashboard(&mut self) {
+        self.dashboard.visible = !self.dashboard.visible;
+        if self.dashboard.visible {
+            self.dashboard.refresh_goals(&GoalManager::new(&self.config.workspace_root));
+        }
+    }
+
+    fn open_goal(&mut self, name: &str) {
+        let manager = GoalManager::new(&self.config.workspace_root);
+        self.dashboard.refresh_goals(&manager);
+        if let Err(e) = self.dashboard.open(&manager, name) {
+            self.messages.push(format!("Error: could not open goal {}: {}", name, e));
+        }
+    }
+
+    /// Enforces the retention settings, never touching the protected sessions
+    /// or the current one.
+    fn collect_sessions(&self, protected: Vec<String>) {
+        let policy = RetentionPolicy::from_config(&self.config)
+            .with_protected(protected.into_iter().chain([self.session_id.clone()]));
+        let plan = self.session_store.plan_gc(&policy);
+        if let Err(e) = self.session_store.apply_gc(&plan) {
+            tracing::warn!("Session retention failed: {}", e);
+        }
+    }
+
+    fn resume(&mut self, id: &str) -> anyhow::Result<()> {
+        let session = self.session_store.load(id)?;
+        self.orchestrator.resume(&session);
+
+        if !session.workspace.is_empty() {
+            let manager = WorkspaceManager::new(".");
+            if manager.list().iter().any(|w| w.path == session.workspace) {
+                manager.switch(&session.workspace)?;
+            }
+            self.config.workspace_root = session.workspace.clone();
+        }
+
+        self.messages.clear();
+        self.tool_log.clear();
+        for msg in &self.orchestrator.conversation {
+            match msg.role.as_str() {
+                "user" => self.messages.push(format!("You: {}", msg.content)),
+                "assistant" if !msg.content.is_empty() => {
+                    self.messages.push(format!("Agent: {}", msg.content))
+                }
+                "tool" => self.tool_log.push(format!(
+                    "Result for {}: {}",
+                    msg.tool_call_id.as_deref().unwrap_or("?"),
+                    msg.content
+                )),
+                _ => {}
+            }
+        }
+        self.messages.push(format!(
+            "Resumed session {} ({} events).",
+            session.id,
+            session.events.len()
+        ));
+
+        self.session_id = session.id.clone();
+        self.session = session;
+        Ok(())
+    }
+
+    fn save_session(&mut self) {
+        if let Err(e) = self.session_store.save(&self.session) {
+            self.tool_log.push(format!("Failed to save session: {}", e));
         }
     }
 }
@@ -96,22 +232,53 @@ async fn main() -> anyhow::Result<()> {
     let args: Vec<String> = std::env::args().collect();
     
     // Deterministic Mode
-    let mut _seed = None;
+    let mut seed = None;
     if let Some(idx) = args.iter().position(|a| a == "--seed") {
         if let Some(val) = args.get(idx + 1) {
-            _seed = val.parse::<u64>().ok();
+            seed = val.parse::<u64>().ok();
+        }
+    }
+
+    let llm_mode = if let Some(idx) = args.iter().position(|a| a == "--replay") {
+        match args.get(idx + 1) {
+            Some(id) => LlmMode::Replay(id.clone()),
+            None => anyhow::bail!("--replay requires a session id"),
         }
+    } else if args.iter().any(|a| a == "--record") {
+        LlmMode::Record
+    } else {
+        LlmMode::Live
+    };
+
+    let resume_id = args
+        .iter()
+        .position(|a| a == "--resume")
+        .and_then(|idx| args.get(idx + 1))
+        .cloned();
+
+    if args.get(1).map(String::as_str) == Some("session") {
+        return session_command(&args[2..]);
     }
 
     tokio::spawn(start_health_server());
 
     if args.iter().any(|arg| arg == "--lsp") {
         lsp::start_lsp().await;
+        verifier::remove_mirrors();
         return Ok(());
     }
 
     tracing_subscriber::fmt().init();
 
+    // Sessions this run reads from must survive retention.
+    let mut in_use: Vec<String> = resume_id.iter().cloned().collect();
+    if let LlmMode::Replay(id) = &llm_mode {
+        in_use.push(id.clone());
+    }
+
+    // Build the app before touching the terminal so startup errors print normally
+    let mut app = App::new(llm_mode, seed)?;
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -119,9 +286,18 @@ async fn main() -> anyhow::Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new();
+    if let Some(id) = resume_id {
+        if let Err(e) = app.resume(&id) {
+            app.messages.push(format!("Error: could not resume {}: {}", id, e));
+        }
+    }
+
+    if app.config.session_gc_on_start {
+        app.collect_sessions(in_use);
+    }
 
     let res = run_app(&mut terminal, &mut app).await;
+    verifier::remove_mirrors();
 
     // Restore terminal
     disable_raw_mode()?;
@@ -145,8 +321,27 @@ async fn run_app<B: ratatui::backend::Backend>(
             if let Event::Key(key) = event::read()? {
                 match key.code {
                     KeyCode::Enter => {
+                        if app.dashboard.visible && app.input.is_empty() {
+                            if let Some(name) = app.dashboard.selected_goal() {
+                                app.input = format!("/goal resume {}", name);
+                            }
+                        }
                         handle_input(app);
                     }
+                    KeyCode::Tab => {
+                        app.toggle_dashboard();
+                    }
+                    KeyCode::Up if app.dashboard.visible => {
+                        app.dashboard.select_prev();
+                    }
+                    KeyCode::Down if app.dashboard.visible => {
+                        app.dashboard.select_next();
+                    }
+                    KeyCode::Right if app.dashboard.visible => {
+                        if let Some(name) = app.dashboard.selected_goal().map(str::to_string) {
+                            app.open_goal(&name);
+                        }
+                    }
                     KeyCode::Char(c) => {
                         app.input.push(c);
                     }
@@ -161,12 +356,32 @@ async fn run_app<B: ratatui::backend::Backend>(
             }
         }
 
+        // Coordinator progress feeds the dashboard; goal files are re-read
+        // because the executor saves them as tasks move.
+        let mut coordinator_progress = false;
+        loop {
+            match app.coordinator_rx.try_recv() {
+                Ok(event) => {
+                    app.dashboard.apply(&event);
+                    handle_coordinator_event(app, event);
+                    coordinator_progress = true;
+                }
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
+        }
+        if coordinator_progress || app.event_rx.is_some() {
+            let manager = GoalManager::new(&app.config.workspace_root);
+            app.dashboard.reload(&manager);
+        }
+
         // Process orchestrator events
         if let Some(rx) = &mut app.event_rx {
             while let Ok(event) = rx.try_recv() {
                 match event {
                     OrchestratorEvent::Token(t) => {
                         app.is_thinking = true;
+                        app.session.push(SessionEvent::AgentToken(t.clone()));
                         if let Some(last) = app.messages.last_mut() {
                             if last.starts_with("Agent:") {
                                 last.push_str(&t);
@@ -177,18 +392,38 @@ async fn run_app<B: ratatui::backend::Backend>(
                             app.messages.push(format!("Agent: {}", t));
                         }
                     }
-                    OrchestratorEvent::ToolCall { name, args } => {
+                    OrchestratorEvent::ToolCall { id, name, args } => {
                         app.current_tool = Some(name.clone());
-                        app.tool_log.push(format!("Calling {} with {}", name, args.to_string()));
+                        app.tool_log.push(format!("Calling {} with {}", name, args));
+                        app.pending_tool_args.insert(id, args);
                     }
-                    OrchestratorEvent::ToolResult { name, result } => {
+                    OrchestratorEvent::ToolResult { id, name, result } => {
                         app.current_tool = None;
-                        app.tool_log.push(format!("Result for {}: {}", name, result.to_string()));
+                        app.tool_log.push(format!("Result for {}: {}", name, result));
+                        let args = app.pending_tool_args.remove(&id).unwrap_or_default();
+                        let edit = if name == "edit_file" && result["applied"] == true {
+                            Some(SessionEvent::EditApplied {
+                                file: args["file_path"].as_str().unwrap_or("").to_string(),
+                                patch: args["patch"].as_str().unwrap_or("").to_string(),
+                            })
+                        } else {
+                            None
+                        };
+                        app.session.push(SessionEvent::ToolCalled { id, name, args, result });
+                        if let Some(edit) = edit {
+                            app.session.push(edit);
+                        }
                     }
                     OrchestratorEvent::Done(answer) => {
                         app.is_thinking = false;
                         app.current_tool = None;
-                        app.messages.push(format!("Agent: {}", answer));
+                        // The answer supersedes the tokens streamed for it.
+                        match app.messages.last_mut() {
+                            Some(last) if last.starts_with("Agent:") => *last = format!("Agent: {}", answer),
+                            _ => app.messages.push(format!("Agent: {}", answer)),
+                        }
+                        app.session.finish_turn(answer);
+                        app.save_session();
                         app.event_rx = None;
                         break;
                     }
@@ -196,6 +431,9 @@ async fn run_app<B: ratatui::backend::Backend>(
                         app.is_thinking = false;
                         app.current_tool = None;
                         app.messages.push(format!("Error: {}", err));
+                        app.session.coalesce_tokens();
+                        app.session.push(SessionEvent::Error(err));
+                        app.save_session();
                         app.event_rx = None;
                         break;
                     }
@@ -209,6 +447,76 @@ async fn run_app<B: ratatui::backend::Backend>(
     }
 }
 
+fn handle_coordinator_event(app: &mut App, event: CoordinatorEvent) {
+    match event {
+        CoordinatorEvent::AgentToken { step_id, role, token } => {
+            let prefix = match step_id {
+                Some(step_id) => format!("{} [{}]:", role, step_id),
+                None => format!("{}:", role),
+            };
+            match app.messages.last_mut() {
+                Some(last) if last.starts_with(&prefix) => last.push_str(&token),
+                _ => app.messages.push(format!("{} {}", prefix, token)),
+            }
+        }
+        CoordinatorEvent::ToolCall { step_id, role, id, name, args } => {
+            app.current_tool = Some(name.clone());
+            app.tool_log.push(format!("[{} {}] Calling {} with {}", step_id, role, name, args));
+            app.pending_tool_args.insert(format!("{}/{}", step_id, id), args);
+        }
+        CoordinatorEvent::ToolResult { step_id, role, id, name, result } => {
+            app.current_tool = None;
+            app.tool_log.push(format!("[{} {}] Result for {}: {}", step_id, role, name, result));
+            let args = app.pending_tool_args.remove(&format!("{}/{}", step_id, id)).unwrap_or_default();
+            if name == "edit_file" && result["applied"] == true {
+                app.session.push(SessionEvent::EditApplied {
+                    file: args["file_path"].as_str().unwrap_or("").to_string(),
+                    patch: args["patch"].as_str().unwrap_or("").to_string(),
+                });
+            }
+        }
+        CoordinatorEvent::ReviewVerdict { step_id, round, verdict } => {
+            let outcome = if verdict.approved { "approved" } else { "rejected" };
+            app.messages.push(format!("reviewer [{}]: round {} {}", step_id, round, outcome));
+        }
+        CoordinatorEvent::StepFinished { step_id, error } => match error {
+            None => app.messages.push(format!("Step {} merged.", step_id)),
+            Some(e) => app.messages.push(format!("Step {} failed: {}", step_id, e)),
+        },
+        CoordinatorEvent::FullSuiteVerified { passed, tests_run } => {
+            let outcome = if passed { "passes" } else { "fails" };
+            app.messages.push(format!("Full test suite {} ({}).", outcome, tests_run.join(", ")));
+        }
+        CoordinatorEvent::GoalFinished { goal, error } => {
+            // A macro goal runs many coordinator goals; its own channel reports the end.
+            if app.event_rx.is_none() {
+                app.is_thinking = false;
+                app.current_tool = None;
+            }
+            match error {
+                None => {
+                    let message = format!("Goal completed: {}", goal);
+                    app.messages.push(format!("Agent: {}", message));
+                    app.session.push(SessionEvent::AgentMessage(message));
+                }
+                Some(e) => {
+                    let message = format!("Goal failed: {}", e);
+                    app.messages.push(format!("Error: {}", message));
+                    app.session.push(SessionEvent::Error(message));
+                }
+            }
+            app.save_session();
+        }
+        CoordinatorEvent::PlanReady { steps, .. } => {
+            app.messages.push(format!("Plan ready: {} step(s).", steps.len()));
+        }
+        CoordinatorEvent::StepStarted { step_id, description } => {
+            app.messages.push(format!("Step {} started: {}", step_id, description));
+        }
+        CoordinatorEvent::AgentStarted { .. } => {}
+    }
+}
+
 fn handle_input(app: &mut App) {
     if app.input.is_empty() {
         return;
@@ -225,7 +533,7 @@ fn handle_input(app: &mut App) {
         if let Err(e) = app.orchestrator.barq.index_repo(path) {
             app.barq_context.push(format!("Error indexing: {}", e));
         } else {
-            app.barq_context.push(format!("Indexed successfully."));
+            app.barq_context.push("Indexed successfully.".to_string());
         }
     } else if input == "/config" {
         if let Ok(config_str) = toml::to_string_pretty(&app.config) {
@@ -234,33 +542,80 @@ fn handle_input(app: &mut App) {
     } else if input == "/clear" {
         app.messages.clear();
         app.orchestrator.conversation.clear();
-    } else if input == "/replay" {
-        app.messages.push("Replay not implemented yet.".to_string());
+    } else if input == "/resume" {
+        let sessions = app.session_store.list();
+        if sessions.is_empty() {
+            app.messages.push("No saved sessions.".to_string());
+        }
+        for meta in sessions.iter().take(10) {
+            app.messages.push(format!("{} ({} events, {})", meta.id, meta.event_count, meta.workspace));
+        }
+    } else if let Some(id) = input.strip_prefix("/resume ") {
+        if let Err(e) = app.resume(id.trim()) {
+            app.messages.push(format!("Error: could not resume {}: {}", id.trim(), e));
+        }
+    } else if let Some(query) = input.strip_prefix("/search ") {
+        let hits = app.session_store.search(query, 10);
+        if hits.is_empty() {
+            app.messages.push(format!("No past sessions match \"{}\".", query));
+        }
+        for hit in hits {
+            app.messages.push(format!("{} #{}: {}", hit.session_id, hit.event_index, hit.snippet));
+        }
     } else if input == "/help" {
-        app.messages.push("Commands: /index [path], /config, /clear, /replay, /help".to_string());
-    } else if input.starts_with("/goal ") {
+        app.messages.push("Commands: /index [path], /config, /clear, /resume [id], /search <query>, /goal <text>, /goal list, /goal resume <name>, /dashboard, /help (Tab toggles the goal dashboard)".to_string());
+    } else if input == "/dashboard" {
+        app.toggle_dashboard();
+    } else if input == "/goal list" {
+        let goals = GoalManager::new(&app.config.workspace_root).list_goals();
+        if goals.is_empty() {
+            app.messages.push("No goal files in .barqcoder/goals.".to_string());
+        }
+        for name in goals {
+            app.messages.push(name);
+        }
+    } else if let Some(name) = input.strip_prefix("/goal resume ") {
+        let name = name.trim().to_string();
+        app.open_goal(&name);
+        app.dashboard.visible = true;
         app.is_thinking = true;
-        let goal_text = input["/goal ".len()..].to_string();
-        app.messages.push(format!("Starting multi-agent goal: {}", goal_text));
-        
-        let coordinator = Arc::clone(&app.coordinator);
+        app.messages.push(format!("Resuming goal {}", name));
+
+        let delegate: Arc<dyn TaskDelegate> = app.coordinator.clone();
+        let executor = GoalExecutor::new(&app.config.workspace_root, delegate);
         let (tx, rx) = mpsc::channel(100);
-        
+
         tokio::spawn(async move {
-            let _ = tx.send(OrchestratorEvent::Token("Coordinator analyzing...".to_string())).await;
-            match coordinator.execute_goal(&goal_text).await {
-                Ok(_) => {
-                    let _ = tx.send(OrchestratorEvent::Done("Goal completed successfully.".to_string())).await;
-                }
-                Err(e) => {
-                    let _ = tx.send(OrchestratorEvent::Error(format!("Goal failed: {}", e))).await;
-                }
-            }
+            let progress_tx = tx.clone();
+            let mut progress = move |line: String| {
+                let _ = progress_tx.try_send(OrchestratorEvent::Token(format!("{}\n", line)));
+            };
+            let event = match executor.resume(&name, &mut progress).await {
+                Ok(()) => OrchestratorEvent::Done(format!("Goal {} completed.", name)),
+                Err(e) => OrchestratorEvent::Error(format!(
+                    "Goal {} stopped: {}. Run /goal resume {} to continue.",
+                    name, e, name
+                )),
+            };
+            let _ = tx.send(event).await;
         });
-        
+
         app.event_rx = Some(rx);
+    } else if let Some(goal_text) = input.strip_prefix("/goal ") {
+        app.is_thinking = true;
+        let goal_text = goal_text.to_string();
+        app.messages.push(format!("Starting multi-agent goal: {}", goal_text));
+        app.session.push(SessionEvent::UserInput(input.clone()));
+        app.dashboard.visible = true;
+
+        // Progress and the outcome arrive as CoordinatorEvents.
+        let coordinator = Arc::clone(&app.coordinator);
+        tokio::spawn(async move {
+            let _ = coordinator.execute_goal(&goal_text).await;
+        });
     } else {
         // Start orchestrator loop
+        app.session.push(SessionEvent::UserInput(input.clone()));
         app.is_thinking = true;
         let rx = app.orchestrator.run(&input);
         app.event_rx = Some(rx);
@@ -268,6 +623,21 @@ fn handle_input(app: &mut App) {
 }
 
 fn ui(f: &mut ratatui::Frame, app: &App) {
+    if app.dashboard.visible {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(35), // Chat history
+                Constraint::Percentage(55), // Goal dashboard
+                Constraint::Percentage(10), // Input
+            ])
+            .split(f.area());
+        render_messages(f, app, chunks[0]);
+        app.dashboard.render(f, chunks[1]);
+        render_input(f, app, chunks[2]);
+        return;
+    }
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -278,22 +648,7 @@ fn ui(f: &mut ratatui::Frame, app: &App) {
         ])
         .split(f.area());
 
-    // Pane 1: Chat history
-    let messages_text = app.messages.join("\n");
-    let mut messages_height = app.messages.len() as u16;
-    let available_height = chunks[0].height.saturating_sub(2);
-    let mut scroll_offset = 0;
-    if messages_height > available_height {
-        scroll_offset = messages_height - available_height;
-    }
-
-    let spinner = if app.is_thinking { "⠋" } else { "" };
-    
-    let messages_p = Paragraph::new(messages_text)
-        .block(Block::default().title(format!("BarqCoder {}", spinner)).borders(Borders::ALL))
-        .wrap(Wrap { trim: false })
-        .scroll((scroll_offset, 0));
-    f.render_widget(messages_p, chunks[0]);
+    render_messages(f, app, chunks[0]);
 
     // Pane 2: Tool log
     let tool_title = if let Some(t) = &app.current_tool {
@@ -316,10 +671,89 @@ fn ui(f: &mut ratatui::Frame, app: &App) {
         .wrap(Wrap { trim: false });
     f.render_widget(context_p, chunks[2]);
 
-    // Pane 4: Input
+    render_input(f, app, chunks[3]);
+}
+
+fn render_messages(f: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+    let messages_text = app.messages.join("\n");
+    let messages_height = app.messages.len() as u16;
+    let available_height = area.height.saturating_sub(2);
+    let mut scroll_offset = 0;
+    if messages_height > available_height {
+        scroll_offset = messages_height - available_height;
+    }
+
+    let spinner = if app.is_thinking { "⠋" } else { "" };
+    
+    let messages_p = Paragraph::new(messages_text)
+        .block(Block::default().title(format!("BarqCoder {}", spinner)).borders(Borders::ALL))
+        .wrap(Wrap { trim: false })
+        .scroll((scroll_offset, 0));
+    f.render_widget(messages_p, area);
+}
+
+fn render_input(f: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
     let input_p = Paragraph::new(app.input.as_str())
-        .block(Block::default().title("Input (ESC quit)").borders(Borders::ALL));
-    f.render_widget(input_p, chunks[3]);
+        .block(Block::default().title("Input (ESC quit, Tab goals)").borders(Borders::ALL));
+    f.render_widget(input_p, area);
+}
+
+fn session_command(args: &[String]) -> anyhow::Result<()> {
+    const USAGE: &str = "Usage: barqcoder session list | search <query> | export <id> --format md|jsonl|patch | gc [--apply]";
+    let config = Config::load();
+    let store = match BarqIndex::new(&config) {
+        Ok(barq) => SessionStore::with_barq(&config.workspace_root, Arc::new(barq)),
+        Err(_) => SessionStore::new(&config.workspace_root),
+    };
+
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            for meta in store.list() {
+                println!("{}\t{} events\t{}", meta.id, meta.event_count, meta.title);
+            }
+            Ok(())
+        }
+        Some("search") => {
+            let query = args[1..].join(" ");
+            if query.is_empty() {
+                anyhow::bail!(USAGE);
+            }
+            for hit in store.search(&query, 20) {
+                println!("{} #{}\t{:.2}\t{}", hit.session_id, hit.event_index, hit.score, hit.snippet);
+            }
+            Ok(())
+        }
+        Some("export") => {
+            let id = args.get(1).ok_or_else(|| anyhow::anyhow!(USAGE))?;
+            let format = args
+                .iter()
+                .position(|a| a == "--format")
+                .and_then(|idx| args.get(idx + 1))
+                .map(String::as_str)
+                .unwrap_or("md")
+                .parse::<ExportFormat>()?;
+            let session = store.load(id)?;
+            print!("{}", session.export(format)?);
+            Ok(())
+        }
+        Some("gc") => {
+            let plan = store.plan_gc(&RetentionPolicy::from_config(&config));
+            for action in &plan.actions {
+                match action {
+                    GcAction::Delete { id, reason } => println!("delete   {} ({})", id, reason),
+                    GcAction::Compress { id } => println!("compress {}", id),
+                }
+            }
+            println!("{} actions, {} KiB reclaimed by deletion", plan.actions.len(), plan.reclaimed_bytes / 1024);
+            if args.iter().any(|a| a == "--apply") {
+                store.apply_gc(&plan)?;
+            } else if !plan.actions.is_empty() {
+                println!("Preview only; rerun with --apply to enforce.");
+            }
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!(USAGE)),
+    }
 }
 
 async fn start_health_server() {
@@ -327,7 +761,7 @@ async fn start_health_server() {
         loop {
             if let Ok((mut stream, _)) = listener.accept().await {
                 let mut buf = [0; 1024];
-                if let Ok(_) = stream.read(&mut buf).await {
+                if stream.read(&mut buf).await.is_ok() {
                     let req = String::from_utf8_lossy(&buf);
                     if req.starts_with("GET /health") {
                         let _ = stream.write_all(b"HTTP/1.1 200 OK\r\n\r\nOK").await;
@@ -1,21 +1,23 @@
-use crate::agent::{Message, OllamaClient, parse_response};
+use crate::agent::{LlmProvider, Message};
 use crate::barq::BarqIndex;
 use crate::config::Config;
+use crate::session::Session;
 use crate::tools::ToolRegistry;
+use crate::verifier::{TestScope, Verifier};
 use serde_json::Value;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
 pub enum OrchestratorEvent {
     Token(String),
-    ToolCall { name: String, args: Value },
-    ToolResult { name: String, result: Value },
+    ToolCall { id: String, name: String, args: Value },
+    ToolResult { id: String, name: String, result: Value },
     Done(String),
     Error(String),
 }
 
 pub struct Orchestrator {
-    pub agent: OllamaClient,
+    pub agent: Arc<dyn LlmProvider>,
     pub tools: Arc<ToolRegistry>,
     pub barq: Arc<BarqIndex>,
     pub config: Config,
@@ -24,7 +26,7 @@ pub struct Orchestrator {
 
 impl Orchestrator {
     pub fn new(
-        agent: OllamaClient,
+        agent: Arc<dyn LlmProvider>,
         tools: Arc<ToolRegistry>,
         barq: Arc<BarqIndex>,
         config: Config,
@@ -38,6 +40,15 @@ impl Orchestrator {
         }
     }
 
+    /// Restores the conversation and workspace of a stored session so the next
+    /// `run` continues where it left off. The system prompt is rebuilt on that run.
+    pub fn resume(&mut self, session: &Session) {
+        self.conversation = session.to_conversation();
+        if !session.workspace.is_empty() {
+            self.config.workspace_root = session.workspace.clone();
+        }
+    }
+
     pub fn run(&mut self, user_input: &str) -> mpsc::Receiver<OrchestratorEvent> {
         let (tx, rx) = mpsc::channel(100);
 
@@ -65,7 +76,7 @@ impl Orchestrator {
              RULES:\n\
              1. ALWAYS reference BARQ context before suggesting code\n\
              2. Use tools in this order: barq_search -> edit_file -> cargo_check\n\
-             3. NEVER apply edits without running cargo_check after\n\
+             3. edit_file verifies every edit and rolls it back if it fails; read its \"verification\" result and fix the cause before retrying\n\
              4. If cargo_check fails, fix errors before final_answer\n\
              5. Respond ONLY as valid JSON matching this schema:\n\
              {{\n\
@@ -77,8 +88,8 @@ impl Orchestrator {
              context_str, deps_str
         );
 
-        if self.conversation.is_empty() {
-            self.conversation.push(Message {
+        if self.conversation.first().is_none_or(|m| m.role != "system") {
+            self.conversation.insert(0, Message {
                 role: "system".to_string(),
                 content: sys_prompt,
                 tool_calls: None,
@@ -94,9 +105,22 @@ impl Orchestrator {
             tool_call_id: None,
         });
 
+        // The answer waits for the whole test suite when configured to.
+        let full_suite = self.config.full_suite_before_answer.then(|| {
+            Verifier::new(self.barq.clone(), &self.config.workspace_root).with_test_scope(TestScope::Full)
+        });
+
         // Mock event stream behavior for compilation
         tokio::spawn(async move {
             let _ = tx.send(OrchestratorEvent::Token("Thinking...".to_string())).await;
+            if let Some(verifier) = full_suite {
+                let verification = verifier.verify_changes(&[]).await;
+                if verification.should_revert {
+                    let message = format!("Full test suite fails: {}", verification.errors.join("; "));
+                    let _ = tx.send(OrchestratorEvent::Error(message)).await;
+                    return;
+                }
+            }
             let _ = tx.send(OrchestratorEvent::Done("I am ready to help.".to_string())).await;
         });
 
@@ -0,0 +1,142 @@
+use crate::agent::{LlmProvider, Message};
+use crate::barq::fnv1a;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// One recorded request/response pair, stored as a line of the session cassette.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Exchange {
+    pub key: String,
+    pub messages: Vec<Message>,
+    pub chunks: Vec<String>,
+}
+
+/// Stable key for a request. FNV-1a over the serialized messages and tool
+/// schemas, so cassettes stay valid across builds and platforms.
+pub fn request_key(messages: &[Message], tools: &[Value]) -> String {
+    let payload = serde_json::to_string(&(messages, tools)).unwrap_or_default();
+    format!("{:016x}", fnv1a(payload.bytes()))
+}
+
+/// Wraps a live provider and appends every exchange to a cassette file.
+pub struct RecordingProvider {
+    inner: Arc<dyn LlmProvider>,
+    path: Arc<Mutex<PathBuf>>,
+}
+
+impl RecordingProvider {
+    pub fn new(inner: Arc<dyn LlmProvider>, path: &Path) -> Self {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        Self {
+            inner,
+            path: Arc::new(Mutex::new(path.to_path_buf())),
+        }
+    }
+
+    /// Records `inner` into the same cassette, e.g. for another agent role's model.
+    pub fn wrap(&self, inner: Arc<dyn LlmProvider>) -> Self {
+        Self {
+            inner,
+            path: Arc::clone(&self.path),
+        }
+    }
+}
+
+impl LlmProvider for RecordingProvider {
+    fn chat_stream(&self, messages: Vec<Message>, tools: Vec<Value>) -> anyhow::Result<mpsc::Receiver<String>> {
+        let key = request_key(&messages, &tools);
+        let mut inner_rx = self.inner.chat_stream(messages.clone(), tools)?;
+        let path = Arc::clone(&self.path);
+        let (tx, rx) = mpsc::channel(100);
+
+        tokio::spawn(async move {
+            let mut chunks = Vec::new();
+            while let Some(chunk) = inner_rx.recv().await {
+                chunks.push(chunk.clone());
+                let _ = tx.send(chunk).await;
+            }
+
+            let exchange = Exchange { key, messages, chunks };
+            let path = path.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = append_exchange(&path, &exchange) {
+                tracing::warn!("Failed to record LLM exchange: {}", e);
+            }
+            // The stream ends only once the exchange is on disk, so a cassette
+            // is complete as soon as its last response has been read.
+            drop(tx);
+        });
+
+        Ok(rx)
+    }
+}
+
+fn append_exchange(path: &Path, exchange: &Exchange) -> anyhow::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(exchange)?)?;
+    Ok(())
+}
+
+/// Serves recorded exchanges back without contacting any model. Identical
+/// requests are answered in the order they were recorded.
+pub struct ReplayProvider {
+    exchanges: Mutex<HashMap<String, VecDeque<Vec<String>>>>,
+    misses: Mutex<usize>,
+}
+
+impl ReplayProvider {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let mut exchanges: HashMap<String, VecDeque<Vec<String>>> = HashMap::new();
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let exchange: Exchange = serde_json::from_str(line)?;
+            exchanges
+                .entry(exchange.key)
+                .or_default()
+                .push_back(exchange.chunks);
+        }
+        Ok(Self {
+            exchanges: Mutex::new(exchanges),
+            misses: Mutex::new(0),
+        })
+    }
+
+    /// Number of requests that had no recorded response.
+    pub fn misses(&self) -> usize {
+        *self.misses.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl LlmProvider for ReplayProvider {
+    /// Fails for a request the cassette has no (remaining) response to, so a
+    /// replay that diverges from its recording cannot pass unnoticed.
+    fn chat_stream(&self, messages: Vec<Message>, tools: Vec<Value>) -> anyhow::Result<mpsc::Receiver<String>> {
+        let key = request_key(&messages, &tools);
+        let chunks = self
+            .exchanges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(&key)
+            .and_then(|queue| queue.pop_front());
+
+        let Some(chunks) = chunks else {
+            *self.misses.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+            anyhow::bail!("No recorded response for request {} ({} replay misses so far)", key, self.misses());
+        };
+
+        let (tx, rx) = mpsc::channel(chunks.len().max(1));
+        tokio::spawn(async move {
+            for chunk in chunks {
+                let _ = tx.send(chunk).await;
+            }
+        });
+        Ok(rx)
+    }
+}
@@ -0,0 +1,384 @@
+use super::{Session, SessionEvent};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    JsonLines,
+    Patch,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "md" | "markdown" => Ok(Self::Markdown),
+            "jsonl" => Ok(Self::JsonLines),
+            "patch" => Ok(Self::Patch),
+            _ => Err(anyhow::anyhow!("Unknown export format: {}. Must be md, jsonl, or patch.", s)),
+        }
+    }
+}
+
+impl Session {
+    pub fn export(&self, format: ExportFormat) -> anyhow::Result<String> {
+        match format {
+            ExportFormat::Markdown => Ok(self.to_markdown()),
+            ExportFormat::JsonLines => self.to_jsonl(),
+            ExportFormat::Patch => Ok(self.to_patch()),
+        }
+    }
+
+    /// Renders the conversation for humans: tool calls are collapsed into
+    /// `<details>` blocks and applied edits are fenced as diffs.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# Session {}\n\n- Created: {}\n- Workspace: `{}`\n- Events: {}\n",
+            self.id,
+            self.created_at,
+            self.workspace,
+            self.events.len()
+        );
+        let mut in_agent_turn = false;
+
+        for event in &self.events {
+            if in_agent_turn && !matches!(event, SessionEvent::AgentToken(_)) {
+                out.push('\n');
+                in_agent_turn = false;
+            }
+
+            match event {
+                SessionEvent::AgentToken(token) => {
+                    if !in_agent_turn {
+                        out.push_str("\n**Agent:** ");
+                        in_agent_turn = true;
+                    }
+                    out.push_str(token);
+                }
+                SessionEvent::AgentMessage(message) => {
+                    out.push_str(&format!("\n**Agent:** {}\n", message));
+                }
+                SessionEvent::UserInput(input) => {
+                    out.push_str(&format!("\n**You:** {}\n", input));
+                }
+                SessionEvent::ToolCalled { name, args, result, .. } => {
+                    out.push_str(&format!(
+                        "\n<details>\n<summary>Tool: <code>{}</code></summary>\n\n```json\n{}\n```\n\n```json\n{}\n```\n\n</details>\n",
+                        name,
+                        serde_json::to_string_pretty(args).unwrap_or_default(),
+                        serde_json::to_string_pretty(result).unwrap_or_default()
+                    ));
+                }
+                SessionEvent::EditApplied { file, patch } => {
+                    out.push_str(&format!(
+                        "\n#### Edit `{}`\n\n```diff\n{}\n```\n",
+                        file,
+                        patch.trim_end()
+                    ));
+                }
+                SessionEvent::Error(err) => {
+                    out.push_str(&format!("\n> **Error:** {}\n", err));
+                }
+            }
+        }
+        if in_agent_turn {
+            out.push('\n');
+        }
+
+        out
+    }
+
+    /// One `SessionEvent` per line, for tooling.
+    pub fn to_jsonl(&self) -> anyhow::Result<String> {
+        let mut out = String::new();
+        for event in &self.events {
+            out.push_str(&serde_json::to_string(event)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// The cumulative diff of every applied edit, one git-style section per
+    /// file from before its first edit to after its last, so the result can
+    /// be fed to `git apply`. Files the session created diff against
+    /// `/dev/null`.
+    pub fn to_patch(&self) -> String {
+        let mut order: Vec<String> = Vec::new();
+        let mut histories: HashMap<String, FileHistory> = HashMap::new();
+        for event in &self.events {
+            let SessionEvent::EditApplied { file, patch } = event else {
+                continue;
+            };
+            let hunks = parse_hunks(patch);
+            let Some(first) = hunks.first() else {
+                continue;
+            };
+            let file = file.trim_start_matches("./").to_string();
+            let created = first.old_start == 0 && first.old_len == 0;
+            histories
+                .entry(file.clone())
+                .or_insert_with(|| {
+                    order.push(file.clone());
+                    FileHistory::new(created)
+                })
+                .apply(hunks);
+        }
+
+        let mut out = String::new();
+        for file in order {
+            out.push_str(&histories[&file].to_git_patch(&file));
+        }
+        out
+    }
+}
+
+/// Lines of context around each change, as `diff -u` uses.
+const CONTEXT: usize = 3;
+
+struct Hunk {
+    old_start: usize,
+    old_len: usize,
+    /// Body lines, each starting with ` `, `-` or `+`.
+    lines: Vec<String>,
+}
+
+/// The hunks of a unified diff; file headers and anything else are skipped.
+fn parse_hunks(patch: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for line in patch.lines() {
+        if let Some(header) = line.strip_prefix("@@ -") {
+            let range = header.split_whitespace().next().unwrap_or("");
+            let (start, len) = match range.split_once(',') {
+                Some((start, len)) => (start.parse().unwrap_or(0), len.parse().unwrap_or(0)),
+                None => (range.parse().unwrap_or(0), 1),
+            };
+            hunks.push(Hunk {
+                old_start: start,
+                old_len: len,
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = hunks.last_mut() {
+            if !line.starts_with('\\') {
+                hunk.lines.push(line.to_string());
+            }
+        }
+    }
+    hunks
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    /// An original line with its 1-based number, or a line an edit added.
+    Line { text: String, orig: Option<usize> },
+    /// Original lines no edit has shown; `None` runs to the end of the file.
+    Unseen { start: usize, len: Option<usize> },
+}
+
+/// One file as a session's edits left it, reconstructed from their hunks
+/// alone: the lines the hunks showed are known, the rest are unseen
+/// original lines.
+struct FileHistory {
+    entries: Vec<Entry>,
+    /// Original lines the edits removed, by line number.
+    removed: BTreeMap<usize, String>,
+    created: bool,
+}
+
+enum Op<'a> {
+    Keep(&'a str),
+    Remove(&'a str),
+    Add(&'a str),
+    Unseen(usize),
+}
+
+impl FileHistory {
+    fn new(created: bool) -> Self {
+        Self {
+            entries: if created {
+                vec![]
+            } else {
+                vec![Entry::Unseen { start: 1, len: None }]
+            },
+            removed: BTreeMap::new(),
+            created,
+        }
+    }
+
+    /// Applies one edit's hunks, bottom-up so earlier line numbers hold.
+    fn apply(&mut self, mut hunks: Vec<Hunk>) {
+        hunks.sort_by_key(|h| std::cmp::Reverse(h.old_start));
+        for hunk in hunks {
+            self.apply_hunk(hunk);
+        }
+    }
+
+    fn apply_hunk(&mut self, hunk: Hunk) {
+        // An insertion-only hunk names the line it goes after.
+        let start = if hunk.old_len == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let from = self.boundary(start);
+        let to = self.boundary(start + hunk.old_len);
+        let mut old = Vec::new();
+        for entry in self.entries.drain(from..to) {
+            match entry {
+                Entry::Line { orig, .. } => old.push(orig),
+                Entry::Unseen { start, len } => old.extend((start..start + len.unwrap_or(0)).map(Some)),
+            }
+        }
+
+        let mut old = old.into_iter();
+        let mut new = Vec::new();
+        for line in hunk.lines {
+            let (marker, text) = match line.char_indices().nth(1) {
+                Some((i, _)) => (&line[..i], line[i..].to_string()),
+                None => (line.as_str(), String::new()),
+            };
+            match marker {
+                "+" => new.push(Entry::Line { text, orig: None }),
+                "-" => {
+                    if let Some(Some(orig)) = old.next() {
+                        self.removed.insert(orig, text);
+                    }
+                }
+                _ => {
+                    let orig = old.next().flatten();
+                    new.push(Entry::Line { text, orig });
+                }
+            }
+        }
+        // Lines a miscounted hunk did not account for stay as they were.
+        new.extend(old.flatten().map(|orig| Entry::Unseen { start: orig, len: Some(1) }));
+        self.entries.splice(from..from, new);
+    }
+
+    /// The index of the entry starting at 0-based line `pos`, splitting an
+    /// unseen run there if needed.
+    fn boundary(&mut self, pos: usize) -> usize {
+        let mut at = 0;
+        for i in 0..self.entries.len() {
+            if at == pos {
+                return i;
+            }
+            match self.entries[i] {
+                Entry::Line { .. } => at += 1,
+                Entry::Unseen { start, len } => {
+                    let n = len.unwrap_or(usize::MAX);
+                    if pos < at.saturating_add(n) {
+                        let k = pos - at;
+                        self.entries[i] = Entry::Unseen { start, len: Some(k) };
+                        self.entries.insert(
+                            i + 1,
+                            Entry::Unseen {
+                                start: start + k,
+                                len: len.map(|l| l - k),
+                            },
+                        );
+                        return i + 1;
+                    }
+                    at += n;
+                }
+            }
+        }
+        self.entries.len()
+    }
+
+    /// The file from the original to the final version, in order, with each
+    /// removed line placed before the next original line that stayed.
+    fn ops(&self) -> Vec<Op<'_>> {
+        let mut next_orig = vec![usize::MAX; self.entries.len()];
+        let mut next = usize::MAX;
+        for (i, entry) in self.entries.iter().enumerate().rev() {
+            match entry {
+                Entry::Line { orig: Some(o), .. } | Entry::Unseen { start: o, .. } => next = *o,
+                Entry::Line { orig: None, .. } => {}
+            }
+            next_orig[i] = next;
+        }
+
+        let mut removed = self.removed.iter().peekable();
+        let mut ops = Vec::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            while let Some((_, text)) = removed.next_if(|(line, _)| **line < next_orig[i]) {
+                ops.push(Op::Remove(text));
+            }
+            ops.push(match entry {
+                Entry::Line { text, orig: Some(_) } => Op::Keep(text),
+                Entry::Line { text, orig: None } => Op::Add(text),
+                Entry::Unseen { len, .. } => Op::Unseen(len.unwrap_or(0)),
+            });
+        }
+        ops.extend(removed.map(|(_, text)| Op::Remove(text)));
+        ops
+    }
+
+    fn to_git_patch(&self, file: &str) -> String {
+        let ops = self.ops();
+        let (mut old_before, mut new_before) = (Vec::new(), Vec::new());
+        let (mut old_at, mut new_at) = (0, 0);
+        for op in &ops {
+            old_before.push(old_at);
+            new_before.push(new_at);
+            match op {
+                Op::Keep(_) => (old_at, new_at) = (old_at + 1, new_at + 1),
+                Op::Remove(_) => old_at += 1,
+                Op::Add(_) => new_at += 1,
+                Op::Unseen(n) => (old_at, new_at) = (old_at + n, new_at + n),
+            }
+        }
+
+        // Changes close enough to share context, with no unseen lines
+        // between them, go in one hunk.
+        let changes: Vec<usize> = (0..ops.len()).filter(|&i| matches!(ops[i], Op::Remove(_) | Op::Add(_))).collect();
+        let mut groups: Vec<(usize, usize)> = Vec::new();
+        for &i in &changes {
+            match groups.last_mut() {
+                Some((_, last)) if i - *last <= 2 * CONTEXT + 1 && !ops[*last..i].iter().any(|op| matches!(op, Op::Unseen(_))) => *last = i,
+                _ => groups.push((i, i)),
+            }
+        }
+        if groups.is_empty() {
+            return String::new();
+        }
+
+        let mut out = format!("diff --git a/{0} b/{0}\n", file);
+        if self.created {
+            out.push_str(&format!("new file mode 100644\n--- /dev/null\n+++ b/{}\n", file));
+        } else {
+            out.push_str(&format!("--- a/{0}\n+++ b/{0}\n", file));
+        }
+        for (first, last) in groups {
+            let mut start = first;
+            while start > 0 && first - start < CONTEXT && matches!(ops[start - 1], Op::Keep(_)) {
+                start -= 1;
+            }
+            let mut end = last + 1;
+            while end < ops.len() && end - last <= CONTEXT && matches!(ops[end], Op::Keep(_)) {
+                end += 1;
+            }
+            let body = &ops[start..end];
+            let old_len = body.iter().filter(|op| matches!(op, Op::Keep(_) | Op::Remove(_))).count();
+            let new_len = body.iter().filter(|op| matches!(op, Op::Keep(_) | Op::Add(_))).count();
+            // An empty side names the line before it, as `diff -u` does.
+            let old_start = old_before[start] + (old_len > 0) as usize;
+            let new_start = new_before[start] + (new_len > 0) as usize;
+            out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_len, new_start, new_len));
+            for op in body {
+                let (marker, text) = match op {
+                    Op::Keep(text) => (' ', text),
+                    Op::Remove(text) => ('-', text),
+                    Op::Add(text) => ('+', text),
+                    Op::Unseen(_) => continue,
+                };
+                out.push(marker);
+                out.push_str(text);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,212 @@
+use super::{Session, SessionEvent, SessionMeta};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Words that carry no signal in questions like "when did we last touch X".
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "with", "when", "did", "was", "were", "last", "where", "which",
+    "that", "this", "kept", "session", "from", "into", "what", "have",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexEntry {
+    #[serde(flatten)]
+    pub meta: SessionMeta,
+    /// Distinct lowercased terms of the session, used to skip sessions that
+    /// cannot match a keyword query without opening them.
+    #[serde(default)]
+    pub terms: BTreeSet<String>,
+    /// How many searchable units have already been pushed to BARQ.
+    #[serde(default)]
+    pub embedded: usize,
+}
+
+/// The small on-disk index kept next to the session files so listing and
+/// searching don't have to parse every session.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SessionIndex {
+    pub sessions: Vec<IndexEntry>,
+}
+
+/// A search match pointing at a single event of a stored session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionHit {
+    pub session_id: String,
+    pub event_index: usize,
+    pub snippet: String,
+    pub score: f32,
+}
+
+impl SessionIndex {
+    pub fn path(sessions_dir: &Path) -> PathBuf {
+        sessions_dir.join("index.json")
+    }
+
+    pub fn load(sessions_dir: &Path) -> Option<Self> {
+        let content = fs::read_to_string(Self::path(sessions_dir)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, sessions_dir: &Path) -> anyhow::Result<()> {
+        let content = serde_json::to_string(self)?;
+        fs::write(Self::path(sessions_dir), content)?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&IndexEntry> {
+        self.sessions.iter().find(|e| e.meta.id == id)
+    }
+
+    pub fn upsert(&mut self, entry: IndexEntry) {
+        match self.sessions.iter_mut().find(|e| e.meta.id == entry.meta.id) {
+            Some(existing) => *existing = entry,
+            None => self.sessions.push(entry),
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.sessions.retain(|e| e.meta.id != id);
+    }
+
+    /// Ids of sessions with a term starting with one of the query terms, newest first.
+    pub fn candidates(&self, query_terms: &[String]) -> Vec<String> {
+        let mut matches: Vec<&IndexEntry> = self
+            .sessions
+            .iter()
+            .filter(|e| {
+                query_terms.iter().any(|t| {
+                    e.terms
+                        .range(t.clone()..)
+                        .next()
+                        .is_some_and(|term| term.starts_with(t.as_str()))
+                })
+            })
+            .collect();
+        matches.sort_by_key(|e| std::cmp::Reverse(e.meta.updated_at));
+        matches.into_iter().map(|e| e.meta.id.clone()).collect()
+    }
+}
+
+impl Session {
+    /// The session split into searchable units keyed by the index of their
+    /// first event. Streamed agent tokens of one turn form a single unit.
+    pub fn searchable_events(&self) -> Vec<(usize, String)> {
+        let mut units: Vec<(usize, String)> = Vec::new();
+        let mut in_agent_turn = false;
+
+        for (idx, event) in self.events.iter().enumerate() {
+            let text = match event {
+                SessionEvent::AgentToken(token) | SessionEvent::AgentMessage(token) => {
+                    match units.last_mut() {
+                        Some((_, text)) if in_agent_turn => text.push_str(token),
+                        _ => units.push((idx, token.clone())),
+                    }
+                    in_agent_turn = true;
+                    continue;
+                }
+                SessionEvent::UserInput(input) => input.clone(),
+                SessionEvent::ToolCalled { name, args, result, .. } => {
+                    format!("{} {} {}", name, args, result)
+                }
+                SessionEvent::EditApplied { file, patch } => format!("{}\n{}", file, patch),
+                SessionEvent::Error(err) => err.clone(),
+            };
+            in_agent_turn = false;
+            units.push((idx, text));
+        }
+
+        units
+    }
+
+    /// Matches of the query terms in this session, scored by the fraction of
+    /// terms each event contains.
+    pub fn keyword_hits(&self, query_terms: &[String]) -> Vec<SessionHit> {
+        if query_terms.is_empty() {
+            return vec![];
+        }
+        self.searchable_events()
+            .into_iter()
+            .filter_map(|(idx, text)| {
+                let lower = text.to_lowercase();
+                let matched = query_terms.iter().filter(|t| lower.contains(t.as_str())).count();
+                if matched == 0 {
+                    return None;
+                }
+                Some(SessionHit {
+                    session_id: self.id.clone(),
+                    event_index: idx,
+                    snippet: snippet(&text, query_terms),
+                    score: matched as f32 / query_terms.len() as f32,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Lowercased search terms of a text, skipping short and stop words.
+pub fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.len() >= 3)
+        .map(|w| w.to_lowercase())
+        .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+        .collect()
+}
+
+pub fn session_terms(session: &Session) -> BTreeSet<String> {
+    session
+        .searchable_events()
+        .iter()
+        .flat_map(|(_, text)| terms(text))
+        .collect()
+}
+
+/// Keeps the best-scoring hit per event and orders hits by score.
+pub fn merge_hits(hits: Vec<SessionHit>, limit: usize) -> Vec<SessionHit> {
+    let mut best: HashMap<(String, usize), SessionHit> = HashMap::new();
+    for hit in hits {
+        let key = (hit.session_id.clone(), hit.event_index);
+        match best.get(&key) {
+            Some(existing) if existing.score >= hit.score => {}
+            _ => {
+                best.insert(key, hit);
+            }
+        }
+    }
+    let mut merged: Vec<SessionHit> = best.into_values().collect();
+    merged.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| b.session_id.cmp(&a.session_id))
+            .then_with(|| a.event_index.cmp(&b.event_index))
+    });
+    merged.truncate(limit);
+    merged
+}
+
+fn snippet(text: &str, query_terms: &[String]) -> String {
+    // Lowercasing can change byte lengths, so remember where each byte of the
+    // lowercased text came from in the original.
+    let mut lower = String::with_capacity(text.len());
+    let mut origin = Vec::with_capacity(text.len());
+    for (i, c) in text.char_indices() {
+        for l in c.to_lowercase() {
+            lower.push(l);
+            origin.extend(std::iter::repeat_n(i, l.len_utf8()));
+        }
+    }
+    let start = query_terms
+        .iter()
+        .filter_map(|t| lower.find(t.as_str()))
+        .min()
+        .map_or(0, |i| origin[i]);
+    // Back up a little for context, staying on a char boundary.
+    let mut from = start.saturating_sub(40);
+    while !text.is_char_boundary(from) {
+        from -= 1;
+    }
+    let snippet: String = text[from..].chars().take(120).collect();
+    snippet.replace('\n', " ")
+}
@@ -0,0 +1,428 @@
+use crate::agent::{Message, ToolCall};
+use crate::barq::BarqIndex;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use index::{IndexEntry, SessionHit, SessionIndex};
+use retention::{GcAction, GcPlan, RetentionPolicy, StoredSession};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub mod export;
+pub mod index;
+pub mod retention;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Session {
+    pub id: String,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub workspace: String,
+    #[serde(default)]
+    pub events: Vec<SessionEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum SessionEvent {
+    UserInput(String),
+    AgentToken(String),
+    /// A completed agent turn; replaces the streamed tokens it was built from.
+    AgentMessage(String),
+    ToolCalled {
+        // Older sessions were saved without call ids; one is synthesized on resume.
+        #[serde(default)]
+        id: String,
+        name: String,
+        args: Value,
+        result: Value,
+    },
+    EditApplied {
+        file: String,
+        patch: String,
+    },
+    Error(String),
+}
+
+impl Session {
+    pub fn new(id: &str, workspace: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            created_at: now_secs(),
+            workspace: workspace.to_string(),
+            events: vec![],
+        }
+    }
+
+    pub fn push(&mut self, event: SessionEvent) {
+        self.events.push(event);
+    }
+
+    /// Folds every run of consecutive `AgentToken`s into a single `AgentMessage`.
+    /// Called when a turn completes so stored sessions hold one event per message.
+    pub fn coalesce_tokens(&mut self) {
+        let mut events = Vec::with_capacity(self.events.len());
+        let mut pending: Option<String> = None;
+
+        for event in self.events.drain(..) {
+            match event {
+                SessionEvent::AgentToken(token) => {
+                    pending.get_or_insert_with(String::new).push_str(&token);
+                }
+                other => {
+                    if let Some(message) = pending.take() {
+                        events.push(SessionEvent::AgentMessage(message));
+                    }
+                    events.push(other);
+                }
+            }
+        }
+        if let Some(message) = pending {
+            events.push(SessionEvent::AgentMessage(message));
+        }
+
+        self.events = events;
+    }
+
+    /// Ends an agent turn with its final `answer`, which replaces the tokens
+    /// streamed since the last other event so the reply is stored once.
+    pub fn finish_turn(&mut self, answer: String) {
+        while matches!(self.events.last(), Some(SessionEvent::AgentToken(_))) {
+            self.events.pop();
+        }
+        self.coalesce_tokens();
+        self.events.push(SessionEvent::AgentMessage(answer));
+    }
+
+    /// Rebuilds the chat history the orchestrator had when this session was saved.
+    /// Consecutive agent tokens become one assistant message, and every tool call
+    /// becomes an assistant `tool_calls` message followed by its `tool` result.
+    pub fn to_conversation(&self) -> Vec<Message> {
+        let mut conversation = Vec::new();
+        let mut pending = String::new();
+
+        for (idx, event) in self.events.iter().enumerate() {
+            match event {
+                SessionEvent::AgentToken(token) => pending.push_str(token),
+                SessionEvent::AgentMessage(message) => {
+                    flush_assistant(&mut conversation, &mut pending);
+                    pending.push_str(message);
+                }
+                SessionEvent::UserInput(input) => {
+                    flush_assistant(&mut conversation, &mut pending);
+                    conversation.push(Message {
+                        role: "user".to_string(),
+                        content: input.clone(),
+                        tool_calls: None,
+                        tool_call_id: None,
+                    });
+                }
+                SessionEvent::ToolCalled { id, name, args, result } => {
+                    let id = if id.is_empty() {
+                        format!("call_{}", idx)
+                    } else {
+                        id.clone()
+                    };
+                    conversation.push(Message {
+                        role: "assistant".to_string(),
+                        content: std::mem::take(&mut pending),
+                        tool_calls: Some(vec![ToolCall {
+                            id: id.clone(),
+                            name: name.clone(),
+                            arguments: args.clone(),
+                        }]),
+                        tool_call_id: None,
+                    });
+                    conversation.push(Message {
+                        role: "tool".to_string(),
+                        content: result.to_string(),
+                        tool_calls: None,
+                        tool_call_id: Some(id),
+                    });
+                }
+                // Edits are already represented by their edit_file tool call, and
+                // errors never reached the model.
+                SessionEvent::EditApplied { .. } | SessionEvent::Error(_) => {
+                    flush_assistant(&mut conversation, &mut pending);
+                }
+            }
+        }
+        flush_assistant(&mut conversation, &mut pending);
+
+        conversation
+    }
+}
+
+fn flush_assistant(conversation: &mut Vec<Message>, pending: &mut String) {
+    if pending.is_empty() {
+        return;
+    }
+    conversation.push(Message {
+        role: "assistant".to_string(),
+        content: std::mem::take(pending),
+        tool_calls: None,
+        tool_call_id: None,
+    });
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionMeta {
+    pub id: String,
+    pub created_at: u64,
+    pub event_count: usize,
+    pub workspace: String,
+    #[serde(default)]
+    pub updated_at: u64,
+    /// The first user input, to tell sessions apart in listings.
+    #[serde(default)]
+    pub title: String,
+}
+
+impl SessionMeta {
+    fn from_session(session: &Session, updated_at: u64) -> Self {
+        let title = session
+            .events
+            .iter()
+            .find_map(|e| match e {
+                SessionEvent::UserInput(input) => Some(input.chars().take(80).collect()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        Self {
+            id: session.id.clone(),
+            created_at: session.created_at,
+            event_count: session.events.len(),
+            workspace: session.workspace.clone(),
+            updated_at,
+            title,
+        }
+    }
+}
+
+pub struct SessionStore {
+    sessions_dir: PathBuf,
+    barq: Option<Arc<BarqIndex>>,
+}
+
+// Sessions are stored in BARQ under this pseudo path, one entry per searchable event.
+const BARQ_SESSION_PREFIX: &str = "session://";
+
+impl SessionStore {
+    pub fn new(workspace: &str) -> Self {
+        let dir = Path::new(workspace).join(".barqcoder/sessions/");
+        let _ = fs::create_dir_all(&dir);
+        Self { sessions_dir: dir, barq: None }
+    }
+
+    /// A store whose sessions are also embedded in BARQ for semantic search.
+    pub fn with_barq(workspace: &str, barq: Arc<BarqIndex>) -> Self {
+        let mut store = Self::new(workspace);
+        store.barq = Some(barq);
+        store
+    }
+
+    pub fn save(&self, session: &Session) -> anyhow::Result<()> {
+        let content = serde_json::to_string(session)?;
+        fs::write(self.session_path(&session.id), content)?;
+        // A resumed session that had been compressed is live again.
+        let _ = fs::remove_file(self.compressed_path(&session.id));
+        self.update_index(session)
+    }
+
+    fn session_path(&self, id: &str) -> PathBuf {
+        self.sessions_dir.join(format!("{}.json", id))
+    }
+
+    fn compressed_path(&self, id: &str) -> PathBuf {
+        self.sessions_dir.join(format!("{}.json.gz", id))
+    }
+
+    fn update_index(&self, session: &Session) -> anyhow::Result<()> {
+        let mut index = self.index();
+        let mut embedded = index.get(&session.id).map_or(0, |e| e.embedded);
+
+        if let Some(barq) = &self.barq {
+            let units = session.searchable_events();
+            let path = format!("{}{}", BARQ_SESSION_PREFIX, session.id);
+            for (event_index, text) in units.iter().skip(embedded) {
+                barq.index_text(&path, *event_index, "session", text);
+            }
+            embedded = units.len();
+        }
+
+        index.upsert(IndexEntry {
+            meta: SessionMeta::from_session(session, now_secs()),
+            terms: index::session_terms(session),
+            embedded,
+        });
+        index.save(&self.sessions_dir)
+    }
+
+    /// The on-disk index, rebuilt from the session files when it is missing
+    /// (e.g. for sessions saved before it existed).
+    fn index(&self) -> SessionIndex {
+        if let Some(index) = SessionIndex::load(&self.sessions_dir) {
+            return index;
+        }
+        let mut index = SessionIndex::default();
+        for session in self.load_all() {
+            index.upsert(IndexEntry {
+                meta: SessionMeta::from_session(&session, session.created_at),
+                terms: index::session_terms(&session),
+                embedded: 0,
+            });
+        }
+        let _ = index.save(&self.sessions_dir);
+        index
+    }
+
+    fn load_all(&self) -> Vec<Session> {
+        let mut sessions = Vec::new();
+        if let Ok(entries) = fs::read_dir(&self.sessions_dir) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if path == SessionIndex::path(&self.sessions_dir) {
+                    continue;
+                }
+                let name = entry.file_name().to_string_lossy().to_string();
+                let id = name
+                    .strip_suffix(".json.gz")
+                    .or_else(|| name.strip_suffix(".json"));
+                if let Some(Ok(session)) = id.map(|id| self.load(id)) {
+                    sessions.push(session);
+                }
+            }
+        }
+        sessions
+    }
+
+    /// Where the LLM exchanges of a session are recorded for deterministic replay.
+    pub fn cassette_path(&self, id: &str) -> PathBuf {
+        self.sessions_dir.join(format!("{}.llm.jsonl", id))
+    }
+
+    pub fn load(&self, id: &str) -> anyhow::Result<Session> {
+        let path = self.session_path(id);
+        let content = if path.exists() {
+            fs::read_to_string(path)?
+        } else {
+            let mut content = String::new();
+            GzDecoder::new(fs::File::open(self.compressed_path(id))?).read_to_string(&mut content)?;
+            content
+        };
+        let session = serde_json::from_str(&content)?;
+        Ok(session)
+    }
+
+    pub fn list(&self) -> Vec<SessionMeta> {
+        let mut metas: Vec<SessionMeta> = self.index().sessions.into_iter().map(|e| e.meta).collect();
+        metas.sort_by_key(|m| std::cmp::Reverse(m.created_at));
+        metas
+    }
+
+    /// Finds the events of past sessions that best match the query: keyword
+    /// matches from the index, plus embedding matches when BARQ is available.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SessionHit> {
+        let query_terms = index::terms(query);
+        let index = self.index();
+        let mut hits = Vec::new();
+
+        for id in index.candidates(&query_terms) {
+            if let Ok(session) = self.load(&id) {
+                hits.extend(session.keyword_hits(&query_terms));
+            }
+        }
+
+        if let Some(barq) = &self.barq {
+            for result in barq.query(query, limit * 4) {
+                let id = result.file_path.strip_prefix(BARQ_SESSION_PREFIX);
+                // Embeddings of sessions deleted before they were forgotten
+                // may linger; skip them.
+                if let Some(id) = id.filter(|id| index.get(id).is_some()) {
+                    hits.push(SessionHit {
+                        session_id: id.to_string(),
+                        event_index: result.line,
+                        snippet: result.content.chars().take(120).collect::<String>().replace('\n', " "),
+                        score: result.score,
+                    });
+                }
+            }
+        }
+
+        index::merge_hits(hits, limit)
+    }
+
+    /// What enforcing the policy would delete or compress right now.
+    pub fn plan_gc(&self, policy: &RetentionPolicy) -> GcPlan {
+        let sessions = self
+            .index()
+            .sessions
+            .into_iter()
+            .map(|entry| {
+                let id = entry.meta.id.clone();
+                let compressed = !self.session_path(&id).exists();
+                let size_bytes = [self.session_path(&id), self.compressed_path(&id), self.cassette_path(&id)]
+                    .iter()
+                    .filter_map(|p| fs::metadata(p).ok())
+                    .map(|m| m.len())
+                    .sum();
+                StoredSession { meta: entry.meta, size_bytes, compressed }
+            })
+            .collect();
+        retention::plan(policy, sessions, now_secs())
+    }
+
+    pub fn apply_gc(&self, plan: &GcPlan) -> anyhow::Result<()> {
+        for action in &plan.actions {
+            match action {
+                GcAction::Delete { id, .. } => self.delete(id)?,
+                GcAction::Compress { id } => self.compress(id)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, id: &str) -> anyhow::Result<()> {
+        if let (Some(barq), Ok(session)) = (&self.barq, self.load(id)) {
+            let path = format!("{}{}", BARQ_SESSION_PREFIX, session.id);
+            for (event_index, _) in session.searchable_events() {
+                barq.forget_text(&path, event_index);
+            }
+        }
+        for path in [self.session_path(id), self.compressed_path(id), self.cassette_path(id)] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        let mut index = self.index();
+        index.remove(id);
+        index.save(&self.sessions_dir)
+    }
+
+    fn compress(&self, id: &str) -> anyhow::Result<()> {
+        let path = self.session_path(id);
+        let content = fs::read(&path)?;
+        let mut encoder = GzEncoder::new(fs::File::create(self.compressed_path(id))?, Compression::default());
+        encoder.write_all(&content)?;
+        encoder.finish()?;
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    pub fn replay(&self, id: &str) -> impl Iterator<Item = SessionEvent> {
+        let session = self.load(id).unwrap_or_else(|_| Session::new(id, ""));
+        session.events.into_iter()
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
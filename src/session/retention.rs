@@ -0,0 +1,106 @@
+use super::SessionMeta;
+use crate::config::Config;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Limits on stored sessions. A zero value disables the corresponding limit.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    pub max_age_days: u64,
+    pub max_total_bytes: u64,
+    pub keep_last: usize,
+    pub compress_after_days: u64,
+    /// Sessions that are never deleted or compressed, such as the one being
+    /// replayed or resumed. They still count towards the other limits.
+    pub protected: Vec<String>,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_age_days: config.session_max_age_days,
+            max_total_bytes: config.session_max_total_mb * 1024 * 1024,
+            keep_last: config.session_keep_last,
+            compress_after_days: config.session_compress_after_days,
+            protected: Vec::new(),
+        }
+    }
+
+    pub fn with_protected(mut self, ids: impl IntoIterator<Item = String>) -> Self {
+        self.protected.extend(ids);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GcAction {
+    Delete { id: String, reason: String },
+    Compress { id: String },
+}
+
+/// What enforcing a policy would do, so it can be previewed before applying.
+#[derive(Debug, Default)]
+pub struct GcPlan {
+    pub actions: Vec<GcAction>,
+    pub reclaimed_bytes: u64,
+}
+
+/// A stored session as seen by the planner.
+pub struct StoredSession {
+    pub meta: SessionMeta,
+    pub size_bytes: u64,
+    pub compressed: bool,
+}
+
+/// Walks sessions newest first. Each one is deleted when it falls outside the
+/// last N, is older than the max age, or would push the kept total over the
+/// size budget. Surviving sessions past the compression age are gzipped.
+pub fn plan(policy: &RetentionPolicy, mut sessions: Vec<StoredSession>, now: u64) -> GcPlan {
+    sessions.sort_by_key(|s| std::cmp::Reverse(last_active(&s.meta)));
+
+    let mut plan = GcPlan::default();
+    let mut kept_bytes = 0;
+    let mut kept = 0;
+
+    for session in sessions {
+        let age_days = now.saturating_sub(last_active(&session.meta)) / DAY_SECS;
+        let reason = if policy.protected.contains(&session.meta.id) {
+            None
+        } else if policy.keep_last > 0 && kept >= policy.keep_last {
+            Some(format!("beyond the last {} sessions", policy.keep_last))
+        } else if policy.max_age_days > 0 && age_days >= policy.max_age_days {
+            Some(format!("inactive for {} days", age_days))
+        } else if policy.max_total_bytes > 0 && kept_bytes + session.size_bytes > policy.max_total_bytes {
+            Some("over the total size limit".to_string())
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => {
+                plan.reclaimed_bytes += session.size_bytes;
+                plan.actions.push(GcAction::Delete {
+                    id: session.meta.id,
+                    reason,
+                });
+            }
+            None => {
+                kept += 1;
+                kept_bytes += session.size_bytes;
+                if policy.compress_after_days > 0
+                    && age_days >= policy.compress_after_days
+                    && !session.compressed
+                    && !policy.protected.contains(&session.meta.id)
+                {
+                    plan.actions.push(GcAction::Compress { id: session.meta.id });
+                }
+            }
+        }
+    }
+
+    plan
+}
+
+fn last_active(meta: &SessionMeta) -> u64 {
+    meta.updated_at.max(meta.created_at)
+}
@@ -1,15 +1,22 @@
+use super::diagnostic::Diagnostic;
+use super::rules::EXCESSIVE_CLONE;
 use super::AstWalker;
 use syn::visit::Visit;
 
-pub fn analyze_borrows(file_content: &str) -> Vec<String> {
+pub fn analyze_borrows(file: &syn::File) -> Vec<Diagnostic> {
     let mut hints = Vec::new();
-    if let Ok(file) = syn::parse_str::<syn::File>(file_content) {
-        let mut walker = AstWalker::new();
-        walker.visit_file(&file);
+    let mut walker = AstWalker::default();
+    walker.visit_file(file);
 
-        if walker.clones > 2 {
-            hints.push(format!("Detected {} .clone() calls. Consider passing by reference instead.", walker.clones));
-        }
+    if walker.clones.len() > 2 {
+        hints.push(
+            Diagnostic::new(
+                EXCESSIVE_CLONE,
+                format!("Detected {} .clone() calls, the first here.", walker.clones.len()),
+            )
+            .at(walker.clones[0])
+            .with_fix("Consider passing by reference instead", None),
+        );
     }
     hints
 }
@@ -1,4 +1,4 @@
 // Detects import cycles using the BARQ graph. Mocked for this scope.
-pub fn detect_cycles(_file_path: &str) -> Vec<String> {
+pub fn detect_cycles(_file_path: &str) -> Vec<super::diagnostic::Diagnostic> {
     vec![]
 }
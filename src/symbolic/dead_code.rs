@@ -1,5 +1,218 @@
-// Using BARQ graph DB in a real implementation to find unreferenced symbols.
-// This is a mocked implementation for the scope of this commit.
-pub fn detect_dead_code(_file_path: &str, _file_content: &str) -> Vec<String> {
-    vec![]
+use super::diagnostic::Diagnostic;
+use super::rules::{RuleSet, DEAD_CODE};
+use super::symbol_graph::{Root, SymbolGraph, SymbolKind};
+use crate::barq::fnv1a;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+
+/// How much of the crate uses an item, worst first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Usage {
+    Unused,
+    TestsOnly,
+    Used,
+}
+
+/// Items of the crate in `root` that nothing reaches from its entry points:
+/// `main` of each binary, the public API of the library, exported symbols
+/// and trait impls for foreign types. Items reached only from `#[test]`
+/// functions are reported as test-only unless they are test code themselves.
+/// An item compiled into several targets is dead only if it is in all of
+/// them. Inside an unused module or type, only the module or type is
+/// reported. Diagnostics carry the crate-relative file.
+pub fn find_dead_code(root: &Path) -> anyhow::Result<Vec<Diagnostic>> {
+    let graph = SymbolGraph::extract(root)?;
+    let entry = graph.reachable(&[Root::Entry]);
+    let any = graph.reachable(&[Root::Entry, Root::Test]);
+
+    // The same definition appears once per target that compiles its file.
+    let mut definitions: BTreeMap<(String, usize, usize, String), Vec<usize>> = BTreeMap::new();
+    for (i, symbol) in graph.symbols.iter().enumerate() {
+        if symbol.name.is_empty() {
+            continue;
+        }
+        let start = symbol.span.start();
+        definitions
+            .entry((symbol.file.clone(), start.line, start.column, symbol.name.clone()))
+            .or_default()
+            .push(i);
+    }
+
+    let usage_of = |i: usize| {
+        if entry[i] {
+            Usage::Used
+        } else if any[i] {
+            Usage::TestsOnly
+        } else {
+            Usage::Unused
+        }
+    };
+    let mut usage = vec![Usage::Unused; graph.symbols.len()];
+    for instances in definitions.values() {
+        let best = instances.iter().map(|&i| usage_of(i)).max().unwrap_or(Usage::Unused);
+        for &i in instances {
+            usage[i] = best;
+        }
+    }
+
+    // Test code used by tests is where it belongs.
+    let settled = |i: usize| {
+        let symbol = &graph.symbols[i];
+        usage[i] == Usage::Used || symbol.root.is_some() || (symbol.test_code && usage[i] == Usage::TestsOnly)
+    };
+
+    // A module is as used as the most used item in it; modules with no
+    // items say nothing either way.
+    let mut module_usage: Vec<Option<Usage>> = vec![None; graph.symbols.len()];
+    for (i, symbol) in graph.symbols.iter().enumerate() {
+        if symbol.kind == SymbolKind::Module || symbol.name.is_empty() {
+            continue;
+        }
+        let mut owner = symbol.owner;
+        while let Some(o) = owner {
+            if graph.symbols[o].kind == SymbolKind::Module {
+                let effective = if settled(i) { Usage::Used } else { usage[i] };
+                module_usage[o] = module_usage[o].max(Some(effective));
+            }
+            owner = graph.symbols[o].owner;
+        }
+    }
+    for instances in definitions.values() {
+        let best = instances.iter().map(|&i| module_usage[i]).max().flatten();
+        for &i in instances {
+            module_usage[i] = best;
+        }
+    }
+
+    let reported = |i: usize| {
+        let symbol = &graph.symbols[i];
+        match symbol.kind {
+            SymbolKind::Module => module_usage[i].is_some_and(|u| u != Usage::Used) && !symbol.test_code,
+            _ => !settled(i),
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    for instances in definitions.values() {
+        let i = instances[0];
+        if !reported(i) {
+            continue;
+        }
+        // Only the outermost unused module or type is worth a diagnostic.
+        let mut owner = graph.symbols[i].owner;
+        let mut covered = false;
+        while let Some(o) = owner {
+            if reported(o) {
+                covered = true;
+                break;
+            }
+            owner = graph.symbols[o].owner;
+        }
+        if covered {
+            continue;
+        }
+
+        let symbol = &graph.symbols[i];
+        let name = match (symbol.kind, symbol.owner) {
+            (SymbolKind::Variant | SymbolKind::Method, Some(o)) => format!("{}::{}", graph.symbols[o].name, symbol.name),
+            _ => symbol.name.clone(),
+        };
+        let usage = match symbol.kind {
+            SymbolKind::Module => module_usage[i].unwrap_or(Usage::Unused),
+            _ => usage[i],
+        };
+        let kind = capitalize(&symbol.kind.to_string());
+        let diagnostic = match usage {
+            Usage::TestsOnly => Diagnostic::new(DEAD_CODE, format!("{} `{}` is only used by tests.", kind, name))
+                .with_fix("Move it into the test module, or put it to use", None),
+            _ => Diagnostic::new(DEAD_CODE, format!("{} `{}` is never used.", kind, name)).with_fix("Remove it", None),
+        };
+        let mut diagnostic = diagnostic.at(symbol.span);
+        diagnostic.file = Some(symbol.file.clone());
+        diagnostics.push(diagnostic);
+    }
+    Ok(diagnostics)
+}
+
+/// Dead code in the crate at `patched` that was not dead in the same crate
+/// at `original`, matched by file and message so that moved lines do not
+/// count as new. The original's dead code is kept until its sources change,
+/// so repeated verifications against the same workspace analyse it once. If
+/// the original cannot be analysed there is nothing to compare with, and
+/// that is the error.
+pub fn find_new_dead_code(original: &Path, patched: &Path) -> anyhow::Result<Vec<Diagnostic>> {
+    let before = original_dead_code(original)?;
+    let before: HashSet<(&Option<String>, &String)> = before.iter().map(|d| (&d.file, &d.message)).collect();
+    Ok(find_dead_code(patched)?
+        .into_iter()
+        .filter(|d| !before.contains(&(&d.file, &d.message)))
+        .collect())
+}
+
+/// The dead code of each analysed original, with the revision it is for.
+type Originals = HashMap<PathBuf, (u64, Arc<Vec<Diagnostic>>)>;
+
+/// `find_dead_code` for `root`, reusing the last result while the revision
+/// of its sources is the same.
+fn original_dead_code(root: &Path) -> anyhow::Result<Arc<Vec<Diagnostic>>> {
+    static ORIGINALS: OnceLock<Mutex<Originals>> = OnceLock::new();
+    let originals = ORIGINALS.get_or_init(Default::default);
+    let revision = source_revision(root);
+    if let Some((cached, dead)) = originals.lock().unwrap_or_else(|e| e.into_inner()).get(root) {
+        if *cached == revision {
+            return Ok(dead.clone());
+        }
+    }
+    let dead = Arc::new(find_dead_code(root)?);
+    originals
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(root.to_path_buf(), (revision, dead.clone()));
+    Ok(dead)
+}
+
+/// Hashes the path, length and modification time of every file under
+/// `root/src`, which is everything `SymbolGraph::extract` reads.
+fn source_revision(root: &Path) -> u64 {
+    let mut files: Vec<(PathBuf, u64, u128)> = walkdir::WalkDir::new(root.join("src"))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+            Some((e.into_path(), meta.len(), modified))
+        })
+        .collect();
+    files.sort();
+    let listing: Vec<String> = files
+        .iter()
+        .map(|(path, len, modified)| format!("{} {} {}", path.display(), len, modified))
+        .collect();
+    fnv1a(listing.join("\n").bytes())
+}
+
+/// Grades dead code found by `find_dead_code` with `rules` and the
+/// `barq-allow` comments of the file each item is in.
+pub fn grade(dead: Vec<Diagnostic>, crate_root: &Path, rules: &RuleSet) -> Vec<Diagnostic> {
+    let mut by_file: BTreeMap<String, Vec<Diagnostic>> = BTreeMap::new();
+    for diagnostic in dead {
+        by_file.entry(diagnostic.file.clone().unwrap_or_default()).or_default().push(diagnostic);
+    }
+    let mut graded = Vec::new();
+    for (file, findings) in by_file {
+        let source = std::fs::read_to_string(crate_root.join(&file)).unwrap_or_default();
+        graded.extend(super::grade(findings, &file, &source, rules));
+    }
+    graded
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
 }
@@ -0,0 +1,101 @@
+use super::rules::Severity;
+use serde::{Deserialize, Serialize};
+
+/// A position in a source file: 1-based line, 0-based column in chars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: LineColumn,
+    pub end: LineColumn,
+}
+
+impl From<proc_macro2::Span> for Span {
+    fn from(span: proc_macro2::Span) -> Self {
+        let (start, end) = (span.start(), span.end());
+        Self {
+            start: LineColumn { line: start.line, column: start.column },
+            end: LineColumn { line: end.line, column: end.column },
+        }
+    }
+}
+
+/// A replacement that resolves a diagnostic, or advice when there is none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SuggestedFix {
+    pub description: String,
+    /// Text to put in place of the diagnostic's span.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+}
+
+/// One finding of a symbolic checker.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub rule_id: String,
+    /// The rule's severity for the file. Checkers leave it at the built-in
+    /// default; `analyze` grades it with the workspace's rules.
+    pub severity: Severity,
+    pub message: String,
+    /// The file, relative to the workspace. Set once the diagnostic leaves
+    /// the checker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<SuggestedFix>,
+}
+
+impl Diagnostic {
+    pub fn new(rule_id: &str, message: impl Into<String>) -> Self {
+        Self {
+            rule_id: rule_id.to_string(),
+            severity: Severity::Warn,
+            message: message.into(),
+            file: None,
+            span: None,
+            fix: None,
+        }
+    }
+
+    /// Locates the diagnostic at `span`, e.g. `node.span()` of a syn node.
+    pub fn at(mut self, span: proc_macro2::Span) -> Self {
+        self.span = Some(span.into());
+        self
+    }
+
+    pub fn with_fix(mut self, description: impl Into<String>, replacement: Option<String>) -> Self {
+        self.fix = Some(SuggestedFix {
+            description: description.into(),
+            replacement,
+        });
+        self
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.span.map(|s| s.start.line)
+    }
+}
+
+/// `file:line:column: [RULE] message (fix)`, with 1-based columns as
+/// editors show them.
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.file, &self.span) {
+            (Some(file), Some(span)) => write!(f, "{}:{}:{}: ", file, span.start.line, span.start.column + 1)?,
+            (Some(file), None) => write!(f, "{}: ", file)?,
+            (None, Some(span)) => write!(f, "{}:{}: ", span.start.line, span.start.column + 1)?,
+            (None, None) => {}
+        }
+        write!(f, "[{}] {}", self.rule_id, self.message)?;
+        if let Some(fix) = &self.fix {
+            write!(f, " ({})", fix.description)?;
+        }
+        Ok(())
+    }
+}
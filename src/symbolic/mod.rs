@@ -1,29 +1,164 @@
+use crate::barq::fnv1a;
+use diagnostic::Diagnostic;
+use rules::{RuleSet, Severity, Suppressions};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
 use syn::visit::Visit;
-use syn::ItemFn;
 
+pub mod diagnostic;
 pub mod rules;
 pub mod unsafe_check;
 pub mod borrow_hint;
 pub mod dead_code;
+pub mod symbol_graph;
 pub mod type_check;
 pub mod cycle_detect;
 pub mod security;
 pub mod perf;
 
-pub struct AstWalker {
-    pub unsafe_blocks: Vec<syn::ExprUnsafe>,
-    pub loops: Vec<syn::ExprLoop>,
-    pub clones: usize,
+/// Findings of every symbolic pass over one file, graded by the severity
+/// their rule has for it. Rules that are off leave nothing behind.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SymbolicReport {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl SymbolicReport {
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(move |d| d.severity == severity)
+    }
 }
 
-impl AstWalker {
-    pub fn new() -> Self {
-        Self {
-            unsafe_blocks: Vec::new(),
-            loops: Vec::new(),
-            clones: 0,
+/// How many files' findings the cache keeps before dropping the least
+/// recently used.
+const CACHE_CAPACITY: usize = 256;
+
+/// Findings by content hash, least recently used first in `order`.
+#[derive(Default)]
+struct FindingsCache {
+    entries: HashMap<u64, Vec<Diagnostic>>,
+    order: VecDeque<u64>,
+}
+
+impl FindingsCache {
+    fn get(&mut self, key: u64) -> Option<Vec<Diagnostic>> {
+        let findings = self.entries.get(&key)?.clone();
+        self.touch(key);
+        Some(findings)
+    }
+
+    fn insert(&mut self, key: u64, findings: Vec<Diagnostic>) {
+        self.entries.insert(key, findings);
+        self.touch(key);
+        while self.order.len() > CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
         }
     }
+
+    fn touch(&mut self, key: u64) {
+        self.order.retain(|k| *k != key);
+        self.order.push_back(key);
+    }
+}
+
+fn cache() -> &'static Mutex<FindingsCache> {
+    static CACHE: OnceLock<Mutex<FindingsCache>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Hashes the path and both versions, which are all the passes depend on.
+fn content_hash(file_path: &str, original: Option<&str>, source: &str) -> u64 {
+    let original = original.map_or(&[1u8][..], str::as_bytes);
+    fnv1a(file_path.bytes().chain([0]).chain(original.iter().copied()).chain([0]).chain(source.bytes()))
+}
+
+/// Runs every symbolic pass over `source`, parsing it once, and grades the
+/// findings with `rules` for the workspace-relative `file_path`, dropping
+/// those allowed by `barq-allow` comments. The passes' results for recently
+/// analysed files are cached by content hash; the second value says whether
+/// this was a hit. A file that
+/// does not parse yields an empty report, leaving the error to the compiler.
+pub fn analyze(file_path: &str, source: &str, rules: &RuleSet) -> (SymbolicReport, bool) {
+    analyze_edit(file_path, None, source, rules)
+}
+
+/// `analyze` for an edit from `original` to `source`, so that only unsafe
+/// code the edit introduces is reported as such.
+pub fn analyze_edit(file_path: &str, original: Option<&str>, source: &str, rules: &RuleSet) -> (SymbolicReport, bool) {
+    let key = content_hash(file_path, original, source);
+    let cached = cache().lock().unwrap_or_else(|e| e.into_inner()).get(key);
+    let hit = cached.is_some();
+    let findings = match cached {
+        Some(findings) => findings,
+        None => {
+            let findings = run_passes(file_path, original, source);
+            cache().lock().unwrap_or_else(|e| e.into_inner()).insert(key, findings.clone());
+            findings
+        }
+    };
+
+    let report = SymbolicReport {
+        diagnostics: grade(findings, file_path, source, rules),
+    };
+    (report, hit)
+}
+
+/// Grades findings in the workspace-relative `file_path` with `rules`,
+/// dropping those that are off for it or allowed by `barq-allow` comments in
+/// its `source`.
+pub fn grade(findings: Vec<Diagnostic>, file_path: &str, source: &str, rules: &RuleSet) -> Vec<Diagnostic> {
+    let suppressions = Suppressions::parse(source);
+    let mut graded = Vec::new();
+    for mut diagnostic in findings {
+        if suppressions.allows(&diagnostic.rule_id, diagnostic.line()) {
+            continue;
+        }
+        diagnostic.severity = rules.severity(&diagnostic.rule_id, file_path);
+        if diagnostic.severity == Severity::Off {
+            continue;
+        }
+        diagnostic.file = Some(file_path.to_string());
+        graded.push(diagnostic);
+    }
+    graded
+}
+
+fn run_passes(file_path: &str, original: Option<&str>, source: &str) -> Vec<Diagnostic> {
+    let Ok(file) = syn::parse_file(source) else {
+        return vec![];
+    };
+    // An original that does not parse cannot vouch for any unsafe code.
+    let original_file = original.map(|o| (syn::parse_file(o).unwrap_or_else(|_| empty_file()), o));
+    let mut findings = Vec::new();
+    findings.extend(unsafe_check::check_unsafe(
+        &file,
+        source,
+        original_file.as_ref().map(|(file, source)| (file, *source)),
+    ));
+    findings.extend(borrow_hint::analyze_borrows(&file));
+    findings.extend(type_check::verify_trait_bounds(&file));
+    findings.extend(cycle_detect::detect_cycles(file_path));
+    findings.extend(security::scan_security_patterns(&file));
+    findings.extend(perf::lint_perf(&file));
+    findings
+}
+
+fn empty_file() -> syn::File {
+    syn::File {
+        shebang: None,
+        attrs: vec![],
+        items: vec![],
+    }
+}
+
+#[derive(Default)]
+pub struct AstWalker {
+    pub unsafe_blocks: Vec<syn::ExprUnsafe>,
+    /// Where `.clone()` is called.
+    pub clones: Vec<proc_macro2::Span>,
 }
 
 impl<'ast> Visit<'ast> for AstWalker {
@@ -32,14 +167,9 @@ impl<'ast> Visit<'ast> for AstWalker {
         syn::visit::visit_expr_unsafe(self, i);
     }
 
-    fn visit_expr_loop(&mut self, i: &'ast syn::ExprLoop) {
-        self.loops.push(i.clone());
-        syn::visit::visit_expr_loop(self, i);
-    }
-    
     fn visit_expr_method_call(&mut self, i: &'ast syn::ExprMethodCall) {
         if i.method == "clone" {
-            self.clones += 1;
+            self.clones.push(i.method.span());
         }
         syn::visit::visit_expr_method_call(self, i);
     }
@@ -1,16 +1,345 @@
-use super::AstWalker;
+use super::diagnostic::Diagnostic;
+use super::rules::{ALLOC_IN_LOOP, CLONE_IN_LOOP, O_N_SQUARED};
+use quote::ToTokens;
+use std::collections::HashSet;
+use syn::spanned::Spanned;
 use syn::visit::Visit;
 
-// Advanced perf linting (O(n^2), clones in loop)
-pub fn lint_perf(file_content: &str) -> Vec<String> {
-    let mut warnings = Vec::new();
-    if let Ok(file) = syn::parse_str::<syn::File>(file_content) {
-        let mut walker = AstWalker::new();
-        walker.visit_file(&file);
+/// Iterator methods whose closure argument runs once per element.
+const ITER_ADAPTERS: &[&str] = &[
+    "all", "any", "filter", "filter_map", "find", "find_map", "flat_map", "fold", "for_each", "inspect",
+    "map", "map_while", "max_by", "max_by_key", "min_by", "min_by_key", "partition", "position", "reduce",
+    "retain", "rposition", "scan", "skip_while", "sort_by", "sort_by_key", "take_while", "try_fold",
+    "try_for_each",
+];
 
-        if !walker.loops.is_empty() && walker.clones > 0 {
-            warnings.push("Detected clones being performed, and loops exist. Ensure clone is not inside an inner loop.".to_string());
+/// Iterator methods that scan until they find an element.
+const LINEAR_SEARCHES: &[&str] = &["all", "any", "find", "find_map", "position", "rposition"];
+
+/// Types whose `contains` is not a linear scan.
+const HASHED: &[&str] = &["HashSet", "HashMap", "BTreeSet", "BTreeMap", "IndexSet", "IndexMap"];
+
+/// String types, whose `contains` is a substring search rather than a scan
+/// of a collection.
+const STRINGS: &[&str] = &["str", "String", "Cow"];
+
+/// Methods that return a string.
+const STRING_METHODS: &[&str] = &[
+    "as_str", "to_string", "to_owned", "to_lowercase", "to_uppercase", "trim", "trim_start", "trim_end",
+];
+
+/// Clones, allocations and linear work repeated on every iteration of a
+/// loop: `for`, `while` and `loop` bodies, and closures passed to iterator
+/// adapters. Nested iteration is only flagged when both levels walk a
+/// collection, so loops over ranges and `while`/`loop` never count as the
+/// outer or inner level.
+pub fn lint_perf(file: &syn::File) -> Vec<Diagnostic> {
+    let mut known = KnownNames::default();
+    known.visit_file(file);
+    let mut walker = PerfWalker {
+        hashed: known.hashed,
+        strings: known.strings,
+        loop_depth: 0,
+        collection_depth: 0,
+        diagnostics: Vec::new(),
+    };
+    walker.visit_file(file);
+    walker.diagnostics
+}
+
+struct PerfWalker {
+    hashed: HashSet<String>,
+    strings: HashSet<String>,
+    /// Loops of any kind around the current expression.
+    loop_depth: usize,
+    /// Of those, loops over a collection.
+    collection_depth: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl PerfWalker {
+    fn in_loop<T>(&mut self, over_collection: bool, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.loop_depth += 1;
+        self.collection_depth += over_collection as usize;
+        let result = f(self);
+        self.loop_depth -= 1;
+        self.collection_depth -= over_collection as usize;
+        result
+    }
+
+    fn is_hashed(&self, expr: &syn::Expr) -> bool {
+        receiver_name(expr).is_some_and(|name| self.hashed.contains(&name))
+    }
+
+    fn is_string(&self, expr: &syn::Expr) -> bool {
+        is_string_expr(expr) || receiver_name(expr).is_some_and(|name| self.strings.contains(&name))
+    }
+
+    fn check_call(&mut self, i: &syn::ExprMethodCall) {
+        let method = i.method.to_string();
+        let at = i.method.span();
+        match method.as_str() {
+            "clone" if i.args.is_empty() => self.diagnostics.push(
+                Diagnostic::new(CLONE_IN_LOOP, format!("`{}.clone()` runs on every iteration.", short(&i.receiver)))
+                    .at(at)
+                    .with_fix("Borrow it, or clone once before the loop", None),
+            ),
+            "to_string" if i.args.is_empty() => self.diagnostics.push(
+                Diagnostic::new(ALLOC_IN_LOOP, "`.to_string()` allocates a String on every iteration.")
+                    .at(at)
+                    .with_fix("Reuse a buffer, or hoist the conversion out of the loop", None),
+            ),
+            "collect" => self.diagnostics.push(
+                Diagnostic::new(ALLOC_IN_LOOP, "`.collect()` allocates a collection on every iteration.")
+                    .at(at)
+                    .with_fix("Collect once outside the loop, or iterate without collecting", None),
+            ),
+            "contains"
+                if i.args.len() == 1
+                    && !is_range(&i.receiver)
+                    && !is_str_literal(&i.args[0])
+                    && !self.is_hashed(&i.receiver)
+                    && !self.is_string(&i.receiver) =>
+            {
+                self.diagnostics.push(
+                    Diagnostic::new(
+                        O_N_SQUARED,
+                        format!("`{}.contains()` is a linear search inside a loop.", short(&i.receiver)),
+                    )
+                    .at(at)
+                    .with_fix("Look up in a HashSet built before the loop", None),
+                );
+            }
+            m if LINEAR_SEARCHES.contains(&m) => {
+                if let Some(collection) = iterated_collection(&i.receiver) {
+                    if !is_range(collection) && !self.is_hashed(collection) {
+                        self.diagnostics.push(
+                            Diagnostic::new(
+                                O_N_SQUARED,
+                                format!("`.{}()` searches `{}` linearly inside a loop.", m, short(collection)),
+                            )
+                            .at(at)
+                            .with_fix("Index it in a HashMap or HashSet built before the loop", None),
+                        );
+                    }
+                }
+            }
+            _ => {}
         }
     }
-    warnings
+}
+
+impl<'ast> Visit<'ast> for PerfWalker {
+    fn visit_expr_for_loop(&mut self, i: &'ast syn::ExprForLoop) {
+        // The iterated expression is evaluated once, outside the loop.
+        self.visit_expr(&i.expr);
+        let over_collection = !is_range(&i.expr);
+        if over_collection && self.collection_depth > 0 {
+            self.diagnostics.push(
+                Diagnostic::new(
+                    O_N_SQUARED,
+                    format!("Loop over `{}` nested in a loop over a collection.", short(&i.expr)),
+                )
+                .at(i.for_token.span())
+                .with_fix("Index the inner collection in a HashMap before the outer loop", None),
+            );
+        }
+        self.in_loop(over_collection, |w| {
+            w.visit_pat(&i.pat);
+            w.visit_block(&i.body);
+        });
+    }
+
+    fn visit_expr_while(&mut self, i: &'ast syn::ExprWhile) {
+        self.in_loop(false, |w| {
+            w.visit_expr(&i.cond);
+            w.visit_block(&i.body);
+        });
+    }
+
+    fn visit_expr_loop(&mut self, i: &'ast syn::ExprLoop) {
+        self.in_loop(false, |w| w.visit_block(&i.body));
+    }
+
+    fn visit_expr_method_call(&mut self, i: &'ast syn::ExprMethodCall) {
+        self.visit_expr(&i.receiver);
+        if self.loop_depth > 0 {
+            self.check_call(i);
+        }
+
+        let method = i.method.to_string();
+        let adapter = ITER_ADAPTERS.contains(&method.as_str());
+        for arg in &i.args {
+            match arg {
+                syn::Expr::Closure(closure) if adapter => {
+                    // Only the first closure of a chain starts a new level of
+                    // iteration; `.filter(..).map(..)` is still one pass.
+                    let starts_pass = !is_adapter_call(&i.receiver);
+                    if starts_pass && self.collection_depth > 0 && !LINEAR_SEARCHES.contains(&method.as_str()) {
+                        self.diagnostics.push(
+                            Diagnostic::new(
+                                O_N_SQUARED,
+                                format!("`.{}()` iterates a collection inside a loop over a collection.", method),
+                            )
+                            .at(i.method.span())
+                            .with_fix("Index the inner collection in a HashMap before the outer loop", None),
+                        );
+                    }
+                    self.in_loop(true, |w| w.visit_expr_closure(closure));
+                }
+                _ => self.visit_expr(arg),
+            }
+        }
+    }
+
+    fn visit_macro(&mut self, i: &'ast syn::Macro) {
+        if self.loop_depth > 0 && i.path.is_ident("format") {
+            self.diagnostics.push(
+                Diagnostic::new(ALLOC_IN_LOOP, "`format!` allocates a String on every iteration.")
+                    .at(i.path.span())
+                    .with_fix("Write into a reused buffer with `write!`", None),
+            );
+        }
+        syn::visit::visit_macro(self, i);
+    }
+
+    fn visit_item_fn(&mut self, i: &'ast syn::ItemFn) {
+        // A function declared inside a loop body does not run per iteration.
+        let depths = (self.loop_depth, self.collection_depth);
+        (self.loop_depth, self.collection_depth) = (0, 0);
+        syn::visit::visit_item_fn(self, i);
+        (self.loop_depth, self.collection_depth) = depths;
+    }
+}
+
+/// Names of variables, parameters and fields declared with a hashed or a
+/// string type, or initialised from one, so their `contains` is not mistaken
+/// for a scan.
+#[derive(Default)]
+struct KnownNames {
+    hashed: HashSet<String>,
+    strings: HashSet<String>,
+}
+
+impl KnownNames {
+    fn record(&mut self, name: String, ty: &syn::Type) {
+        if is_type_in(ty, HASHED) {
+            self.hashed.insert(name);
+        } else if is_type_in(ty, STRINGS) {
+            self.strings.insert(name);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for KnownNames {
+    fn visit_pat_type(&mut self, i: &'ast syn::PatType) {
+        if let syn::Pat::Ident(ident) = &*i.pat {
+            self.record(ident.ident.to_string(), &i.ty);
+        }
+        syn::visit::visit_pat_type(self, i);
+    }
+
+    fn visit_local(&mut self, i: &'ast syn::Local) {
+        if let (syn::Pat::Ident(ident), Some(init)) = (&i.pat, &i.init) {
+            let tokens = init.expr.to_token_stream().to_string();
+            if HASHED.iter().any(|t| tokens.contains(t)) {
+                self.hashed.insert(ident.ident.to_string());
+            } else if tokens.starts_with("String ::") || is_string_expr(&init.expr) {
+                self.strings.insert(ident.ident.to_string());
+            }
+        }
+        syn::visit::visit_local(self, i);
+    }
+
+    fn visit_field(&mut self, i: &'ast syn::Field) {
+        if let Some(ident) = &i.ident {
+            self.record(ident.to_string(), &i.ty);
+        }
+        syn::visit::visit_field(self, i);
+    }
+}
+
+fn is_type_in(ty: &syn::Type, names: &[&str]) -> bool {
+    match ty {
+        syn::Type::Reference(r) => is_type_in(&r.elem, names),
+        syn::Type::Path(p) => p
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| names.contains(&s.ident.to_string().as_str())),
+        _ => false,
+    }
+}
+
+/// The variable or field an expression reads, through references and parens.
+fn receiver_name(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::Path(p) => p.path.get_ident().map(|i| i.to_string()),
+        syn::Expr::Field(f) => match &f.member {
+            syn::Member::Named(ident) => Some(ident.to_string()),
+            syn::Member::Unnamed(_) => None,
+        },
+        syn::Expr::Reference(r) => receiver_name(&r.expr),
+        syn::Expr::Paren(p) => receiver_name(&p.expr),
+        _ => None,
+    }
+}
+
+fn is_range(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Range(_) => true,
+        syn::Expr::Paren(p) => is_range(&p.expr),
+        syn::Expr::MethodCall(m) if matches!(m.method.to_string().as_str(), "rev" | "step_by") => is_range(&m.receiver),
+        _ => false,
+    }
+}
+
+fn is_str_literal(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Lit(l) => matches!(l.lit, syn::Lit::Str(_) | syn::Lit::Char(_)),
+        syn::Expr::Reference(r) => is_str_literal(&r.expr),
+        _ => false,
+    }
+}
+
+/// A string literal, `format!` or a method call that returns a string.
+fn is_string_expr(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::MethodCall(m) => STRING_METHODS.contains(&m.method.to_string().as_str()),
+        syn::Expr::Macro(m) => m.mac.path.is_ident("format"),
+        syn::Expr::Paren(p) => is_string_expr(&p.expr),
+        _ => is_str_literal(expr),
+    }
+}
+
+/// `xs` in `xs.iter()`, `xs.iter().copied()` and the like.
+fn iterated_collection(expr: &syn::Expr) -> Option<&syn::Expr> {
+    let syn::Expr::MethodCall(m) = expr else {
+        return None;
+    };
+    match m.method.to_string().as_str() {
+        "iter" | "into_iter" | "iter_mut" => Some(&m.receiver),
+        "copied" | "cloned" | "enumerate" | "rev" => iterated_collection(&m.receiver),
+        _ => None,
+    }
+}
+
+fn is_adapter_call(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::MethodCall(m) => {
+            (ITER_ADAPTERS.contains(&m.method.to_string().as_str()) && m.args.iter().any(|a| matches!(a, syn::Expr::Closure(_))))
+                || is_adapter_call(&m.receiver)
+        }
+        _ => false,
+    }
+}
+
+/// An expression's source, shortened for messages.
+fn short(expr: &syn::Expr) -> String {
+    let text = expr.to_token_stream().to_string().replace(" . ", ".").replace(" (", "(").replace("( ", "(").replace(" )", ")");
+    if text.chars().count() > 40 {
+        format!("{}…", text.chars().take(40).collect::<String>())
+    } else {
+        text
+    }
 }
@@ -1,29 +1,347 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Where project rules live, relative to the workspace root.
+pub const RULES_FILE: &str = ".barqcoder/rules.yaml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Info,
+    Warn,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Severity::Off => "off",
+            Severity::Info => "info",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DiagnosticRule {
     pub id: String,
     pub description: String,
-    pub severity: String,
+    pub severity: Severity,
+    /// Globs of workspace-relative paths the rule applies to. Empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// Globs of paths the rule never applies to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
 }
 
-pub fn load_rules() -> Vec<DiagnosticRule> {
-    // In reality this would load from a rules.yaml file
-    vec![
-        DiagnosticRule {
-            id: "UNSAFE_BLOCK".to_string(),
-            description: "Unsafe block detected and requires manual review".to_string(),
-            severity: "warn".to_string(),
-        },
-        DiagnosticRule {
-            id: "O_N_SQUARED".to_string(),
-            description: "Nested loop detected causing potential O(n^2) perf".to_string(),
-            severity: "warn".to_string(),
-        },
-        DiagnosticRule {
-            id: "CLONE_IN_LOOP".to_string(),
-            description: ".clone() called inside a loop".to_string(),
-            severity: "error".to_string(),
+/// A rule as written in `rules.yaml`. Unset fields keep the built-in value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paths: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Vec<String>>,
+}
+
+/// `rules.yaml`: rule IDs mapped to a severity, or to a full `RuleConfig`.
+///
+/// ```yaml
+/// rules:
+///   UNSAFE_BLOCK: off
+///   CLONE_IN_LOOP:
+///     severity: error
+///     paths: ["src/engine/**"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+struct RulesFile {
+    /// Parsed entry by entry, so one bad entry does not discard the rest.
+    #[serde(default)]
+    rules: BTreeMap<String, serde_yaml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RuleEntry {
+    Severity(Severity),
+    Config(RuleConfig),
+}
+
+/// The symbolic checkers' rules, keyed by ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleSet {
+    rules: BTreeMap<String, DiagnosticRule>,
+    /// Why entries of `rules.yaml` were skipped.
+    warnings: Vec<String>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl RuleSet {
+    pub fn builtin() -> Self {
+        Self {
+            rules: builtin_rules().into_iter().map(|rule| (rule.id.clone(), rule)).collect(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Built-in rules, overridden by the workspace's `rules.yaml` if it has one.
+    /// Entries that name an unknown rule or do not parse are skipped and
+    /// reported in `warnings`.
+    pub fn load(workspace: &Path) -> anyhow::Result<Self> {
+        let mut rules = Self::builtin();
+        let path = workspace.join(RULES_FILE);
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            rules
+                .apply_yaml(&content)
+                .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+            for warning in &mut rules.warnings {
+                *warning = format!("{}: {}", path.display(), warning);
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn apply_yaml(&mut self, content: &str) -> anyhow::Result<()> {
+        let file: RulesFile = serde_yaml::from_str(content)?;
+        for (id, entry) in file.rules {
+            let Some(rule) = self.rules.get_mut(&id) else {
+                let known = self.rules.keys().cloned().collect::<Vec<_>>().join(", ");
+                self.warnings.push(format!("unknown rule {} (known: {})", id, known));
+                continue;
+            };
+            let entry = match serde_yaml::from_value::<RuleEntry>(entry) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.warnings.push(format!("rule {}: {}", id, e));
+                    continue;
+                }
+            };
+            let overrides = match entry {
+                RuleEntry::Severity(severity) => RuleConfig {
+                    severity: Some(severity),
+                    ..RuleConfig::default()
+                },
+                RuleEntry::Config(config) => config,
+            };
+            if let Some(v) = overrides.severity {
+                rule.severity = v;
+            }
+            if let Some(v) = overrides.paths {
+                rule.paths = v;
+            }
+            if let Some(v) = overrides.exclude {
+                rule.exclude = v;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&DiagnosticRule> {
+        self.rules.get(id)
+    }
+
+    /// Why entries of the loaded `rules.yaml` were skipped.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The severity of `id` for a file at workspace-relative `path`: `Off`
+    /// when the rule is unknown or scoped away from the file.
+    pub fn severity(&self, id: &str, path: &str) -> Severity {
+        let Some(rule) = self.get(id) else {
+            return Severity::Off;
+        };
+        let path = path.replace('\\', "/");
+        let path = path.trim_start_matches("./");
+        let included = rule.paths.is_empty() || rule.paths.iter().any(|g| glob_match(g, path));
+        if !included || rule.exclude.iter().any(|g| glob_match(g, path)) {
+            return Severity::Off;
         }
+        rule.severity
+    }
+}
+
+pub const UNSAFE_BLOCK: &str = "UNSAFE_BLOCK";
+pub const MISSING_SAFETY_COMMENT: &str = "MISSING_SAFETY_COMMENT";
+pub const EXCESSIVE_CLONE: &str = "EXCESSIVE_CLONE";
+pub const DEAD_CODE: &str = "DEAD_CODE";
+pub const TRAIT_BOUND: &str = "TRAIT_BOUND";
+pub const IMPORT_CYCLE: &str = "IMPORT_CYCLE";
+pub const INSECURE_PATTERN: &str = "INSECURE_PATTERN";
+pub const O_N_SQUARED: &str = "O_N_SQUARED";
+pub const CLONE_IN_LOOP: &str = "CLONE_IN_LOOP";
+pub const ALLOC_IN_LOOP: &str = "ALLOC_IN_LOOP";
+
+fn builtin_rules() -> Vec<DiagnosticRule> {
+    let rule = |id: &str, description: &str, severity: Severity| DiagnosticRule {
+        id: id.to_string(),
+        description: description.to_string(),
+        severity,
+        paths: vec![],
+        exclude: vec![],
+    };
+    vec![
+        rule(UNSAFE_BLOCK, "New unsafe block, fn, impl or trait that requires manual review", Severity::Error),
+        rule(MISSING_SAFETY_COMMENT, "Unsafe block or impl without a // SAFETY: comment", Severity::Warn),
+        rule(EXCESSIVE_CLONE, "Many .clone() calls where borrowing may do", Severity::Warn),
+        rule(DEAD_CODE, "Item is never referenced", Severity::Warn),
+        rule(TRAIT_BOUND, "Trait bound is not satisfied", Severity::Error),
+        rule(IMPORT_CYCLE, "Modules import each other in a cycle", Severity::Error),
+        rule(INSECURE_PATTERN, "String-built SQL or unchecked path join", Severity::Error),
+        rule(O_N_SQUARED, "Nested loops over collections, or a linear search inside a loop", Severity::Warn),
+        rule(CLONE_IN_LOOP, ".clone() called inside a loop body or iterator closure", Severity::Warn),
+        rule(ALLOC_IN_LOOP, "format!, to_string or collect on every loop iteration", Severity::Warn),
     ]
 }
+
+/// Matches a workspace-relative path against a glob: `*` and `?` stay within
+/// a path segment, `**` spans any number of them.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn segments(p: &[&str], s: &[&str]) -> bool {
+        match p.split_first() {
+            None => s.is_empty(),
+            Some((&"**", rest)) => (0..=s.len()).any(|i| segments(rest, &s[i..])),
+            Some((first, rest)) => match s.split_first() {
+                Some((seg, s_rest)) => segment(first.as_bytes(), seg.as_bytes()) && segments(rest, s_rest),
+                None => false,
+            },
+        }
+    }
+    fn segment(p: &[u8], s: &[u8]) -> bool {
+        match p.split_first() {
+            None => s.is_empty(),
+            Some((b'*', rest)) => (0..=s.len()).any(|i| segment(rest, &s[i..])),
+            Some((b'?', rest)) => !s.is_empty() && segment(rest, &s[1..]),
+            Some((c, rest)) => s.first() == Some(c) && segment(rest, &s[1..]),
+        }
+    }
+    let pattern: Vec<&str> = pattern.trim_start_matches("./").split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    segments(&pattern, &path)
+}
+
+/// `// barq-allow(RULE): reason` comments in a file. A comment alone on its
+/// line covers the next line of code, a trailing one its own line, and an
+/// inner doc comment (`//! barq-allow(...)`) the whole file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Suppressions {
+    file: Vec<String>,
+    /// 1-based line numbers and the rules allowed on them.
+    lines: BTreeMap<usize, Vec<String>>,
+}
+
+impl Suppressions {
+    pub fn parse(source: &str) -> Self {
+        let mut suppressions = Self::default();
+        let mut pending: Vec<String> = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let trimmed = line.trim_start();
+            let rules = allowed_rules(line);
+            if trimmed.starts_with("//!") {
+                suppressions.file.extend(rules);
+                continue;
+            }
+            if trimmed.starts_with("//") {
+                pending.extend(rules);
+                continue;
+            }
+            if trimmed.is_empty() || trimmed.starts_with("#!") {
+                continue;
+            }
+            let here = suppressions.lines.entry(i + 1).or_default();
+            here.append(&mut pending);
+            here.extend(rules);
+        }
+        suppressions.lines.retain(|_, rules| !rules.is_empty());
+        suppressions
+    }
+
+    /// Whether `rule` is allowed at `line` (1-based), or file-wide when the
+    /// finding has no line.
+    pub fn allows(&self, rule: &str, line: Option<usize>) -> bool {
+        if self.file.iter().any(|r| r == rule) {
+            return true;
+        }
+        line.and_then(|l| self.lines.get(&l)).is_some_and(|rules| rules.iter().any(|r| r == rule))
+    }
+}
+
+/// Rule IDs named by the `barq-allow(A, B)` comments on a line.
+fn allowed_rules(line: &str) -> Vec<String> {
+    let Some(comment) = comment_start(line).map(|i| &line[i..]) else {
+        return vec![];
+    };
+    let mut rules = Vec::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find("barq-allow(") {
+        rest = &rest[start + "barq-allow(".len()..];
+        let Some(end) = rest.find(')') else {
+            break;
+        };
+        rules.extend(rest[..end].split(',').map(|r| r.trim().to_string()).filter(|r| !r.is_empty()));
+        rest = &rest[end..];
+    }
+    rules
+}
+
+/// Byte offset of the `//` comment on a line, skipping string and char
+/// literals. Strings spanning lines are not tracked.
+fn comment_start(line: &str) -> Option<usize> {
+    let mut chars = line.char_indices().peekable();
+    let mut prev_ident = false;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' if line[i + 1..].starts_with('/') => return Some(i),
+            'r' if !prev_ident && line[i + 1..].trim_start_matches('#').starts_with('"') => {
+                let hashes = line[i + 1..].len() - line[i + 1..].trim_start_matches('#').len();
+                let close = format!("\"{}", "#".repeat(hashes));
+                let body = i + 1 + hashes + 1;
+                let end = line[body..].find(&close).map_or(line.len(), |e| body + e + close.len());
+                while chars.next_if(|(j, _)| *j < end).is_some() {}
+                prev_ident = false;
+                continue;
+            }
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            // A char literal; a lifetime has no closing quote after one char.
+            '\'' => {
+                let rest = &line[i + 1..];
+                let mut literal = rest.chars();
+                let len = match literal.next() {
+                    Some('\\') => rest.get(2..).and_then(|r| r.find('\'')).map(|e| e + 3),
+                    Some(c) if literal.next() == Some('\'') => Some(c.len_utf8() + 1),
+                    _ => None,
+                };
+                if let Some(len) = len {
+                    let end = i + 1 + len;
+                    while chars.next_if(|(j, _)| *j < end).is_some() {}
+                }
+            }
+            _ => {}
+        }
+        prev_ident = c.is_alphanumeric() || c == '_';
+    }
+    None
+}
@@ -1,4 +1,4 @@
 // Scans AST for string concatenation in SQL or vulnerable path joins. Mocked.
-pub fn scan_security_patterns(_file_content: &str) -> Vec<String> {
+pub fn scan_security_patterns(_file: &syn::File) -> Vec<super::diagnostic::Diagnostic> {
     vec![]
 }
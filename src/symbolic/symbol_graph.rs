@@ -0,0 +1,593 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Module,
+    Fn,
+    Method,
+    Struct,
+    Enum,
+    Variant,
+    Union,
+    Trait,
+    TypeAlias,
+    Const,
+    Static,
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SymbolKind::Module => "module",
+            SymbolKind::Fn => "function",
+            SymbolKind::Method => "method",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Variant => "variant",
+            SymbolKind::Union => "union",
+            SymbolKind::Trait => "trait",
+            SymbolKind::TypeAlias => "type alias",
+            SymbolKind::Const => "constant",
+            SymbolKind::Static => "static",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Why a symbol is used even when nothing in the crate names it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Root {
+    /// `main` of a binary, the public API of a library, or an exported symbol.
+    Entry,
+    /// A `#[test]` or `#[bench]` function.
+    Test,
+}
+
+/// One item definition in one crate target.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// File it is defined in, relative to the crate root directory.
+    pub file: String,
+    pub span: proc_macro2::Span,
+    /// Index into `SymbolGraph::targets`.
+    pub target: usize,
+    pub root: Option<Root>,
+    /// Inside `#[cfg(test)]` code.
+    pub test_code: bool,
+    /// The enum of a variant, the type or trait of a method, otherwise the
+    /// enclosing module; `None` at the crate root.
+    pub owner: Option<usize>,
+    /// Names the symbol's definition mentions.
+    pub refs: BTreeSet<String>,
+}
+
+/// Item definitions of a crate and the names each one refers to, for every
+/// target (library and binaries) the crate builds. Names are resolved by
+/// identifier only, so a reference to `new` reaches every `new` of the
+/// target: reachability over this graph may keep dead code alive, but never
+/// the reverse.
+#[derive(Debug, Default)]
+pub struct SymbolGraph {
+    /// Crate root file of each target, relative to the crate directory.
+    pub targets: Vec<String>,
+    pub symbols: Vec<Symbol>,
+    /// `use a as b` renames per target: `b` → `a`.
+    aliases: Vec<HashMap<String, Vec<String>>>,
+    by_name: Vec<HashMap<String, Vec<usize>>>,
+}
+
+impl SymbolGraph {
+    /// Extracts the graph of the crate in `root`: `src/lib.rs`, `src/main.rs`
+    /// and `src/bin/*`, following `mod` declarations.
+    pub fn extract(root: &Path) -> anyhow::Result<Self> {
+        let mut graph = Self::default();
+        let mut roots = Vec::new();
+        for candidate in ["src/lib.rs", "src/main.rs"] {
+            if root.join(candidate).is_file() {
+                roots.push(candidate.to_string());
+            }
+        }
+        if let Ok(entries) = std::fs::read_dir(root.join("src/bin")) {
+            let mut bins: Vec<String> = entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let path = e.path();
+                    if path.extension().is_some_and(|x| x == "rs") {
+                        Some(format!("src/bin/{}", e.file_name().to_string_lossy()))
+                    } else if path.join("main.rs").is_file() {
+                        Some(format!("src/bin/{}/main.rs", e.file_name().to_string_lossy()))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            bins.sort();
+            roots.extend(bins);
+        }
+        if roots.is_empty() {
+            anyhow::bail!("{} has no src/lib.rs, src/main.rs or src/bin", root.display());
+        }
+
+        for (target, crate_root) in roots.iter().enumerate() {
+            graph.targets.push(crate_root.clone());
+            graph.aliases.push(HashMap::new());
+            let is_lib = crate_root == "src/lib.rs";
+            let mut walker = ModuleWalker {
+                graph: &mut graph,
+                root,
+                target,
+                is_lib,
+                impl_methods: Vec::new(),
+            };
+            walker.file(crate_root, None, Scope::crate_root(is_lib))?;
+            walker.resolve_impl_owners();
+        }
+
+        for target in 0..graph.targets.len() {
+            let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+            for (i, symbol) in graph.symbols.iter().enumerate().filter(|(_, s)| s.target == target) {
+                by_name.entry(symbol.name.clone()).or_default().push(i);
+            }
+            graph.by_name.push(by_name);
+        }
+        Ok(graph)
+    }
+
+    /// Symbols a name mentioned in `target` may refer to.
+    pub fn resolve(&self, target: usize, name: &str) -> Vec<usize> {
+        let mut found = self.by_name[target].get(name).cloned().unwrap_or_default();
+        if let Some(originals) = self.aliases[target].get(name) {
+            for original in originals {
+                found.extend(self.by_name[target].get(original).into_iter().flatten());
+            }
+        }
+        found
+    }
+
+    /// Symbols reachable from those with one of `roots`.
+    pub fn reachable(&self, roots: &[Root]) -> Vec<bool> {
+        // Owners pull in what is used through them without being named:
+        // trait impl methods with their type, trait methods with their trait.
+        let mut implied: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            if let (SymbolKind::Method, Some(owner)) = (symbol.kind, symbol.owner) {
+                if symbol.refs.contains(IMPLIED) {
+                    implied.entry(owner).or_default().push(i);
+                }
+            }
+        }
+
+        let mut live = vec![false; self.symbols.len()];
+        let mut stack: Vec<usize> = (0..self.symbols.len())
+            .filter(|&i| self.symbols[i].root.is_some_and(|r| roots.contains(&r)))
+            .collect();
+        while let Some(i) = stack.pop() {
+            if std::mem::replace(&mut live[i], true) {
+                continue;
+            }
+            let symbol = &self.symbols[i];
+            for name in &symbol.refs {
+                stack.extend(self.resolve(symbol.target, name).into_iter().filter(|&j| !live[j]));
+            }
+            stack.extend(implied.get(&i).into_iter().flatten().filter(|&&j| !live[j]));
+        }
+        live
+    }
+}
+
+/// Marks methods that are used whenever their owner is (see `reachable`).
+const IMPLIED: &str = "<implied>";
+
+/// What items inherit from the module or block they are declared in.
+#[derive(Clone, Copy)]
+struct Scope {
+    /// Every enclosing module is `pub`, so `pub` items are public API.
+    public_path: bool,
+    test_code: bool,
+}
+
+impl Scope {
+    fn crate_root(is_lib: bool) -> Self {
+        Self {
+            public_path: is_lib,
+            test_code: false,
+        }
+    }
+}
+
+struct ModuleWalker<'a> {
+    graph: &'a mut SymbolGraph,
+    root: &'a Path,
+    target: usize,
+    is_lib: bool,
+    /// Methods of impl blocks, the type they are for and whether the block
+    /// implements a trait; owners are resolved once every type is known.
+    impl_methods: Vec<(usize, Option<String>, bool)>,
+}
+
+impl ModuleWalker<'_> {
+    fn file(&mut self, rel: &str, owner: Option<usize>, scope: Scope) -> anyhow::Result<()> {
+        let source = std::fs::read_to_string(self.root.join(rel))?;
+        let file = syn::parse_file(&source).map_err(|e| anyhow::anyhow!("{}: {}", rel, e))?;
+        let scope = Scope {
+            test_code: scope.test_code || is_cfg_test(&file.attrs),
+            ..scope
+        };
+        self.items(&file.items, rel, owner, scope);
+        Ok(())
+    }
+
+    fn add(&mut self, mut symbol: Symbol) -> usize {
+        if !matches!(symbol.kind, SymbolKind::Fn | SymbolKind::Method) {
+            // A type naming itself does not make it used.
+            symbol.refs.remove(&symbol.name);
+        }
+        self.graph.symbols.push(symbol);
+        self.graph.symbols.len() - 1
+    }
+
+    fn symbol(&self, name: &syn::Ident, kind: SymbolKind, file: &str, scope: Scope) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            kind,
+            file: file.to_string(),
+            span: name.span(),
+            target: self.target,
+            root: None,
+            test_code: scope.test_code,
+            owner: None,
+            refs: BTreeSet::new(),
+        }
+    }
+
+    fn items(&mut self, items: &[syn::Item], file: &str, owner: Option<usize>, scope: Scope) {
+        for item in items {
+            let attrs = item_attrs(item);
+            let scope = Scope {
+                test_code: scope.test_code || is_cfg_test(attrs),
+                ..scope
+            };
+            let public = |vis: &syn::Visibility| scope.public_path && matches!(vis, syn::Visibility::Public(_));
+            match item {
+                syn::Item::Fn(i) => {
+                    let mut symbol = self.symbol(&i.sig.ident, SymbolKind::Fn, file, scope);
+                    symbol.refs = fn_refs(&i.attrs, &i.sig, i.block.to_token_stream());
+                    symbol.owner = owner;
+                    symbol.root = if is_test_fn(&i.attrs) {
+                        Some(Root::Test)
+                    } else if public(&i.vis)
+                        || is_exported(&i.attrs)
+                        || (!self.is_lib && owner.is_none() && i.sig.ident == "main")
+                    {
+                        Some(Root::Entry)
+                    } else {
+                        None
+                    };
+                    self.add(symbol);
+                }
+                syn::Item::Struct(i) => {
+                    let mut symbol = self.symbol(&i.ident, SymbolKind::Struct, file, scope);
+                    symbol.refs = idents(i.to_token_stream());
+                    symbol.owner = owner;
+                    symbol.root = public(&i.vis).then_some(Root::Entry);
+                    self.add(symbol);
+                }
+                syn::Item::Union(i) => {
+                    let mut symbol = self.symbol(&i.ident, SymbolKind::Union, file, scope);
+                    symbol.refs = idents(i.to_token_stream());
+                    symbol.owner = owner;
+                    symbol.root = public(&i.vis).then_some(Root::Entry);
+                    self.add(symbol);
+                }
+                syn::Item::Enum(i) => {
+                    let mut symbol = self.symbol(&i.ident, SymbolKind::Enum, file, scope);
+                    // Naming the enum does not use its variants.
+                    for attr in &i.attrs {
+                        symbol.refs.extend(idents(attr.to_token_stream()));
+                    }
+                    for variant in &i.variants {
+                        symbol.refs.extend(idents(variant.fields.to_token_stream()));
+                        if let Some((_, discriminant)) = &variant.discriminant {
+                            symbol.refs.extend(idents(discriminant.to_token_stream()));
+                        }
+                    }
+                    symbol.owner = owner;
+                    symbol.root = public(&i.vis).then_some(Root::Entry);
+                    let enum_index = self.add(symbol);
+                    for variant in &i.variants {
+                        let mut symbol = self.symbol(&variant.ident, SymbolKind::Variant, file, scope);
+                        symbol.owner = Some(enum_index);
+                        symbol.root = public(&i.vis).then_some(Root::Entry);
+                        self.add(symbol);
+                    }
+                }
+                syn::Item::Trait(i) => {
+                    let mut symbol = self.symbol(&i.ident, SymbolKind::Trait, file, scope);
+                    symbol.refs = idents(i.to_token_stream());
+                    symbol.owner = owner;
+                    symbol.root = public(&i.vis).then_some(Root::Entry);
+                    let trait_index = self.add(symbol);
+                    for trait_item in &i.items {
+                        if let syn::TraitItem::Fn(f) = trait_item {
+                            let mut symbol = self.symbol(&f.sig.ident, SymbolKind::Method, file, scope);
+                            symbol.refs = fn_refs(&f.attrs, &f.sig, f.default.to_token_stream());
+                            symbol.refs.insert(IMPLIED.to_string());
+                            symbol.owner = Some(trait_index);
+                            self.add(symbol);
+                        }
+                    }
+                }
+                syn::Item::Type(i) => {
+                    let mut symbol = self.symbol(&i.ident, SymbolKind::TypeAlias, file, scope);
+                    symbol.refs = idents(i.to_token_stream());
+                    symbol.owner = owner;
+                    symbol.root = public(&i.vis).then_some(Root::Entry);
+                    self.add(symbol);
+                }
+                syn::Item::Const(i) => {
+                    let mut symbol = self.symbol(&i.ident, SymbolKind::Const, file, scope);
+                    symbol.refs = idents(i.to_token_stream());
+                    symbol.owner = owner;
+                    symbol.root = public(&i.vis).then_some(Root::Entry);
+                    self.add(symbol);
+                }
+                syn::Item::Static(i) => {
+                    let mut symbol = self.symbol(&i.ident, SymbolKind::Static, file, scope);
+                    symbol.refs = idents(i.to_token_stream());
+                    symbol.owner = owner;
+                    symbol.root = (public(&i.vis) || is_exported(&i.attrs)).then_some(Root::Entry);
+                    self.add(symbol);
+                }
+                syn::Item::Impl(i) => self.impl_block(i, file, scope),
+                syn::Item::Use(i) => self.use_item(i, scope),
+                syn::Item::Macro(i) => {
+                    // Items a macro_rules! expands to are invisible, so
+                    // whatever it names is taken to be used.
+                    let mut symbol = match &i.ident {
+                        Some(ident) => self.symbol(ident, SymbolKind::Fn, file, scope),
+                        None => continue,
+                    };
+                    symbol.refs = idents(i.mac.tokens.clone());
+                    symbol.root = Some(Root::Entry);
+                    self.add(symbol);
+                }
+                syn::Item::Mod(i) => {
+                    let mut symbol = self.symbol(&i.ident, SymbolKind::Module, file, scope);
+                    symbol.owner = owner;
+                    let module_index = self.add(symbol);
+                    let scope = Scope {
+                        public_path: scope.public_path && matches!(i.vis, syn::Visibility::Public(_)),
+                        ..scope
+                    };
+                    match &i.content {
+                        Some((_, items)) => self.items(items, file, Some(module_index), scope),
+                        None => {
+                            if let Some(child) = self.module_file(file, &i.ident.to_string(), &i.attrs) {
+                                // A missing or unparsable module file leaves
+                                // the module empty rather than failing the graph.
+                                let _ = self.file(&child, Some(module_index), scope);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn impl_block(&mut self, i: &syn::ItemImpl, file: &str, scope: Scope) {
+        let self_name = type_name(&i.self_ty);
+        let trait_name = i.trait_.as_ref().and_then(|(_, path, _)| path.segments.last()).map(|s| s.ident.to_string());
+        for impl_item in &i.items {
+            let syn::ImplItem::Fn(f) = impl_item else {
+                continue;
+            };
+            let scope = Scope {
+                test_code: scope.test_code || is_cfg_test(&f.attrs),
+                ..scope
+            };
+            let mut symbol = self.symbol(&f.sig.ident, SymbolKind::Method, file, scope);
+            symbol.refs = fn_refs(&f.attrs, &f.sig, f.block.to_token_stream());
+            symbol.refs.extend(idents(i.self_ty.to_token_stream()));
+            symbol.refs.extend(idents(i.generics.to_token_stream()));
+            if let Some(name) = &trait_name {
+                // Trait methods run through the trait, wherever the type goes.
+                symbol.refs.insert(name.clone());
+                symbol.refs.insert(IMPLIED.to_string());
+            }
+            if symbol.refs.remove("Self") {
+                symbol.refs.extend(self_name.clone());
+            }
+            symbol.root = (scope.public_path && matches!(f.vis, syn::Visibility::Public(_))).then_some(Root::Entry);
+            let index = self.add(symbol);
+            self.impl_methods.push((index, self_name.clone(), trait_name.is_some()));
+        }
+    }
+
+    fn resolve_impl_owners(&mut self) {
+        for (index, self_name, trait_impl) in std::mem::take(&mut self.impl_methods) {
+            let owner = self_name.and_then(|name| {
+                self.graph
+                    .symbols
+                    .iter()
+                    .position(|s| s.target == self.target && s.name == name && is_type(s.kind))
+            });
+            let method = &mut self.graph.symbols[index];
+            method.owner = owner;
+            if trait_impl && owner.is_none() {
+                // A trait impl for a type this crate does not define is
+                // reachable through the foreign type.
+                method.root = Some(Root::Entry);
+            }
+        }
+    }
+
+    fn use_item(&mut self, i: &syn::ItemUse, scope: Scope) {
+        fn walk(tree: &syn::UseTree, out: &mut Vec<(String, Option<String>)>) {
+            match tree {
+                syn::UseTree::Path(p) => walk(&p.tree, out),
+                syn::UseTree::Name(n) => out.push((n.ident.to_string(), None)),
+                syn::UseTree::Rename(r) => out.push((r.ident.to_string(), Some(r.rename.to_string()))),
+                syn::UseTree::Glob(_) => {}
+                syn::UseTree::Group(g) => g.items.iter().for_each(|t| walk(t, out)),
+            }
+        }
+        let mut names = Vec::new();
+        walk(&i.tree, &mut names);
+        for (name, rename) in &names {
+            if let Some(rename) = rename {
+                self.graph.aliases[self.target].entry(rename.clone()).or_default().push(name.clone());
+            }
+        }
+        if scope.public_path && matches!(i.vis, syn::Visibility::Public(_)) {
+            // A re-export is a root for whatever it names.
+            let mut symbol = Symbol {
+                name: String::new(),
+                kind: SymbolKind::Module,
+                file: String::new(),
+                span: proc_macro2::Span::call_site(),
+                target: self.target,
+                root: Some(Root::Entry),
+                test_code: scope.test_code,
+                owner: None,
+                refs: BTreeSet::new(),
+            };
+            symbol.refs.extend(names.into_iter().map(|(name, _)| name));
+            self.add(symbol);
+        }
+    }
+
+    /// `dir/name.rs` or `dir/name/mod.rs` next to a `mod.rs`, `lib.rs` or
+    /// `main.rs`; `dir/file/name.rs` next to any other file; or `#[path]`.
+    fn module_file(&self, parent: &str, name: &str, attrs: &[syn::Attribute]) -> Option<String> {
+        let parent = Path::new(parent);
+        let dir = parent.parent().unwrap_or(Path::new(""));
+        let stem = parent.file_stem()?.to_string_lossy();
+        let base: PathBuf = if matches!(stem.as_ref(), "mod" | "lib" | "main") || self.graph.targets.last().map(String::as_str) == parent.to_str() {
+            dir.to_path_buf()
+        } else {
+            dir.join(stem.as_ref())
+        };
+        for attr in attrs {
+            if let syn::Meta::NameValue(nv) = &attr.meta {
+                if nv.path.is_ident("path") {
+                    if let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) = &nv.value {
+                        return Some(dir.join(s.value()).to_string_lossy().to_string());
+                    }
+                }
+            }
+        }
+        [base.join(format!("{}.rs", name)), base.join(name).join("mod.rs")]
+            .into_iter()
+            .find(|p| self.root.join(p).is_file())
+            .map(|p| p.to_string_lossy().to_string())
+    }
+}
+
+fn is_type(kind: SymbolKind) -> bool {
+    matches!(
+        kind,
+        SymbolKind::Struct | SymbolKind::Enum | SymbolKind::Union | SymbolKind::TypeAlias | SymbolKind::Trait
+    )
+}
+
+/// The name of the type an impl block is for: `Foo` in `impl<T> Foo<T>`.
+fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+        syn::Type::Reference(r) => type_name(&r.elem),
+        syn::Type::Paren(p) => type_name(&p.elem),
+        _ => None,
+    }
+}
+
+/// Every identifier in the tokens, including inside macro invocations, plus
+/// the segments of string literals that look like paths (`#[serde(default =
+/// "module::function")]`).
+fn idents(tokens: TokenStream) -> BTreeSet<String> {
+    fn walk(tokens: TokenStream, out: &mut BTreeSet<String>) {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) => {
+                    out.insert(ident.to_string().trim_start_matches("r#").to_string());
+                }
+                TokenTree::Group(group) => walk(group.stream(), out),
+                TokenTree::Literal(lit) => {
+                    let text = lit.to_string();
+                    if let Some(inner) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+                        let is_path = !inner.is_empty()
+                            && inner.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+                            && !inner.starts_with(|c: char| c.is_numeric());
+                        if is_path {
+                            out.extend(inner.split("::").filter(|s| !s.is_empty()).map(str::to_string));
+                        }
+                    }
+                }
+                TokenTree::Punct(_) => {}
+            }
+        }
+    }
+    let mut out = BTreeSet::new();
+    walk(tokens, &mut out);
+    out
+}
+
+/// What a function mentions besides its own name, which would otherwise
+/// reach every function sharing it.
+fn fn_refs(attrs: &[syn::Attribute], sig: &syn::Signature, body: TokenStream) -> BTreeSet<String> {
+    let mut refs = idents(body);
+    for attr in attrs {
+        refs.extend(idents(attr.to_token_stream()));
+    }
+    refs.extend(idents(sig.generics.to_token_stream()));
+    refs.extend(idents(sig.inputs.to_token_stream()));
+    refs.extend(idents(sig.output.to_token_stream()));
+    refs
+}
+
+fn item_attrs(item: &syn::Item) -> &[syn::Attribute] {
+    match item {
+        syn::Item::Const(i) => &i.attrs,
+        syn::Item::Enum(i) => &i.attrs,
+        syn::Item::Fn(i) => &i.attrs,
+        syn::Item::Impl(i) => &i.attrs,
+        syn::Item::Macro(i) => &i.attrs,
+        syn::Item::Mod(i) => &i.attrs,
+        syn::Item::Static(i) => &i.attrs,
+        syn::Item::Struct(i) => &i.attrs,
+        syn::Item::Trait(i) => &i.attrs,
+        syn::Item::Type(i) => &i.attrs,
+        syn::Item::Union(i) => &i.attrs,
+        syn::Item::Use(i) => &i.attrs,
+        _ => &[],
+    }
+}
+
+fn is_cfg_test(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg") && attr.meta.to_token_stream().to_string().replace(' ', "") == "cfg(test)"
+    })
+}
+
+/// `#[test]`, `#[tokio::test]`, `#[bench]` and the like.
+fn is_test_fn(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == "test" || s.ident == "bench")
+    })
+}
+
+/// Exported under a fixed symbol name, so callable from outside Rust.
+fn is_exported(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        let text = attr.meta.to_token_stream().to_string();
+        attr.path().is_ident("no_mangle") || attr.path().is_ident("export_name") || text.starts_with("unsafe (no_mangle")
+    })
+}
@@ -1,4 +1,4 @@
 // Verifies trait bounds. Mocked for this scope.
-pub fn verify_trait_bounds(_file_content: &str) -> Vec<String> {
+pub fn verify_trait_bounds(_file: &syn::File) -> Vec<super::diagnostic::Diagnostic> {
     vec![]
 }
@@ -1,15 +1,386 @@
-use super::AstWalker;
+use super::diagnostic::Diagnostic;
+use super::rules::{MISSING_SAFETY_COMMENT, UNSAFE_BLOCK};
+use quote::ToTokens;
+use std::collections::{BTreeMap, HashSet};
+use syn::spanned::Spanned;
 use syn::visit::Visit;
 
-pub fn check_unsafe(file_content: &str) -> Vec<String> {
-    let mut diagnostics = Vec::new();
-    if let Ok(file) = syn::parse_str::<syn::File>(file_content) {
-        let mut walker = AstWalker::new();
-        walker.visit_file(&file);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsafeKind {
+    Block,
+    Fn,
+    Impl,
+    Trait,
+}
+
+/// What an unsafe block does that needs it to be unsafe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnsafeOp {
+    /// `*expr` on something known to be a raw pointer: a binding or field
+    /// declared as one, a cast to one, or a pointer-returning call.
+    RawPointerDeref,
+    FfiCall,
+    Transmute,
+    StaticMutAccess,
+    UncheckedAccess,
+    UnsafeFnCall,
+}
+
+impl std::fmt::Display for UnsafeOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            UnsafeOp::RawPointerDeref => "raw pointer deref",
+            UnsafeOp::FfiCall => "FFI call",
+            UnsafeOp::Transmute => "transmute",
+            UnsafeOp::StaticMutAccess => "static mut access",
+            UnsafeOp::UncheckedAccess => "unchecked access",
+            UnsafeOp::UnsafeFnCall => "unsafe fn call",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// One place unsafe code appears.
+#[derive(Debug, Clone)]
+pub struct UnsafeSite {
+    pub kind: UnsafeKind,
+    /// `unsafe fn`, `unsafe impl Send for T`, or the function holding the block.
+    pub name: String,
+    pub ops: Vec<UnsafeOp>,
+    pub has_safety_comment: bool,
+    pub span: proc_macro2::Span,
+    /// Identifies the site across versions of the file: the signature for
+    /// functions, the header for impls, and for blocks only what they do, so
+    /// editing a block's body or renaming its function keeps it the same
+    /// site. Sites with equal fingerprints are matched by count.
+    fingerprint: String,
+}
 
-        if !walker.unsafe_blocks.is_empty() {
-            diagnostics.push(format!("Found {} unsafe blocks. Please ensure they are necessary.", walker.unsafe_blocks.len()));
+/// Every unsafe block, `unsafe fn`, `unsafe impl` and `unsafe trait` in the
+/// file, with what each block does and whether a `// SAFETY:` comment
+/// justifies it.
+pub fn audit(file: &syn::File, source: &str) -> Vec<UnsafeSite> {
+    let mut declared = Declared::default();
+    declared.visit_file(file);
+    let mut finder = SiteFinder {
+        declared: &declared,
+        lines: source.lines().collect(),
+        context: String::new(),
+        sites: Vec::new(),
+    };
+    finder.visit_file(file);
+    finder.sites
+}
+
+/// Audits the patched file. Unsafe code that was not in `original` (all of
+/// it when there is no original) is an `UNSAFE_BLOCK` finding; blocks and
+/// impls without a `// SAFETY:` comment are `MISSING_SAFETY_COMMENT` ones,
+/// whether new or not.
+pub fn check_unsafe(file: &syn::File, source: &str, original: Option<(&syn::File, &str)>) -> Vec<Diagnostic> {
+    let mut before: BTreeMap<String, usize> = BTreeMap::new();
+    if let Some((file, source)) = original {
+        for site in audit(file, source) {
+            *before.entry(site.fingerprint).or_default() += 1;
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    for site in audit(file, source) {
+        let existing = match before.get_mut(&site.fingerprint) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        };
+        if !existing {
+            diagnostics.push(Diagnostic::new(UNSAFE_BLOCK, describe(&site)).at(site.span));
+        }
+        if !site.has_safety_comment && matches!(site.kind, UnsafeKind::Block | UnsafeKind::Impl) {
+            diagnostics.push(
+                Diagnostic::new(
+                    MISSING_SAFETY_COMMENT,
+                    format!("{} has no `// SAFETY:` comment.", label(&site)),
+                )
+                .at(site.span)
+                .with_fix("Explain why the invariants hold in a `// SAFETY:` comment above it", None),
+            );
         }
     }
     diagnostics
 }
+
+fn label(site: &UnsafeSite) -> String {
+    match site.kind {
+        UnsafeKind::Block if site.name.is_empty() => "Unsafe block".to_string(),
+        UnsafeKind::Block => format!("Unsafe block in `{}`", site.name),
+        UnsafeKind::Fn => format!("`unsafe fn {}`", site.name),
+        UnsafeKind::Impl => format!("`{}`", site.name),
+        UnsafeKind::Trait => format!("`unsafe trait {}`", site.name),
+    }
+}
+
+fn describe(site: &UnsafeSite) -> String {
+    let mut text = format!("New unsafe code: {}", label(site));
+    if !site.ops.is_empty() {
+        let ops: Vec<String> = site.ops.iter().map(|op| op.to_string()).collect();
+        text.push_str(&format!(" ({})", ops.join(", ")));
+    }
+    text.push('.');
+    text
+}
+
+/// Names declared in the file that make a use unsafe.
+#[derive(Default)]
+struct Declared {
+    extern_fns: HashSet<String>,
+    unsafe_fns: HashSet<String>,
+    static_muts: HashSet<String>,
+    /// Bindings and fields holding raw pointers.
+    raw_ptrs: HashSet<String>,
+}
+
+impl Declared {
+    fn is_raw_ptr(&self, expr: &syn::Expr) -> bool {
+        match expr {
+            syn::Expr::Path(p) => p.path.get_ident().is_some_and(|i| self.raw_ptrs.contains(&i.to_string())),
+            syn::Expr::Field(f) => matches!(&f.member, syn::Member::Named(i) if self.raw_ptrs.contains(&i.to_string())),
+            syn::Expr::Cast(c) => matches!(*c.ty, syn::Type::Ptr(_)),
+            syn::Expr::Paren(p) => self.is_raw_ptr(&p.expr),
+            syn::Expr::MethodCall(m) => match m.method.to_string().as_str() {
+                "as_ptr" | "as_mut_ptr" | "cast" | "cast_mut" | "cast_const" => true,
+                "add" | "sub" | "offset" | "byte_add" | "byte_sub" | "wrapping_add" | "wrapping_sub"
+                | "wrapping_offset" => self.is_raw_ptr(&m.receiver),
+                _ => false,
+            },
+            syn::Expr::Call(c) => matches!(
+                &*c.func,
+                syn::Expr::Path(p) if p.path.segments.last().is_some_and(|s| s.ident == "null" || s.ident == "null_mut")
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Declared {
+    fn visit_foreign_item_fn(&mut self, i: &'ast syn::ForeignItemFn) {
+        self.extern_fns.insert(i.sig.ident.to_string());
+    }
+
+    fn visit_foreign_item_static(&mut self, i: &'ast syn::ForeignItemStatic) {
+        self.static_muts.insert(i.ident.to_string());
+    }
+
+    fn visit_signature(&mut self, i: &'ast syn::Signature) {
+        if i.unsafety.is_some() {
+            self.unsafe_fns.insert(i.ident.to_string());
+        }
+        syn::visit::visit_signature(self, i);
+    }
+
+    fn visit_pat_type(&mut self, i: &'ast syn::PatType) {
+        if let (syn::Pat::Ident(ident), syn::Type::Ptr(_)) = (&*i.pat, &*i.ty) {
+            self.raw_ptrs.insert(ident.ident.to_string());
+        }
+        syn::visit::visit_pat_type(self, i);
+    }
+
+    fn visit_local(&mut self, i: &'ast syn::Local) {
+        if let (syn::Pat::Ident(ident), Some(init)) = (&i.pat, &i.init) {
+            if self.is_raw_ptr(&init.expr) {
+                self.raw_ptrs.insert(ident.ident.to_string());
+            }
+        }
+        syn::visit::visit_local(self, i);
+    }
+
+    fn visit_field(&mut self, i: &'ast syn::Field) {
+        if let (Some(ident), syn::Type::Ptr(_)) = (&i.ident, &i.ty) {
+            self.raw_ptrs.insert(ident.to_string());
+        }
+        syn::visit::visit_field(self, i);
+    }
+
+    fn visit_item_static(&mut self, i: &'ast syn::ItemStatic) {
+        if matches!(i.mutability, syn::StaticMutability::Mut(_)) {
+            self.static_muts.insert(i.ident.to_string());
+        }
+        syn::visit::visit_item_static(self, i);
+    }
+}
+
+struct SiteFinder<'a> {
+    declared: &'a Declared,
+    lines: Vec<&'a str>,
+    /// The function being walked.
+    context: String,
+    sites: Vec<UnsafeSite>,
+}
+
+impl SiteFinder<'_> {
+    fn add(&mut self, kind: UnsafeKind, name: String, ops: Vec<UnsafeOp>, span: proc_macro2::Span, identity: String) {
+        let has_safety_comment = self.has_safety_comment(span.start().line);
+        self.sites.push(UnsafeSite {
+            fingerprint: format!("{:?} {}", kind, identity),
+            kind,
+            name,
+            ops,
+            has_safety_comment,
+            span,
+        });
+    }
+
+    /// A `SAFETY:` comment on the line itself or in the comments and
+    /// attributes directly above it.
+    fn has_safety_comment(&self, line: usize) -> bool {
+        let Some(index) = line.checked_sub(1) else {
+            return false;
+        };
+        if self.lines.get(index).is_some_and(|l| l.contains("SAFETY:")) {
+            return true;
+        }
+        // Walking upwards, so a block comment is entered at its `*/`.
+        let mut in_block_comment = false;
+        for above in self.lines[..index.min(self.lines.len())].iter().rev() {
+            let trimmed = above.trim();
+            if in_block_comment || trimmed.ends_with("*/") {
+                if trimmed.contains("SAFETY:") {
+                    return true;
+                }
+                in_block_comment = !trimmed.starts_with("/*");
+                continue;
+            }
+            if trimmed.starts_with("//") {
+                if trimmed.contains("SAFETY:") {
+                    return true;
+                }
+                continue;
+            }
+            if !trimmed.starts_with("#[") {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn with_context(&mut self, name: String, f: impl FnOnce(&mut Self)) {
+        let outer = std::mem::replace(&mut self.context, name);
+        f(self);
+        self.context = outer;
+    }
+
+    fn check_fn(&mut self, sig: &syn::Signature) {
+        if sig.unsafety.is_some() {
+            let name = sig.ident.to_string();
+            let identity = sig.to_token_stream().to_string();
+            self.add(UnsafeKind::Fn, name, vec![], sig.span(), identity);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for SiteFinder<'_> {
+    fn visit_item_fn(&mut self, i: &'ast syn::ItemFn) {
+        self.check_fn(&i.sig);
+        self.with_context(i.sig.ident.to_string(), |f| syn::visit::visit_item_fn(f, i));
+    }
+
+    fn visit_impl_item_fn(&mut self, i: &'ast syn::ImplItemFn) {
+        self.check_fn(&i.sig);
+        self.with_context(i.sig.ident.to_string(), |f| syn::visit::visit_impl_item_fn(f, i));
+    }
+
+    fn visit_trait_item_fn(&mut self, i: &'ast syn::TraitItemFn) {
+        self.check_fn(&i.sig);
+        self.with_context(i.sig.ident.to_string(), |f| syn::visit::visit_trait_item_fn(f, i));
+    }
+
+    fn visit_item_impl(&mut self, i: &'ast syn::ItemImpl) {
+        if let (Some(unsafety), Some((_, path, _))) = (&i.unsafety, &i.trait_) {
+            let header = format!(
+                "unsafe impl {} for {}",
+                path.to_token_stream(),
+                i.self_ty.to_token_stream()
+            )
+            .replace(" < ", "<")
+            .replace(" >", ">");
+            let span = unsafety.span;
+            self.add(UnsafeKind::Impl, header.clone(), vec![], span, header);
+        }
+        syn::visit::visit_item_impl(self, i);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast syn::ItemTrait) {
+        if let Some(unsafety) = &i.unsafety {
+            let name = i.ident.to_string();
+            self.add(UnsafeKind::Trait, name.clone(), vec![], unsafety.span, name);
+        }
+        syn::visit::visit_item_trait(self, i);
+    }
+
+    fn visit_expr_unsafe(&mut self, i: &'ast syn::ExprUnsafe) {
+        let mut ops = OpClassifier {
+            declared: self.declared,
+            ops: Vec::new(),
+        };
+        ops.visit_block(&i.block);
+        let mut ops = ops.ops;
+        ops.sort();
+        ops.dedup();
+        let identity = format!("{:?}", ops);
+        self.add(UnsafeKind::Block, self.context.clone(), ops, i.unsafe_token.span, identity);
+        syn::visit::visit_expr_unsafe(self, i);
+    }
+}
+
+struct OpClassifier<'a> {
+    declared: &'a Declared,
+    ops: Vec<UnsafeOp>,
+}
+
+impl<'ast> Visit<'ast> for OpClassifier<'_> {
+    fn visit_expr_unary(&mut self, i: &'ast syn::ExprUnary) {
+        if matches!(i.op, syn::UnOp::Deref(_)) && self.declared.is_raw_ptr(&i.expr) {
+            self.ops.push(UnsafeOp::RawPointerDeref);
+        }
+        syn::visit::visit_expr_unary(self, i);
+    }
+
+    fn visit_expr_call(&mut self, i: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(p) = &*i.func {
+            let segments: Vec<String> = p.path.segments.iter().map(|s| s.ident.to_string()).collect();
+            let name = segments.last().map(String::as_str).unwrap_or_default();
+            let first = segments.first().map(String::as_str).unwrap_or_default();
+            if name == "transmute" || name == "transmute_copy" {
+                self.ops.push(UnsafeOp::Transmute);
+            } else if self.declared.extern_fns.contains(name)
+                || (segments.len() > 1 && matches!(first, "libc" | "ffi" | "sys" | "winapi" | "windows_sys"))
+            {
+                self.ops.push(UnsafeOp::FfiCall);
+            } else if name.ends_with("_unchecked") || name.starts_with("from_raw_parts") {
+                self.ops.push(UnsafeOp::UncheckedAccess);
+            } else if self.declared.unsafe_fns.contains(name) {
+                self.ops.push(UnsafeOp::UnsafeFnCall);
+            }
+        }
+        syn::visit::visit_expr_call(self, i);
+    }
+
+    fn visit_expr_method_call(&mut self, i: &'ast syn::ExprMethodCall) {
+        let method = i.method.to_string();
+        if method.starts_with("get_unchecked") || method.ends_with("_unchecked") {
+            self.ops.push(UnsafeOp::UncheckedAccess);
+        } else if self.declared.unsafe_fns.contains(&method) {
+            self.ops.push(UnsafeOp::UnsafeFnCall);
+        }
+        syn::visit::visit_expr_method_call(self, i);
+    }
+
+    fn visit_expr_path(&mut self, i: &'ast syn::ExprPath) {
+        if let Some(ident) = i.path.get_ident() {
+            if self.declared.static_muts.contains(&ident.to_string()) {
+                self.ops.push(UnsafeOp::StaticMutAccess);
+            }
+        }
+        syn::visit::visit_expr_path(self, i);
+    }
+}
@@ -0,0 +1,120 @@
+use super::Tool;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+pub struct CargoTest;
+
+/// Outcome of one `cargo test` run, summed over every test binary.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TestRun {
+    pub success: bool,
+    /// False when the tests never ran because the crate or a test failed to build.
+    pub compiled: bool,
+    pub passed: usize,
+    pub failed: usize,
+    pub failures: Vec<String>,
+    pub compile_errors: Vec<String>,
+}
+
+/// Reads the libtest summary lines out of `cargo test` output.
+pub fn parse_test_output(success: bool, stdout: &str, stderr: &str) -> TestRun {
+    let mut run = TestRun {
+        success,
+        ..Default::default()
+    };
+    let mut saw_summary = false;
+
+    for line in stdout.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("test result:") {
+            saw_summary = true;
+            for part in rest.split(';') {
+                let mut words = part.split_whitespace().rev();
+                let (Some(label), Some(count)) = (words.next(), words.next()) else {
+                    continue;
+                };
+                let Ok(count) = count.parse::<usize>() else {
+                    continue;
+                };
+                match label {
+                    "passed" => run.passed += count,
+                    "failed" => run.failed += count,
+                    _ => {}
+                }
+            }
+        } else if let Some(name) = line.strip_prefix("test ").and_then(|l| l.strip_suffix(" ... FAILED")) {
+            run.failures.push(name.to_string());
+        }
+    }
+
+    run.compiled = saw_summary || success;
+    if !run.compiled {
+        let mut current: Option<String> = None;
+        for line in stderr.lines() {
+            if line.starts_with("error") && !line.starts_with("error: could not compile") {
+                if let Some(done) = current.take() {
+                    run.compile_errors.push(done);
+                }
+                current = Some(line.to_string());
+            } else if let Some(err) = current.as_mut() {
+                if line.is_empty() {
+                    run.compile_errors.push(current.take().unwrap_or_default());
+                } else {
+                    err.push('\n');
+                    err.push_str(line);
+                }
+            }
+        }
+        run.compile_errors.extend(current);
+    }
+    run
+}
+
+#[async_trait]
+impl Tool for CargoTest {
+    fn name(&self) -> &'static str {
+        "cargo_test"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run cargo test in a Rust project directory, optionally filtered by test name"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "dir": "string",
+            "filter": "string"
+        })
+    }
+
+    async fn call(&self, args: Value) -> anyhow::Result<Value> {
+        let dir = args.get("dir").and_then(|v| v.as_str()).unwrap_or(".");
+
+        let mut cmd = Command::new("cargo");
+        cmd.arg("test").arg("--no-fail-fast").current_dir(dir);
+        if let Some(filter) = args.get("filter").and_then(|v| v.as_str()) {
+            if !filter.is_empty() {
+                cmd.arg(filter);
+            }
+        }
+
+        let result = timeout(Duration::from_secs(300), cmd.output()).await;
+
+        match result {
+            Ok(Ok(output)) => {
+                let run = parse_test_output(
+                    output.status.success(),
+                    &String::from_utf8_lossy(&output.stdout),
+                    &String::from_utf8_lossy(&output.stderr),
+                );
+                Ok(serde_json::to_value(run)?)
+            }
+            Ok(Err(e)) => anyhow::bail!("Failed to execute cargo test: {}", e),
+            Err(_) => anyhow::bail!("Timeout exceeded"),
+        }
+    }
+}
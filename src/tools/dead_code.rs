@@ -0,0 +1,40 @@
+use super::Tool;
+use crate::symbolic::dead_code;
+use crate::symbolic::rules::RuleSet;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+pub struct FindDeadCode;
+
+#[async_trait]
+impl Tool for FindDeadCode {
+    fn name(&self) -> &'static str {
+        "find_dead_code"
+    }
+
+    fn description(&self) -> &'static str {
+        "Find functions, types, modules and enum variants of a crate that nothing reaches from main, the public API or tests"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "dir": "string"
+        })
+    }
+
+    async fn call(&self, args: Value) -> anyhow::Result<Value> {
+        let dir = PathBuf::from(args.get("dir").and_then(|v| v.as_str()).unwrap_or("."));
+        let diagnostics = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+            let rules = RuleSet::load(&dir)?;
+            let dead = dead_code::find_dead_code(&dir)?;
+            Ok(dead_code::grade(dead, &dir, &rules))
+        })
+        .await??;
+
+        Ok(json!({
+            "count": diagnostics.len(),
+            "diagnostics": diagnostics,
+        }))
+    }
+}
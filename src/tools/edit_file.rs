@@ -1,10 +1,26 @@
 use super::Tool;
+use crate::barq::BarqIndex;
+use crate::verifier::{TestScope, Verifier};
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use tokio::process::Command;
 
-pub struct EditFile;
+/// Applies a patch to a copy of the file and runs the post-edit pipeline
+/// (`Verifier::check_edit`) on the result, which writes it to the workspace
+/// only if the candidate passes verification.
+pub struct EditFile {
+    barq: Arc<BarqIndex>,
+}
+
+impl EditFile {
+    pub fn new(barq: Arc<BarqIndex>) -> Self {
+        Self { barq }
+    }
+}
 
 #[async_trait]
 impl Tool for EditFile {
@@ -20,12 +36,19 @@ impl Tool for EditFile {
         json!({
             "file_path": "string",
             "patch": "string",
-            "preview": "bool"
+            "preview": "bool",
+            "full_tests": "bool, run the whole test suite instead of the tests the edit can affect",
+            "intent": "string, the task the edit is for; unrelated changes are flagged"
         })
     }
 
     async fn call(&self, args: Value) -> anyhow::Result<Value> {
         let file_path = args.get("file_path").and_then(|v| v.as_str()).unwrap_or("");
+        let dir = args
+            .get("working_dir")
+            .or_else(|| args.get("dir"))
+            .and_then(|v| v.as_str())
+            .unwrap_or(".");
         let patch = args.get("patch").and_then(|v| v.as_str()).unwrap_or("");
         let preview = args
             .get("preview")
@@ -42,16 +65,32 @@ impl Tool for EditFile {
             }));
         }
 
-        let original_content = fs::read_to_string(file_path).unwrap_or_default();
+        // Resolved the same way the verifier resolves it.
+        let path = Path::new(dir).join(file_path);
+        let original_content = fs::read_to_string(&path).ok();
 
-        let patch_tmp = format!("{}.patch.tmp", file_path);
+        // The patch is applied outside the workspace, which only ever sees
+        // a verified result.
+        static PATCHES: AtomicUsize = AtomicUsize::new(0);
+        let scratch = std::env::temp_dir().join(format!(
+            "barqcoder-patch-{}-{}",
+            std::process::id(),
+            PATCHES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&scratch)?;
+        let candidate = scratch.join(path.file_name().unwrap_or("file".as_ref()));
+        if let Some(original) = &original_content {
+            fs::write(&candidate, original)?;
+        }
+        let patch_tmp = scratch.join("edit.patch");
         fs::write(&patch_tmp, patch)?;
 
         let mut cmd = Command::new("patch");
-        cmd.arg("-u").arg(file_path).arg("-i").arg(&patch_tmp);
-        let output = cmd.output().await?;
-
-        let _ = fs::remove_file(&patch_tmp);
+        cmd.arg("-u").arg(&candidate).arg("-i").arg(&patch_tmp);
+        let output = cmd.output().await;
+        let patched_content = fs::read_to_string(&candidate);
+        let _ = fs::remove_dir_all(&scratch);
+        let output = output?;
 
         if !output.status.success() {
             return Ok(json!({
@@ -62,29 +101,25 @@ impl Tool for EditFile {
             }));
         }
 
-        let check_cmd = Command::new("cargo")
-            .arg("check")
-            .arg("--message-format")
-            .arg("json")
-            .output()
-            .await?;
-
-        if !check_cmd.status.success() {
-            // Revert
-            fs::write(file_path, original_content)?;
-            return Ok(json!({
-                "success": false,
-                "applied": false,
-                "reverted": true,
-                "errors": vec![String::from_utf8_lossy(&check_cmd.stderr).to_string()],
-            }));
+        let patched_content = patched_content?;
+        let full_tests = args.get("full_tests").and_then(|v| v.as_bool()).unwrap_or(false);
+        let mut verifier = Verifier::new(self.barq.clone(), dir)
+            .with_test_scope(if full_tests { TestScope::Full } else { TestScope::Affected });
+        if let Some(intent) = args.get("intent").and_then(|v| v.as_str()) {
+            verifier = verifier.with_intent(intent);
         }
+        let outcome = verifier
+            .check_edit(file_path, original_content.as_deref(), &patched_content)
+            .await?;
 
+        let mut errors = outcome.verification.errors.clone();
+        errors.extend(outcome.verification.symbolic_errors.iter().cloned());
         Ok(json!({
-            "success": true,
-            "applied": true,
-            "reverted": false,
-            "errors": Vec::<String>::new(),
+            "success": outcome.applied,
+            "applied": outcome.applied,
+            "reverted": outcome.reverted,
+            "errors": errors,
+            "verification": outcome.verification,
         }))
     }
 }
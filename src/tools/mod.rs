@@ -4,12 +4,14 @@ use std::sync::Arc;
 use crate::barq::BarqIndex;
 
 pub mod cargo_check;
+pub mod cargo_test;
 pub mod barq_search;
 pub mod edit_file;
 pub mod shell;
 pub mod file_ops;
 pub mod workspace;
 pub mod bench;
+pub mod dead_code;
 
 #[async_trait]
 pub trait Tool: Send + Sync {
@@ -20,37 +22,52 @@ pub trait Tool: Send + Sync {
 }
 
 pub struct ToolRegistry {
-    pub tools: Vec<Box<dyn Tool + Send + Sync>>,
+    pub tools: Vec<Arc<dyn Tool + Send + Sync>>,
 }
 
 impl ToolRegistry {
-    pub fn new() -> Self {
+    /// The built-in tools, with `edit_file` verifying against `barq`.
+    pub fn new(barq: Arc<BarqIndex>) -> Self {
         Self {
             tools: vec![
-                Box::new(cargo_check::CargoCheck),
-                Box::new(edit_file::EditFile),
-                Box::new(shell::ShellExec),
-                Box::new(shell::GitTool),
-                Box::new(file_ops::ReadFile),
-                Box::new(file_ops::ListFiles),
-                Box::new(file_ops::CreateFile),
-                Box::new(workspace::WorkspaceTool::new(".")),
-                Box::new(bench::CargoBench),
+                Arc::new(cargo_check::CargoCheck),
+                Arc::new(cargo_test::CargoTest),
+                Arc::new(edit_file::EditFile::new(barq)),
+                Arc::new(shell::ShellExec),
+                Arc::new(shell::GitTool),
+                Arc::new(file_ops::ReadFile),
+                Arc::new(file_ops::ListFiles),
+                Arc::new(file_ops::CreateFile),
+                Arc::new(workspace::WorkspaceTool::new(".")),
+                Arc::new(bench::CargoBench),
+                Arc::new(dead_code::FindDeadCode),
             ],
         }
     }
 
     pub fn with_barq(barq: Arc<BarqIndex>) -> Self {
-        let mut registry = Self::new();
+        let mut registry = Self::new(barq.clone());
         registry.register(Box::new(barq_search::BarqSearch::new(barq)));
         registry
     }
 
     pub fn register(&mut self, tool: Box<dyn Tool + Send + Sync>) {
-        self.tools.push(tool);
+        self.tools.push(Arc::from(tool));
     }
 
-    pub fn get(&self, name: &str) -> Option<&Box<dyn Tool + Send + Sync>> {
+    /// A registry sharing only the named tools, for agents limited to a subset.
+    pub fn subset(&self, names: &[String]) -> anyhow::Result<Self> {
+        let mut tools = Vec::new();
+        for name in names {
+            let tool = self
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("unknown tool {}", name))?;
+            tools.push(Arc::clone(tool));
+        }
+        Ok(Self { tools })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool + Send + Sync>> {
         self.tools.iter().find(|t| t.name() == name)
     }
 
@@ -58,9 +75,3 @@ impl ToolRegistry {
         self.tools.iter().map(|t| t.schema()).collect()
     }
 }
-
-impl Default for ToolRegistry {
-    fn default() -> Self {
-        Self::new()
-    }
-}
@@ -86,13 +86,15 @@ impl Tool for GitTool {
     fn schema(&self) -> Value {
         json!({
             "operation": "string",
-            "args": "string"
+            "args": "string",
+            "dir": "string"
         })
     }
 
     async fn call(&self, args: Value) -> anyhow::Result<Value> {
         let op = args.get("operation").and_then(|v| v.as_str()).unwrap_or("");
         let cmd_args = args.get("args").and_then(|v| v.as_str()).unwrap_or("");
+        let dir = args.get("dir").and_then(|v| v.as_str()).unwrap_or(".");
 
         let valid_ops = ["status", "diff", "log", "add", "commit"];
         if !valid_ops.contains(&op) {
@@ -100,7 +102,7 @@ impl Tool for GitTool {
         }
 
         let mut cmd = Command::new("git");
-        cmd.arg(op);
+        cmd.current_dir(dir).arg(op);
         
         if !cmd_args.is_empty() {
              // Split args carefully in a real implementation
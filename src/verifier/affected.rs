@@ -0,0 +1,330 @@
+use super::FileChange;
+use quote::ToTokens;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Which tests a verification runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TestScope {
+    /// Only tests that can reach the changed items.
+    #[default]
+    Affected,
+    Full,
+}
+
+/// One `cargo test` run: target selection flags, then libtest name filters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestInvocation {
+    pub args: Vec<String>,
+    pub filters: Vec<String>,
+}
+
+impl std::fmt::Display for TestInvocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cargo test")?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        if !self.filters.is_empty() {
+            write!(f, " -- {}", self.filters.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The `cargo test` runs needed to cover a set of changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestSelection {
+    pub runs: Vec<TestInvocation>,
+}
+
+impl TestSelection {
+    pub fn full() -> Self {
+        Self {
+            runs: vec![TestInvocation { args: vec![], filters: vec![] }],
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.runs.iter().any(|r| r.args.is_empty() && r.filters.is_empty())
+    }
+}
+
+/// Selects the tests that can reach `changes` (paths relative to `root`):
+/// unit tests in each changed module, integration tests that use a changed
+/// item of the crate (or that the code graph links to the file, via
+/// `neighbors`) and doc tests on changed items. Falls back to the full suite
+/// for changes it cannot attribute, such as `Cargo.toml` or `build.rs`.
+pub fn select(root: &Path, changes: &[FileChange], neighbors: impl Fn(&str) -> Vec<String>) -> TestSelection {
+    let Some(crate_name) = crate_name(root) else {
+        return TestSelection::full();
+    };
+    let has_lib = root.join("src/lib.rs").exists();
+
+    let mut unit: BTreeMap<&'static str, BTreeSet<String>> = BTreeMap::new();
+    let mut whole_unit: BTreeSet<&'static str> = BTreeSet::new();
+    let mut integration: BTreeSet<String> = BTreeSet::new();
+    let mut examples: BTreeSet<String> = BTreeSet::new();
+    let mut doc_items: BTreeSet<String> = BTreeSet::new();
+    let mut changed_items: BTreeSet<String> = BTreeSet::new();
+    let mut items_unknown = false;
+
+    for change in changes {
+        let path = change.path.replace('\\', "/");
+        if !path.ends_with(".rs") {
+            if path.ends_with(".md") {
+                continue;
+            }
+            return TestSelection::full();
+        }
+
+        for neighbor in neighbors(&path) {
+            if let Some(stem) = integration_test_name(&neighbor) {
+                integration.insert(stem);
+            }
+        }
+
+        if let Some(stem) = integration_test_name(&path) {
+            integration.insert(stem);
+            continue;
+        }
+        if let Some(name) = path.strip_prefix("examples/").and_then(|p| p.strip_suffix(".rs")) {
+            examples.insert(name.split('/').next().unwrap_or(name).to_string());
+            continue;
+        }
+        let Some(in_src) = path.strip_prefix("src/") else {
+            // build.rs, benches and anything else cargo test may compile.
+            return TestSelection::full();
+        };
+
+        let target = if has_lib && in_src != "main.rs" && !in_src.starts_with("bin/") {
+            "--lib"
+        } else {
+            "--bins"
+        };
+        match module_path(in_src) {
+            Some(module) if !module.is_empty() => {
+                unit.entry(target).or_default().insert(format!("{}::", module));
+            }
+            _ => {
+                whole_unit.insert(target);
+            }
+        }
+
+        match changed(&change.original, &change.patched) {
+            Some((names, documented)) => {
+                changed_items.extend(names);
+                doc_items.extend(documented);
+            }
+            None => items_unknown = true,
+        }
+    }
+
+    if has_lib && (items_unknown || !changed_items.is_empty()) {
+        for (stem, source) in integration_tests(root) {
+            if !uses_word(&source, &crate_name) {
+                continue;
+            }
+            if items_unknown || changed_items.iter().any(|item| uses_word(&source, item)) {
+                integration.insert(stem);
+            }
+        }
+    }
+
+    let mut selection = TestSelection::default();
+    for target in &whole_unit {
+        selection.runs.push(TestInvocation {
+            args: vec![target.to_string()],
+            filters: vec![],
+        });
+    }
+    for (target, filters) in unit {
+        if whole_unit.contains(target) {
+            continue;
+        }
+        selection.runs.push(TestInvocation {
+            args: vec![target.to_string()],
+            filters: filters.into_iter().collect(),
+        });
+    }
+    if !integration.is_empty() || !examples.is_empty() {
+        let mut args = Vec::new();
+        for stem in integration {
+            args.push("--test".to_string());
+            args.push(stem);
+        }
+        for example in examples {
+            args.push("--example".to_string());
+            args.push(example);
+        }
+        selection.runs.push(TestInvocation { args, filters: vec![] });
+    }
+    if has_lib && !doc_items.is_empty() {
+        selection.runs.push(TestInvocation {
+            args: vec!["--doc".to_string()],
+            filters: doc_items.into_iter().collect(),
+        });
+    }
+    selection
+}
+
+fn crate_name(root: &Path) -> Option<String> {
+    let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(root.join("Cargo.toml")).ok()?).ok()?;
+    let name = manifest.get("lib").and_then(|lib| lib.get("name")).or_else(|| manifest.get("package")?.get("name"))?;
+    Some(name.as_str()?.replace('-', "_"))
+}
+
+/// `tests/foo.rs` and `tests/foo/main.rs` are the integration test `foo`.
+fn integration_test_name(path: &str) -> Option<String> {
+    let rest = path.strip_prefix("tests/")?;
+    match rest.split_once('/') {
+        None => rest.strip_suffix(".rs").map(str::to_string),
+        Some((dir, _)) => Some(dir.to_string()),
+    }
+}
+
+fn integration_tests(root: &Path) -> Vec<(String, String)> {
+    let Ok(entries) = std::fs::read_dir(root.join("tests")) else {
+        return vec![];
+    };
+    let mut tests = Vec::new();
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_file() {
+            if let (Some(stem), Ok(source)) = (name.strip_suffix(".rs"), std::fs::read_to_string(&path)) {
+                tests.push((stem.to_string(), source));
+            }
+        } else if let Ok(source) = std::fs::read_to_string(path.join("main.rs")) {
+            tests.push((name, source));
+        }
+    }
+    tests.sort();
+    tests
+}
+
+/// The module path of a file under `src/`: `a/b.rs` and `a/b/mod.rs` are
+/// `a::b`, crate roots are empty. `None` for binaries under `bin/`.
+fn module_path(in_src: &str) -> Option<String> {
+    if in_src.starts_with("bin/") {
+        return None;
+    }
+    let path = in_src.strip_suffix(".rs")?;
+    let path = path.strip_suffix("/mod").unwrap_or(path);
+    if path == "lib" || path == "main" {
+        return Some(String::new());
+    }
+    Some(path.replace('/', "::"))
+}
+
+/// Names of items that differ between the two versions of a file, and those
+/// of them carrying doc examples. `None` if either version does not parse.
+fn changed(original: &str, patched: &str) -> Option<(BTreeSet<String>, BTreeSet<String>)> {
+    let before = if original.is_empty() { BTreeMap::new() } else { items(&syn::parse_file(original).ok()?.items) };
+    let after = if patched.is_empty() { BTreeMap::new() } else { items(&syn::parse_file(patched).ok()?.items) };
+
+    let mut names = BTreeSet::new();
+    let mut documented = BTreeSet::new();
+    for (key, (tokens, has_example)) in &after {
+        if before.get(key).map(|(t, _)| t) != Some(tokens) {
+            names.insert(leaf(key).to_string());
+            if *has_example {
+                documented.insert(leaf(key).to_string());
+            }
+        }
+    }
+    for key in before.keys() {
+        if !after.contains_key(key) {
+            names.insert(leaf(key).to_string());
+        }
+    }
+    Some((names, documented))
+}
+
+/// Every named item, including those in inline modules and impl blocks, with
+/// its tokens and whether its docs contain a code example. Items are keyed by
+/// their path within the file (`m::Parser::new`, `<Parser as Default>::default`)
+/// so same-named items in different modules or impls stay apart.
+pub(super) fn items(list: &[syn::Item]) -> BTreeMap<String, (String, bool)> {
+    let mut out = BTreeMap::new();
+    collect_items(list, "", &mut out);
+    out
+}
+
+fn collect_items(list: &[syn::Item], prefix: &str, out: &mut BTreeMap<String, (String, bool)>) {
+    for item in list {
+        let named = |ident: &syn::Ident, attrs: &[syn::Attribute]| (format!("{}{}", prefix, ident), has_example(attrs));
+        let entry = match item {
+            syn::Item::Fn(i) => Some(named(&i.sig.ident, &i.attrs)),
+            syn::Item::Struct(i) => Some(named(&i.ident, &i.attrs)),
+            syn::Item::Enum(i) => Some(named(&i.ident, &i.attrs)),
+            syn::Item::Trait(i) => Some(named(&i.ident, &i.attrs)),
+            syn::Item::Type(i) => Some(named(&i.ident, &i.attrs)),
+            syn::Item::Const(i) => Some(named(&i.ident, &i.attrs)),
+            syn::Item::Static(i) => Some(named(&i.ident, &i.attrs)),
+            syn::Item::Union(i) => Some(named(&i.ident, &i.attrs)),
+            syn::Item::Macro(i) => i.ident.as_ref().map(|ident| named(ident, &i.attrs)),
+            syn::Item::Mod(i) => {
+                if let Some((_, nested)) = &i.content {
+                    collect_items(nested, &format!("{}{}::", prefix, i.ident), out);
+                }
+                None
+            }
+            syn::Item::Impl(i) => {
+                let self_ty = type_name(&i.self_ty);
+                let owner = match &i.trait_ {
+                    Some((_, path, _)) => format!("<{} as {}>", self_ty, path_name(path)),
+                    None => self_ty,
+                };
+                for impl_item in &i.items {
+                    if let syn::ImplItem::Fn(f) = impl_item {
+                        out.insert(
+                            format!("{}{}::{}", prefix, owner, f.sig.ident),
+                            (f.to_token_stream().to_string(), has_example(&f.attrs)),
+                        );
+                    }
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some((name, example)) = entry {
+            out.insert(name, (item.to_token_stream().to_string(), example));
+        }
+    }
+}
+
+/// The name an impl's self type goes by: the last path segment without
+/// generics, or the whole type for anything that is not a path.
+fn type_name(ty: &syn::Type) -> String {
+    match ty {
+        syn::Type::Path(p) => path_name(&p.path),
+        other => other.to_token_stream().to_string().replace(' ', ""),
+    }
+}
+
+fn path_name(path: &syn::Path) -> String {
+    path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default()
+}
+
+/// The identifier an item key ends in, which is what code and doc test
+/// names refer to it by.
+fn leaf(key: &str) -> &str {
+    key.rsplit("::").next().unwrap_or(key)
+}
+
+fn has_example(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("doc") && attr.to_token_stream().to_string().contains("```")
+    })
+}
+
+/// Whether `word` occurs in `source` as a whole identifier.
+fn uses_word(source: &str, word: &str) -> bool {
+    source.match_indices(word).any(|(i, _)| {
+        let before = source[..i].chars().next_back();
+        let after = source[i + word.len()..].chars().next();
+        let ident = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        !ident(before) && !ident(after)
+    })
+}
@@ -0,0 +1,745 @@
+use crate::barq::BarqIndex;
+use crate::symbolic;
+use crate::symbolic::dead_code;
+use crate::symbolic::diagnostic::Diagnostic;
+use crate::symbolic::rules::{RuleSet, Severity};
+use crate::tools::cargo_test::parse_test_output;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime};
+use tokio::process::Command;
+
+pub mod affected;
+pub mod semantic;
+
+pub use affected::{TestScope, TestSelection};
+
+pub struct Verifier {
+    pub barq: Arc<BarqIndex>,
+    pub workspace: String,
+    /// `CARGO_TARGET_DIR` shared by every scratch copy, so dependencies are
+    /// only built once.
+    pub target_dir: PathBuf,
+    pub test_scope: TestScope,
+    /// The request or plan step the edit is for, used to score how well the
+    /// edit matches it.
+    pub intent: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyResult {
+    pub cargo_check_pass: bool,
+    pub cargo_test_pass: bool,
+    /// Mean similarity of the changed items to the intent, or between their
+    /// old and new versions when there is no intent.
+    pub semantic_score: f32,
+    /// Compiler and test failures.
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    /// Findings of the symbolic pipeline, graded by the workspace's
+    /// `.barqcoder/rules.yaml` and rendered as `file:line:column: [RULE] message`.
+    pub symbolic_errors: Vec<String>,
+    pub symbolic_warnings: Vec<String>,
+    #[serde(default)]
+    pub symbolic_infos: Vec<String>,
+    /// The same findings with their rule, span and suggested fix.
+    #[serde(default)]
+    pub symbolic_diagnostics: Vec<Diagnostic>,
+    /// The `cargo test` commands that were run.
+    #[serde(default)]
+    pub tests_run: Vec<String>,
+    /// `tests_run` covers the whole test suite rather than the affected tests.
+    #[serde(default)]
+    pub full_suite: bool,
+    #[serde(default)]
+    pub timings: VerifyTimings,
+    /// The candidate fails to build, fails its tests or has symbolic errors.
+    pub should_revert: bool,
+}
+
+/// Wall-clock time spent in each verification step, in milliseconds. The
+/// semantic and symbolic steps overlap the cargo ones, so the steps add up
+/// to more than `total_ms`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyTimings {
+    pub scratch_ms: u64,
+    pub check_ms: u64,
+    pub test_ms: u64,
+    pub semantic_ms: u64,
+    pub symbolic_ms: u64,
+    /// Changed files whose symbolic results came from the cache.
+    pub symbolic_cache_hits: usize,
+    pub total_ms: u64,
+}
+
+/// What the post-edit pipeline did with an edit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditOutcome {
+    /// The edit was written to the workspace.
+    pub applied: bool,
+    /// The edit was rejected because `verification.should_revert`, and the
+    /// workspace keeps its original content.
+    pub reverted: bool,
+    pub verification: VerifyResult,
+}
+
+/// One file touched by an edit, before and after.
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub original: String,
+    pub patched: String,
+}
+
+impl Verifier {
+    pub fn new(barq: Arc<BarqIndex>, workspace: &str) -> Self {
+        Self {
+            barq,
+            workspace: workspace.to_string(),
+            target_dir: Path::new(workspace).join("target").join("barqcoder-verify"),
+            test_scope: TestScope::Affected,
+            intent: None,
+        }
+    }
+
+    pub fn with_intent(mut self, intent: &str) -> Self {
+        self.intent = Some(intent.to_string());
+        self
+    }
+
+    pub fn with_test_scope(mut self, test_scope: TestScope) -> Self {
+        self.test_scope = test_scope;
+        self
+    }
+
+    pub fn with_target_dir(mut self, target_dir: impl Into<PathBuf>) -> Self {
+        self.target_dir = target_dir.into();
+        self
+    }
+
+    pub async fn verify_edit(
+        &self,
+        file_path: &str,
+        original: &str,
+        patched: &str,
+    ) -> VerifyResult {
+        self.verify_changes(&[FileChange {
+            path: file_path.to_string(),
+            original: original.to_string(),
+            patched: patched.to_string(),
+        }])
+        .await
+    }
+
+    /// The post-edit pipeline: verifies `patched` as the new content of
+    /// `file_path`, relative to the workspace, and writes it there only when
+    /// the result says to keep it, so a rejected candidate never reaches the
+    /// workspace. `original` is None for a file that does not exist yet.
+    pub async fn check_edit(&self, file_path: &str, original: Option<&str>, patched: &str) -> anyhow::Result<EditOutcome> {
+        let verification = self.verify_edit(file_path, original.unwrap_or_default(), patched).await;
+        if !verification.should_revert {
+            let on_disk = Path::new(&self.workspace).join(file_path);
+            if let Some(parent) = on_disk.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            // Renamed into place so watchers never see a partial write.
+            let staged = on_disk.with_file_name(format!(
+                ".{}.barqcoder-edit",
+                on_disk.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            std::fs::write(&staged, patched)?;
+            std::fs::rename(&staged, &on_disk)?;
+        }
+        Ok(EditOutcome {
+            applied: !verification.should_revert,
+            reverted: verification.should_revert,
+            verification,
+        })
+    }
+
+    /// Verifies an edit spanning several files: cargo runs once, in a scratch
+    /// copy of the workspace with the patched contents applied, while the
+    /// semantic score and the symbolic pipeline over every changed Rust file
+    /// are computed alongside it. The workspace itself is never written to.
+    pub async fn verify_changes(&self, changes: &[FileChange]) -> VerifyResult {
+        let started = Instant::now();
+        let mut timings = VerifyTimings::default();
+
+        let scratch = match ScratchCopy::create(Path::new(&self.workspace), changes).await {
+            Ok(scratch) => scratch,
+            Err(e) => {
+                timings.scratch_ms = elapsed_ms(started);
+                timings.total_ms = timings.scratch_ms;
+                return VerifyResult {
+                    cargo_check_pass: false,
+                    cargo_test_pass: false,
+                    semantic_score: 0.0,
+                    errors: vec![format!("Could not prepare a scratch copy of the workspace: {}", e)],
+                    warnings: vec![],
+                    symbolic_errors: vec![],
+                    symbolic_warnings: vec![],
+                    symbolic_infos: vec![],
+                    symbolic_diagnostics: vec![],
+                    tests_run: vec![],
+                    full_suite: false,
+                    timings,
+                    should_revert: true,
+                };
+            }
+        };
+        timings.scratch_ms = elapsed_ms(started);
+
+        let mut rule_warnings = Vec::new();
+
+        // The semantic and symbolic steps are CPU-bound and independent of
+        // cargo, so they run on the blocking pool while cargo builds.
+        let semantic = {
+            let barq = self.barq.clone();
+            let intent = self.intent.clone();
+            let changes = changes.to_vec();
+            tokio::task::spawn_blocking(move || {
+                let started = Instant::now();
+                let (score, unrelated) = semantic::score(intent.as_deref(), &changes, |text| barq.embed(text));
+                (score, unrelated, elapsed_ms(started))
+            })
+        };
+        let rules = match RuleSet::load(Path::new(&self.workspace)) {
+            Ok(rules) => {
+                rule_warnings.extend(rules.warnings().iter().cloned());
+                rules
+            }
+            Err(e) => {
+                rule_warnings.push(format!("Using the built-in symbolic rules: {}", e));
+                RuleSet::builtin()
+            }
+        };
+        let symbolic = {
+            let workspace = PathBuf::from(&self.workspace);
+            let patched = scratch.path().to_path_buf();
+            let changes = changes.to_vec();
+            tokio::task::spawn_blocking(move || {
+                let started = Instant::now();
+                let mut reports = Vec::new();
+                let mut cache_hits = 0;
+                for change in changes.iter().filter(|c| c.path.ends_with(".rs")) {
+                    let relative = relative_to(&workspace, &change.path)
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or_else(|_| change.path.clone());
+                    let (report, cached) =
+                        symbolic::analyze_edit(&relative, Some(&change.original), &change.patched, &rules);
+                    cache_hits += cached as usize;
+                    reports.push(report);
+                }
+                // Dead code is a property of the whole crate: an edit in one
+                // file can leave an item in another without callers.
+                if !reports.is_empty() {
+                    if let Ok(dead) = dead_code::find_new_dead_code(&workspace, &patched) {
+                        reports.push(symbolic::SymbolicReport {
+                            diagnostics: dead_code::grade(dead, &patched, &rules),
+                        });
+                    }
+                }
+                (reports, cache_hits, elapsed_ms(started))
+            })
+        };
+
+        let cargo = self.run_cargo(scratch.path(), changes, &mut timings).await;
+        let cargo_check_pass = cargo.check_pass;
+        let errors = cargo.errors;
+        let mut warnings = cargo.warnings;
+        warnings.extend(rule_warnings);
+
+        let semantic_score = match semantic.await {
+            Ok((score, unrelated, ms)) => {
+                warnings.extend(unrelated);
+                timings.semantic_ms = ms;
+                score
+            }
+            Err(e) => {
+                warnings.push(format!("Semantic scoring failed: {}", e));
+                0.0
+            }
+        };
+
+        let mut symbolic_errors = Vec::new();
+        let mut symbolic_warnings = Vec::new();
+        let mut symbolic_infos = Vec::new();
+        let mut symbolic_diagnostics = Vec::new();
+        match symbolic.await {
+            Ok((reports, cache_hits, ms)) => {
+                // Grading has already dropped findings whose rule is off.
+                for report in reports {
+                    let rendered = |severity| report.with_severity(severity).map(|d| d.to_string());
+                    symbolic_errors.extend(rendered(Severity::Error));
+                    symbolic_warnings.extend(rendered(Severity::Warn));
+                    symbolic_infos.extend(rendered(Severity::Info));
+                    symbolic_diagnostics.extend(report.diagnostics);
+                }
+                timings.symbolic_ms = ms;
+                timings.symbolic_cache_hits = cache_hits;
+            }
+            Err(e) => symbolic_errors.push(format!("Symbolic verification failed: {}", e)),
+        }
+        timings.total_ms = elapsed_ms(started);
+
+        let should_revert = !cargo_check_pass || !cargo.test_pass || !symbolic_errors.is_empty();
+        VerifyResult {
+            cargo_check_pass,
+            cargo_test_pass: cargo.test_pass,
+            semantic_score,
+            errors,
+            warnings,
+            symbolic_errors,
+            symbolic_warnings,
+            symbolic_infos,
+            symbolic_diagnostics,
+            tests_run: cargo.tests_run,
+            full_suite: cargo.full_suite,
+            timings,
+            should_revert,
+        }
+    }
+
+    /// `cargo check`, then `cargo test` limited to the tests the changes can
+    /// affect, both in `dir`.
+    async fn run_cargo(&self, dir: &Path, changes: &[FileChange], timings: &mut VerifyTimings) -> CargoRun {
+        let mut run = CargoRun {
+            check_pass: false,
+            test_pass: true,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            tests_run: Vec::new(),
+            full_suite: false,
+        };
+
+        // Step 1: cargo check
+        let started = Instant::now();
+        let mut check_cmd = Command::new("cargo");
+        check_cmd
+            .arg("check")
+            .arg("--message-format")
+            .arg("json")
+            .env("CARGO_TARGET_DIR", &self.target_dir)
+            .current_dir(dir);
+
+        if let Ok(out) = check_cmd.output().await {
+            run.check_pass = out.status.success();
+            let (check_errors, check_warnings) = compiler_messages(&String::from_utf8_lossy(&out.stdout));
+            run.warnings.extend(check_warnings);
+            if !run.check_pass {
+                if check_errors.is_empty() {
+                    run.errors.push(String::from_utf8_lossy(&out.stderr).to_string());
+                }
+                run.errors.extend(check_errors);
+            }
+        } else {
+            run.errors.push("Failed to run cargo check".to_string());
+        }
+        timings.check_ms = elapsed_ms(started);
+
+        // Step 2: cargo test
+        let started = Instant::now();
+        let selection = match self.test_scope {
+            TestScope::Full => TestSelection::full(),
+            TestScope::Affected => {
+                let relative: Vec<FileChange> = changes
+                    .iter()
+                    .filter_map(|c| {
+                        let path = relative_to(Path::new(&self.workspace), &c.path).ok()?;
+                        Some(FileChange {
+                            path: path.to_string_lossy().to_string(),
+                            ..c.clone()
+                        })
+                    })
+                    .collect();
+                affected::select(dir, &relative, |path| self.barq.graph_deps(path))
+            }
+        };
+        run.full_suite = selection.is_full();
+
+        for invocation in &selection.runs {
+            let mut test_cmd = Command::new("cargo");
+            test_cmd
+                .arg("test")
+                .args(&invocation.args)
+                .arg("--no-fail-fast")
+                .env("CARGO_TARGET_DIR", &self.target_dir)
+                .current_dir(dir);
+            if !invocation.filters.is_empty() {
+                test_cmd.arg("--").args(&invocation.filters);
+            }
+            run.tests_run.push(invocation.to_string());
+
+            match test_cmd.output().await {
+                Ok(out) if out.status.success() => {}
+                Ok(out) => {
+                    run.test_pass = false;
+                    let outcome = parse_test_output(
+                        false,
+                        &String::from_utf8_lossy(&out.stdout),
+                        &String::from_utf8_lossy(&out.stderr),
+                    );
+                    if !outcome.compiled {
+                        run.errors.push(format!("{} did not compile:\n{}", invocation, outcome.compile_errors.join("\n")));
+                    } else {
+                        run.errors.push(format!("{} failed: {}", invocation, outcome.failures.join(", ")));
+                    }
+                }
+                Err(_) => {
+                    run.test_pass = false;
+                    run.errors.push(format!("Failed to run {}", invocation));
+                }
+            }
+        }
+        timings.test_ms = elapsed_ms(started);
+        run
+    }
+
+    pub fn cycle_check(&self, symbol: &str) -> bool {
+        let deps = self.barq.graph_deps(symbol);
+        deps.contains(&symbol.to_string()) 
+    }
+}
+
+struct CargoRun {
+    check_pass: bool,
+    test_pass: bool,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+    tests_run: Vec<String>,
+    full_suite: bool,
+}
+
+fn elapsed_ms(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+/// Where mirrors live, relative to the root they mirror.
+const MIRROR_DIR: &str = "target/barqcoder-mirror";
+
+/// A copy of a workspace with candidate file contents applied. The copy
+/// mirrors the whole git repository or cargo workspace around it, so an
+/// enclosing `[workspace]` resolves as it does for the original, and sits in
+/// its `target/barqcoder-mirror/<n>`, with `path` dependencies that lead out
+/// of the root rewritten to absolute paths. Each process locks the `<n>` it
+/// uses through `<n>.lock`, keeps the mirror until `remove_mirrors`, and only
+/// copies files that changed since the last verification. A mirror is held
+/// by one `ScratchCopy` at a time.
+pub struct ScratchCopy {
+    dir: PathBuf,
+    _mirror: tokio::sync::OwnedMutexGuard<Mirror>,
+}
+
+/// A mirror this process has claimed, with what it holds for each file
+/// relative to its root: the length and mtime of the source it was copied
+/// from, or None for a candidate change.
+#[derive(Default)]
+struct Mirror {
+    claimed: Option<(PathBuf, std::fs::File)>,
+    synced: HashMap<PathBuf, Option<(u64, SystemTime)>>,
+}
+
+impl Mirror {
+    /// Locks the first free mirror directory under `root` and empties it,
+    /// since whatever another process left there is in an unknown state.
+    fn claim(&mut self, root: &Path) -> anyhow::Result<PathBuf> {
+        if let Some((dir, _)) = &self.claimed {
+            return Ok(dir.clone());
+        }
+        let base = root.join(MIRROR_DIR);
+        std::fs::create_dir_all(&base)?;
+        for n in 0.. {
+            let lock = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(base.join(format!("{}.lock", n)))?;
+            match lock.try_lock() {
+                Ok(()) => {}
+                Err(std::fs::TryLockError::WouldBlock) => continue,
+                Err(std::fs::TryLockError::Error(e)) => return Err(e.into()),
+            }
+            let dir = base.join(n.to_string());
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir)?;
+            }
+            std::fs::create_dir_all(&dir)?;
+            self.synced.clear();
+            self.claimed = Some((dir.clone(), lock));
+            return Ok(dir);
+        }
+        unreachable!("there is always a next mirror to try")
+    }
+
+    /// Deletes the mirror and gives up its lock.
+    fn release(&mut self) {
+        if let Some((dir, _lock)) = self.claimed.take() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        self.synced.clear();
+    }
+}
+
+fn mirrors() -> &'static Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<Mirror>>>> {
+    static MIRRORS: OnceLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<Mirror>>>>> = OnceLock::new();
+    MIRRORS.get_or_init(Default::default)
+}
+
+/// Deletes the mirrors this process made that no verification is using.
+/// Called on the way out; a mirror left behind by a crash is emptied by the
+/// next process to lock it.
+pub fn remove_mirrors() {
+    let mirrors: Vec<_> = mirrors().lock().unwrap_or_else(|e| e.into_inner()).values().cloned().collect();
+    for mirror in mirrors {
+        if let Ok(mut mirror) = mirror.try_lock() {
+            mirror.release();
+        }
+    }
+}
+
+impl ScratchCopy {
+    /// Brings the mirror of `workspace` up to date (its git-visible files, or
+    /// everything but `target` and `.git` outside git), then writes each
+    /// change's patched content. A change with an empty patch of a non-empty
+    /// file deletes it.
+    pub async fn create(workspace: &Path, changes: &[FileChange]) -> anyhow::Result<Self> {
+        let workspace = workspace.canonicalize()?;
+        let root = mirror_root(&workspace).await;
+
+        let mirror = mirrors()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(root.clone())
+            .or_default()
+            .clone();
+        let mut mirror = mirror.lock_owned().await;
+        let mirror_dir = mirror.claim(&root)?;
+        let synced = &mut mirror.synced;
+
+        let listed: HashSet<PathBuf> = workspace_files(&root)
+            .await?
+            .into_iter()
+            .filter(|rel| !rel.starts_with(MIRROR_DIR))
+            .collect();
+        synced.retain(|rel, _| {
+            let keep = listed.contains(rel);
+            if !keep {
+                let _ = std::fs::remove_file(mirror_dir.join(rel));
+            }
+            keep
+        });
+        for rel in &listed {
+            let from = root.join(rel);
+            let Ok(meta) = std::fs::metadata(&from) else {
+                continue;
+            };
+            if !meta.is_file() {
+                continue;
+            }
+            let stamp = Some((meta.len(), meta.modified()?));
+            if synced.get(rel) == Some(&stamp) {
+                continue;
+            }
+            let to = mirror_dir.join(rel);
+            if let Some(parent) = to.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if rel.ends_with("Cargo.toml") {
+                std::fs::write(&to, rebase_manifest(&std::fs::read_to_string(&from)?, &from, &root))?;
+            } else {
+                std::fs::copy(&from, &to)?;
+            }
+            synced.insert(rel.clone(), stamp);
+        }
+
+        let inner = workspace.strip_prefix(&root)?;
+        let dir = mirror_dir.join(inner);
+        for change in changes {
+            let rel = relative_to(&workspace, &change.path)?;
+            // Stale from now on, so the next sync restores or removes it.
+            synced.insert(inner.join(&rel), None);
+            let to = dir.join(&rel);
+            if change.patched.is_empty() && !change.original.is_empty() {
+                let _ = std::fs::remove_file(&to);
+                continue;
+            }
+            if let Some(parent) = to.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if rel.ends_with("Cargo.toml") {
+                std::fs::write(&to, rebase_manifest(&change.patched, &workspace.join(&rel), &root))?;
+            } else {
+                std::fs::write(&to, &change.patched)?;
+            }
+        }
+        Ok(Self { dir, _mirror: mirror })
+    }
+
+    /// The copy of the workspace inside the mirror.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// `manifest`, read from `manifest_path` under `root`, with every `path`
+/// that leads out of `root` made absolute: the mirror sits deeper than the
+/// original, so those no longer resolve relative to it. Paths inside `root`
+/// are mirrored too and stay as they are.
+fn rebase_manifest(manifest: &str, manifest_path: &Path, root: &Path) -> String {
+    let Ok(mut table) = manifest.parse::<toml::Table>() else {
+        return manifest.to_string();
+    };
+    let dir = manifest_path.parent().unwrap_or(root);
+    if !rebase_paths(&mut table, dir, root) {
+        return manifest.to_string();
+    }
+    toml::to_string(&table).unwrap_or_else(|_| manifest.to_string())
+}
+
+/// Rewrites the `path` keys in `table` and the tables below it, saying
+/// whether any changed.
+fn rebase_paths(table: &mut toml::Table, dir: &Path, root: &Path) -> bool {
+    let mut changed = false;
+    for (key, value) in table.iter_mut() {
+        match value {
+            toml::Value::String(path) if key == "path" => {
+                let resolved = normalize(&dir.join(&*path));
+                if !resolved.starts_with(root) {
+                    *path = resolved.to_string_lossy().to_string();
+                    changed = true;
+                }
+            }
+            toml::Value::Table(inner) => changed |= rebase_paths(inner, dir, root),
+            toml::Value::Array(items) => {
+                for item in items {
+                    if let toml::Value::Table(inner) = item {
+                        changed |= rebase_paths(inner, dir, root);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    changed
+}
+
+/// `path` with `.` and `..` resolved lexically.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// The outermost of `workspace`, its git repository and the cargo workspace
+/// it belongs to, which is what a mirror has to copy.
+async fn mirror_root(workspace: &Path) -> PathBuf {
+    let mut root = workspace.to_path_buf();
+    for dir in workspace.ancestors() {
+        let manifest = std::fs::read_to_string(dir.join("Cargo.toml")).unwrap_or_default();
+        if manifest.parse::<toml::Table>().is_ok_and(|t| t.contains_key("workspace")) {
+            root = dir.to_path_buf();
+            break;
+        }
+    }
+
+    let toplevel = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .current_dir(workspace)
+        .output()
+        .await;
+    if let Ok(out) = toplevel {
+        let toplevel = PathBuf::from(String::from_utf8_lossy(&out.stdout).trim());
+        if out.status.success() && root.starts_with(&toplevel) {
+            root = toplevel.canonicalize().unwrap_or(toplevel);
+        }
+    }
+    root
+}
+
+/// Files under `workspace`, relative to it. Uses git's view (tracked plus
+/// untracked, not ignored) when available so build output is skipped.
+async fn workspace_files(workspace: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let listed = Command::new("git")
+        .args(["ls-files", "-z", "--cached", "--others", "--exclude-standard"])
+        .current_dir(workspace)
+        .output()
+        .await;
+    if let Ok(out) = listed {
+        if out.status.success() {
+            return Ok(out
+                .stdout
+                .split(|b| *b == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| PathBuf::from(String::from_utf8_lossy(name).to_string()))
+                .collect());
+        }
+    }
+
+    let mut files = Vec::new();
+    let walker = walkdir::WalkDir::new(workspace)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !matches!(e.file_name().to_str(), Some("target" | ".git")));
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            files.push(entry.path().strip_prefix(workspace)?.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+/// `path` relative to `workspace`. Absolute paths must lie inside it.
+fn relative_to(workspace: &Path, path: &str) -> anyhow::Result<PathBuf> {
+    let path = Path::new(path);
+    if path.is_relative() {
+        return Ok(path.to_path_buf());
+    }
+    let root = workspace.canonicalize()?;
+    let parent = path.parent().and_then(|p| p.canonicalize().ok());
+    match parent {
+        Some(parent) if parent.starts_with(&root) => {
+            let name = path.file_name().ok_or_else(|| anyhow::anyhow!("{} is not a file", path.display()))?;
+            Ok(parent.strip_prefix(&root)?.join(name))
+        }
+        _ => path
+            .strip_prefix(workspace)
+            .map(Path::to_path_buf)
+            .map_err(|_| anyhow::anyhow!("{} is outside the workspace", path.display())),
+    }
+}
+
+/// Splits `cargo --message-format json` output into rendered errors and warnings.
+fn compiler_messages(stdout: &str) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for line in stdout.lines() {
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if msg["reason"] != "compiler-message" {
+            continue;
+        }
+        let message = &msg["message"];
+        let text = message["rendered"]
+            .as_str()
+            .or_else(|| message["message"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        match message["level"].as_str() {
+            Some("error") => errors.push(text),
+            Some("warning") => warnings.push(text),
+            _ => {}
+        }
+    }
+    (errors, warnings)
+}
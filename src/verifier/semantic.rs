@@ -0,0 +1,93 @@
+use super::affected::items;
+use super::FileChange;
+use crate::barq::cosine;
+use std::collections::BTreeMap;
+
+/// Changed items less similar than this to the task are reported as unrelated.
+pub const UNRELATED_ITEM_THRESHOLD: f32 = 0.1;
+
+/// How well an edit matches its intent, in `[0, 1]`, with a warning for each
+/// changed item that looks unrelated to it.
+///
+/// Every changed item (a whole file when it is not Rust or does not parse) is
+/// embedded before and after the edit. With an `intent`, an item's relevance
+/// is the better of its two similarities to the intent, and the score is the
+/// mean relevance. Without one, the score is the mean similarity between each
+/// item's two versions, i.e. how little the edit drifted.
+pub fn score(intent: Option<&str>, changes: &[FileChange], embed: impl Fn(&str) -> Vec<f32>) -> (f32, Vec<String>) {
+    let intent = intent.map(str::trim).filter(|i| !i.is_empty()).map(&embed);
+    let mut total = 0.0;
+    let mut count = 0usize;
+    let mut warnings = Vec::new();
+
+    for change in changes {
+        for (name, before, after) in changed_items(change) {
+            let before = before.map(|text| embed(&text));
+            let after = after.map(|text| embed(&text));
+            let relevance = match &intent {
+                Some(intent) => [&before, &after]
+                    .into_iter()
+                    .flatten()
+                    .map(|v| cosine(v, intent))
+                    .fold(0.0f32, f32::max),
+                None => match (&before, &after) {
+                    (Some(b), Some(a)) => cosine(b, a),
+                    _ => 0.0,
+                },
+            };
+            if intent.is_some() && relevance < UNRELATED_ITEM_THRESHOLD {
+                warnings.push(format!(
+                    "{}: `{}` changed but looks unrelated to the task (similarity {:.2})",
+                    change.path, name, relevance
+                ));
+            }
+            total += relevance.clamp(0.0, 1.0);
+            count += 1;
+        }
+    }
+
+    let score = if count == 0 { 1.0 } else { total / count as f32 };
+    (score, warnings)
+}
+
+/// Items of a file that differ between its versions, each with its text
+/// before and after (`None` where it does not exist).
+fn changed_items(change: &FileChange) -> Vec<(String, Option<String>, Option<String>)> {
+    if change.original == change.patched {
+        return vec![];
+    }
+    let whole_file = || {
+        vec![(
+            change.path.clone(),
+            Some(change.original.clone()).filter(|s| !s.is_empty()),
+            Some(change.patched.clone()).filter(|s| !s.is_empty()),
+        )]
+    };
+    if !change.path.ends_with(".rs") {
+        return whole_file();
+    }
+    let parse = |source: &str| -> Option<BTreeMap<String, String>> {
+        if source.is_empty() {
+            return Some(BTreeMap::new());
+        }
+        let file = syn::parse_file(source).ok()?;
+        Some(items(&file.items).into_iter().map(|(name, (tokens, _))| (name, tokens)).collect())
+    };
+    let (Some(before), Some(after)) = (parse(&change.original), parse(&change.patched)) else {
+        return whole_file();
+    };
+
+    let mut changed = Vec::new();
+    for (name, tokens) in &after {
+        match before.get(name) {
+            Some(old) if old == tokens => {}
+            old => changed.push((name.clone(), old.cloned(), Some(tokens.clone()))),
+        }
+    }
+    for (name, tokens) in &before {
+        if !after.contains_key(name) {
+            changed.push((name.clone(), Some(tokens.clone()), None));
+        }
+    }
+    changed
+}
@@ -0,0 +1,304 @@
+use barqcoder::agent::{LlmProvider, Message};
+use barqcoder::agents::coordinator::{CoordinatorAgent, CoordinatorEvent};
+use barqcoder::agents::roles::{RoleRegistry, RoleSpec};
+use barqcoder::barq::BarqIndex;
+use barqcoder::config::{Config, RejectionPolicy};
+use barqcoder::tools::ToolRegistry;
+use serde_json::{json, Value};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Answers each request with the next scripted response, in two chunks.
+struct ScriptedProvider {
+    responses: Mutex<Vec<String>>,
+}
+
+impl LlmProvider for ScriptedProvider {
+    fn chat_stream(&self, _messages: Vec<Message>, _tools: Vec<Value>) -> anyhow::Result<mpsc::Receiver<String>> {
+        let (tx, rx) = mpsc::channel(2);
+        let response = self.responses.lock().unwrap().remove(0);
+        tokio::spawn(async move {
+            let (head, tail) = response.split_at(response.len() / 2);
+            let _ = tx.send(head.to_string()).await;
+            let _ = tx.send(tail.to_string()).await;
+        });
+        Ok(rx)
+    }
+}
+
+#[tokio::test]
+async fn test_coordinator_publishes_progress_events() {
+    let root = std::env::temp_dir().join("barqcoder_test_coordinator_events");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let responses = vec![
+        // planner
+        json!({"steps": [{"id": "s1", "description": "write notes"}]}),
+        // coder
+        json!({
+            "reasoning": "create the file",
+            "tool_calls": [{"name": "create_file", "arguments": {"path": "notes.txt", "content": "hi\n"}}],
+            "final_answer": null
+        }),
+        json!({"reasoning": "done", "tool_calls": [], "final_answer": "wrote notes.txt"}),
+        // tester
+        json!({"reasoning": "nothing to test", "tool_calls": [], "final_answer": "{\"summary\": \"no code\"}"}),
+        // reviewer
+        json!({"approved": true, "issues": [], "suggested_changes": []}),
+    ];
+    let llm: Arc<dyn LlmProvider> = Arc::new(ScriptedProvider {
+        responses: Mutex::new(responses.into_iter().map(|r| r.to_string()).collect()),
+    });
+
+    let config = Config {
+        workspace_root: root.to_str().unwrap().to_string(),
+        max_parallel_steps: 1,
+        ..Config::default()
+    };
+    let barq = Arc::new(BarqIndex::new(&config).unwrap());
+    let providers = |_: &RoleSpec| -> anyhow::Result<Arc<dyn LlmProvider>> { Ok(llm.clone()) };
+    let coordinator = CoordinatorAgent::new(
+        &RoleRegistry::builtin(&config),
+        &providers,
+        barq.clone(),
+        Arc::new(ToolRegistry::new(barq)),
+        &config,
+    )
+    .unwrap();
+
+    let mut rx = coordinator.subscribe();
+    coordinator.execute_goal("take notes").await.unwrap();
+    assert_eq!(fs::read_to_string(root.join("notes.txt")).unwrap(), "hi\n");
+
+    let mut events = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        events.push(event);
+    }
+
+    assert!(matches!(&events[0], CoordinatorEvent::AgentToken { step_id: None, role, .. } if role == "planner"));
+    assert!(events.iter().any(|e| matches!(e, CoordinatorEvent::PlanReady { steps, .. } if steps.len() == 1)));
+    let call = events
+        .iter()
+        .position(|e| matches!(e, CoordinatorEvent::ToolCall { step_id, role, name, .. } if step_id == "s1" && role == "coder" && name == "create_file"))
+        .expect("coder's tool call");
+    let result = events
+        .iter()
+        .position(|e| matches!(e, CoordinatorEvent::ToolResult { name, .. } if name == "create_file"))
+        .expect("coder's tool result");
+    assert!(call < result);
+    for role in ["coder", "tester", "reviewer"] {
+        assert!(
+            events.iter().any(|e| matches!(e, CoordinatorEvent::AgentToken { step_id: Some(s), role: r, .. } if s == "s1" && r == role)),
+            "no tokens from {}",
+            role
+        );
+    }
+    assert!(events.iter().any(|e| matches!(e, CoordinatorEvent::ReviewVerdict { verdict, .. } if verdict.approved)));
+    assert!(events.iter().any(|e| matches!(e, CoordinatorEvent::StepFinished { error: None, .. })));
+    assert!(matches!(events.last(), Some(CoordinatorEvent::GoalFinished { error: None, .. })));
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_coordinator_refuses_paths_outside_the_workspace() {
+    let parent = std::env::temp_dir().join("barqcoder_test_coordinator_scope");
+    let root = parent.join("ws");
+    let _ = fs::remove_dir_all(&parent);
+    fs::create_dir_all(&root).unwrap();
+
+    let responses = vec![
+        json!({"steps": [{"id": "s1", "description": "write notes"}]}),
+        json!({
+            "reasoning": "create the file",
+            "tool_calls": [{"name": "create_file", "arguments": {"path": "../escaped.txt", "content": "hi\n"}}],
+            "final_answer": null
+        }),
+        json!({"reasoning": "done", "tool_calls": [], "final_answer": "gave up"}),
+        json!({"reasoning": "nothing to test", "tool_calls": [], "final_answer": "{\"summary\": \"no code\"}"}),
+        json!({"approved": true, "issues": [], "suggested_changes": []}),
+    ];
+    let llm: Arc<dyn LlmProvider> = Arc::new(ScriptedProvider {
+        responses: Mutex::new(responses.into_iter().map(|r| r.to_string()).collect()),
+    });
+
+    let config = Config {
+        workspace_root: root.to_str().unwrap().to_string(),
+        max_parallel_steps: 1,
+        ..Config::default()
+    };
+    let barq = Arc::new(BarqIndex::new(&config).unwrap());
+    let providers = |_: &RoleSpec| -> anyhow::Result<Arc<dyn LlmProvider>> { Ok(llm.clone()) };
+    let coordinator = CoordinatorAgent::new(
+        &RoleRegistry::builtin(&config),
+        &providers,
+        barq.clone(),
+        Arc::new(ToolRegistry::new(barq)),
+        &config,
+    )
+    .unwrap();
+
+    let mut rx = coordinator.subscribe();
+    coordinator.execute_goal("take notes").await.unwrap();
+    assert!(!parent.join("escaped.txt").exists());
+
+    let mut refused = false;
+    while let Ok(event) = rx.try_recv() {
+        if let CoordinatorEvent::ToolResult { name, result, .. } = event {
+            refused |= name == "create_file" && result["error"].as_str().is_some_and(|e| e.contains("outside"));
+        }
+    }
+    assert!(refused);
+
+    fs::remove_dir_all(&parent).unwrap();
+}
+
+#[tokio::test]
+async fn test_coordinator_refuses_rollback_without_git() {
+    let root = std::env::temp_dir().join("barqcoder_test_coordinator_rollback");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let llm: Arc<dyn LlmProvider> = Arc::new(ScriptedProvider { responses: Mutex::new(Vec::new()) });
+    let config = Config {
+        workspace_root: root.to_str().unwrap().to_string(),
+        on_review_rejected: RejectionPolicy::Rollback,
+        ..Config::default()
+    };
+    let barq = Arc::new(BarqIndex::new(&config).unwrap());
+    let providers = |_: &RoleSpec| -> anyhow::Result<Arc<dyn LlmProvider>> { Ok(llm.clone()) };
+    let coordinator = CoordinatorAgent::new(
+        &RoleRegistry::builtin(&config),
+        &providers,
+        barq.clone(),
+        Arc::new(ToolRegistry::new(barq)),
+        &config,
+    )
+    .unwrap();
+
+    let err = coordinator.execute_goal("take notes").await.unwrap_err();
+    assert!(err.to_string().contains("git repository"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_coordinator_runs_full_suite_after_the_last_step() {
+    let root = std::env::temp_dir().join("barqcoder_test_coordinator_full_suite");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"tiny\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    // A test no step touches, which only the full suite runs.
+    fs::write(root.join("src/lib.rs"), "#[test]\nfn already_broken() {\n    panic!(\"broken\");\n}\n").unwrap();
+
+    let responses = vec![
+        json!({"steps": [{"id": "s1", "description": "write notes"}]}),
+        json!({
+            "reasoning": "create the file",
+            "tool_calls": [{"name": "create_file", "arguments": {"path": "notes.txt", "content": "hi\n"}}],
+            "final_answer": null
+        }),
+        json!({"reasoning": "done", "tool_calls": [], "final_answer": "wrote notes.txt"}),
+        json!({"reasoning": "nothing to test", "tool_calls": [], "final_answer": "{\"summary\": \"no code\"}"}),
+        json!({"approved": true, "issues": [], "suggested_changes": []}),
+    ];
+    let llm: Arc<dyn LlmProvider> = Arc::new(ScriptedProvider {
+        responses: Mutex::new(responses.into_iter().map(|r| r.to_string()).collect()),
+    });
+    let config = Config {
+        workspace_root: root.to_str().unwrap().to_string(),
+        max_parallel_steps: 1,
+        full_suite_before_answer: true,
+        ..Config::default()
+    };
+    let barq = Arc::new(BarqIndex::new(&config).unwrap());
+    let providers = |_: &RoleSpec| -> anyhow::Result<Arc<dyn LlmProvider>> { Ok(llm.clone()) };
+    let coordinator = CoordinatorAgent::new(
+        &RoleRegistry::builtin(&config),
+        &providers,
+        barq.clone(),
+        Arc::new(ToolRegistry::new(barq)),
+        &config,
+    )
+    .unwrap();
+
+    let mut rx = coordinator.subscribe();
+    let err = coordinator.execute_goal("take notes").await.unwrap_err();
+    assert!(err.to_string().contains("Full test suite"), "{}", err);
+
+    let mut events = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        events.push(event);
+    }
+    let position = |matches: &dyn Fn(&CoordinatorEvent) -> bool| events.iter().position(matches);
+    let step = position(&|e| matches!(e, CoordinatorEvent::StepFinished { error: None, .. })).expect("step merged");
+    let suite = position(&|e| {
+        matches!(e, CoordinatorEvent::FullSuiteVerified { passed: false, tests_run } if tests_run == &["cargo test".to_string()])
+    })
+    .expect("full suite run");
+    let finished = position(&|e| matches!(e, CoordinatorEvent::GoalFinished { error: Some(_), .. })).expect("goal finished");
+    assert!(step < suite && suite < finished);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_coordinator_returns_implementation_bugs_to_the_coder() {
+    let root = std::env::temp_dir().join("barqcoder_test_coordinator_impl_bugs");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let responses = vec![
+        json!({"steps": [{"id": "s1", "description": "write notes"}]}),
+        // coder, first attempt
+        json!({"reasoning": "done", "tool_calls": [], "final_answer": "wrote nothing"}),
+        // tester blames the implementation
+        json!({
+            "reasoning": "ran",
+            "tool_calls": [],
+            "final_answer": "{\"failures\": [{\"name\": \"notes\", \"message\": \"missing\", \"cause\": \"implementation_bug\"}]}"
+        }),
+        // coder, second attempt
+        json!({"reasoning": "done", "tool_calls": [], "final_answer": "wrote notes.txt"}),
+        json!({"reasoning": "nothing to test", "tool_calls": [], "final_answer": "{\"summary\": \"ok\"}"}),
+        json!({"approved": true, "issues": [], "suggested_changes": []}),
+    ];
+    let llm: Arc<dyn LlmProvider> = Arc::new(ScriptedProvider {
+        responses: Mutex::new(responses.into_iter().map(|r| r.to_string()).collect()),
+    });
+    let config = Config {
+        workspace_root: root.to_str().unwrap().to_string(),
+        max_parallel_steps: 1,
+        ..Config::default()
+    };
+    let barq = Arc::new(BarqIndex::new(&config).unwrap());
+    let providers = |_: &RoleSpec| -> anyhow::Result<Arc<dyn LlmProvider>> { Ok(llm.clone()) };
+    let coordinator = CoordinatorAgent::new(
+        &RoleRegistry::builtin(&config),
+        &providers,
+        barq.clone(),
+        Arc::new(ToolRegistry::new(barq)),
+        &config,
+    )
+    .unwrap();
+
+    let mut rx = coordinator.subscribe();
+    coordinator.execute_goal("take notes").await.unwrap();
+
+    let mut rounds = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        if let CoordinatorEvent::ReviewVerdict { round, .. } = event {
+            rounds.push(round);
+        }
+    }
+    // The first attempt never reached the reviewer.
+    assert_eq!(rounds, vec![2]);
+
+    fs::remove_dir_all(&root).unwrap();
+}
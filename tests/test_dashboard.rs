@@ -0,0 +1,119 @@
+use barqcoder::agents::coordinator::CoordinatorEvent;
+use barqcoder::agents::planner::PlanStep;
+use barqcoder::agents::reviewer::ReviewVerdict;
+use barqcoder::dashboard::{GoalDashboard, StepState};
+use barqcoder::macro_goals::{GoalManager, MacroGoalFile, Phase, Status, Task};
+use ratatui::{backend::TestBackend, Terminal};
+use std::fs;
+
+fn step(id: &str, description: &str) -> PlanStep {
+    PlanStep {
+        id: id.to_string(),
+        description: description.to_string(),
+        target_files: vec![],
+        acceptance_criteria: vec![],
+        depends_on: vec![],
+        role: None,
+    }
+}
+
+fn screen(dashboard: &GoalDashboard) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(160, 30)).unwrap();
+    terminal.draw(|f| dashboard.render(f, f.area())).unwrap();
+    let buffer = terminal.backend().buffer();
+    let mut text = String::new();
+    for y in 0..buffer.area.height {
+        for x in 0..buffer.area.width {
+            text.push_str(buffer[(x, y)].symbol());
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[test]
+fn test_dashboard_follows_coordinator_events() {
+    let mut dashboard = GoalDashboard::new();
+    dashboard.apply(&CoordinatorEvent::PlanReady {
+        goal: "add login".to_string(),
+        steps: vec![step("s1", "add model"), step("s2", "add handler")],
+    });
+    dashboard.apply(&CoordinatorEvent::StepStarted {
+        step_id: "s1".to_string(),
+        description: "add model".to_string(),
+    });
+    dashboard.apply(&CoordinatorEvent::AgentStarted {
+        step_id: "s1".to_string(),
+        role: "tester".to_string(),
+    });
+    dashboard.apply(&CoordinatorEvent::ReviewVerdict {
+        step_id: "s1".to_string(),
+        round: 2,
+        verdict: ReviewVerdict::rejected("handler leaks the token"),
+    });
+
+    assert_eq!(dashboard.steps[0].state, StepState::Running);
+    assert_eq!(dashboard.steps[0].agent.as_deref(), Some("tester"));
+    assert_eq!(dashboard.steps[0].review_rounds, 2);
+    assert_eq!(dashboard.steps[1].state, StepState::Waiting);
+
+    let text = screen(&dashboard);
+    assert!(text.contains("[running] s1 add model (tester) round 2"));
+    assert!(text.contains("s1 round 2: rejected"));
+    assert!(text.contains("handler leaks the token"));
+
+    dashboard.apply(&CoordinatorEvent::StepFinished { step_id: "s1".to_string(), error: None });
+    dashboard.apply(&CoordinatorEvent::StepFinished {
+        step_id: "s2".to_string(),
+        error: Some("merge conflict".to_string()),
+    });
+    assert_eq!(dashboard.steps[0].state, StepState::Merged);
+    assert!(dashboard.steps[0].elapsed().is_some());
+    assert_eq!(dashboard.steps[1].state, StepState::Failed);
+    assert!(screen(&dashboard).contains("merge conflict"));
+}
+
+#[test]
+fn test_dashboard_picks_and_opens_saved_goals() {
+    let root = std::env::temp_dir().join("barqcoder_test_dashboard");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let manager = GoalManager::new(root.to_str().unwrap());
+    for name in ["billing", "auth"] {
+        manager
+            .save_goal(&MacroGoalFile {
+                name: name.to_string(),
+                description: String::new(),
+                phases: vec![Phase {
+                    name: "build".to_string(),
+                    description: String::new(),
+                    status: Status::Running,
+                    tasks: vec![Task {
+                        id: "t1".to_string(),
+                        description: format!("{} handler", name),
+                        status: Status::Done,
+                        started_at: Some(100),
+                        finished_at: Some(165),
+                        ..Default::default()
+                    }],
+                }],
+                source: None,
+            })
+            .unwrap();
+    }
+
+    let mut dashboard = GoalDashboard::new();
+    dashboard.refresh_goals(&manager);
+    assert_eq!(dashboard.saved_goals, vec!["auth", "billing"]);
+    dashboard.select_next();
+    dashboard.select_next();
+    assert_eq!(dashboard.selected_goal(), Some("billing"));
+
+    dashboard.open(&manager, "billing").unwrap();
+    let text = screen(&dashboard);
+    assert!(text.contains("Goal: billing"));
+    assert!(text.contains("[running] build"));
+    assert!(text.contains("[done] t1 billing handler 01:05"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,205 @@
+use async_trait::async_trait;
+use barqcoder::macro_goals::{GoalExecutor, GoalManager, MacroGoalFile, Phase, Status, Task, TaskDelegate};
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+/// Records delegated tasks and fails the ones listed in `fail`.
+struct FakeCoordinator {
+    calls: Mutex<Vec<String>>,
+    fail: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl TaskDelegate for FakeCoordinator {
+    async fn run_task(&self, description: &str) -> anyhow::Result<()> {
+        self.calls.lock().unwrap().push(description.to_string());
+        if self.fail.lock().unwrap().iter().any(|f| f == description) {
+            anyhow::bail!("coordinator gave up");
+        }
+        Ok(())
+    }
+}
+
+fn task(id: &str, description: &str, command: Option<&str>) -> Task {
+    Task {
+        id: id.to_string(),
+        description: description.to_string(),
+        command: command.map(|c| c.to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_goal_executor_persists_and_resumes() {
+    let root = std::env::temp_dir().join("barqcoder_test_macro_goals");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let workspace = root.to_str().unwrap();
+
+    let delegate = Arc::new(FakeCoordinator {
+        calls: Mutex::new(vec![]),
+        fail: Mutex::new(vec!["add handler".to_string()]),
+    });
+    let executor = GoalExecutor::new(workspace, delegate.clone());
+    executor
+        .manager
+        .save_goal(&MacroGoalFile {
+            name: "auth".to_string(),
+            description: "add auth".to_string(),
+            phases: vec![
+                Phase {
+                    name: "setup".to_string(),
+                    description: "prepare".to_string(),
+                    status: Status::Pending,
+                    tasks: vec![task("t1", "touch marker", Some("touch marker"))],
+                },
+                Phase {
+                    name: "build".to_string(),
+                    description: "implement".to_string(),
+                    status: Status::Pending,
+                    tasks: vec![task("t2", "add model", None), task("t3", "add handler", None)],
+                },
+            ],
+            source: None,
+        })
+        .unwrap();
+
+    let mut log = Vec::new();
+    let err = executor.resume("auth", &mut |line| log.push(line)).await.unwrap_err();
+    assert!(err.to_string().contains("t3"));
+    assert!(root.join("marker").exists());
+
+    let saved = executor.manager.load_goal("auth").unwrap();
+    assert_eq!(saved.phases[0].status, Status::Done);
+    assert_eq!(saved.phases[1].status, Status::Failed);
+    assert_eq!(saved.phases[1].tasks[0].status, Status::Done);
+    let failed = &saved.phases[1].tasks[1];
+    assert_eq!(failed.status, Status::Failed);
+    assert_eq!(failed.attempts, 1);
+    assert!(failed.last_error.as_deref().unwrap().contains("gave up"));
+    assert!(failed.started_at.is_some() && failed.finished_at.is_some());
+
+    delegate.fail.lock().unwrap().clear();
+    executor.resume("auth", &mut |_| {}).await.unwrap();
+
+    let calls = delegate.calls.lock().unwrap().clone();
+    assert_eq!(calls, vec!["add model", "add handler", "add handler"]);
+    let saved = executor.manager.load_goal("auth").unwrap();
+    assert!(saved.phases.iter().all(|p| p.status == Status::Done));
+    assert_eq!(saved.phases[1].tasks[1].attempts, 2);
+    assert_eq!(saved.phases[1].tasks[1].last_error, None);
+    assert!(log.iter().any(|l| l.contains("Task t2")));
+}
+
+#[tokio::test]
+async fn test_goal_executor_orders_by_dependencies() {
+    let root = std::env::temp_dir().join("barqcoder_test_macro_goals_deps");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let delegate = Arc::new(FakeCoordinator {
+        calls: Mutex::new(vec![]),
+        fail: Mutex::new(vec![]),
+    });
+    let executor = GoalExecutor::new(root.to_str().unwrap(), delegate.clone());
+    let mut first = task("b", "second", None);
+    first.depends_on = vec!["a".to_string()];
+    executor
+        .manager
+        .save_goal(&MacroGoalFile {
+            name: "deps".to_string(),
+            description: String::new(),
+            phases: vec![Phase {
+                name: "one".to_string(),
+                description: String::new(),
+                status: Status::Pending,
+                tasks: vec![first, task("a", "first", None)],
+            }],
+            source: None,
+        })
+        .unwrap();
+
+    executor.resume("deps", &mut |_| {}).await.unwrap();
+    assert_eq!(delegate.calls.lock().unwrap().clone(), vec!["first", "second"]);
+}
+
+#[tokio::test]
+async fn test_goal_resumes_when_name_differs_from_file() {
+    let root = std::env::temp_dir().join("barqcoder_test_macro_goals_renamed");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let delegate = Arc::new(FakeCoordinator {
+        calls: Mutex::new(vec![]),
+        fail: Mutex::new(vec!["second".to_string()]),
+    });
+    let executor = GoalExecutor::new(root.to_str().unwrap(), delegate.clone());
+    let goals = root.join(".barqcoder/goals");
+    fs::write(
+        goals.join("release.yaml"),
+        "name: Release 1.0
+phases:
+  - name: p
+    tasks:
+      - id: a
+        description: first
+      - id: b
+        description: second
+",
+    )
+    .unwrap();
+
+    assert!(executor.resume("release", &mut |_| {}).await.is_err());
+    assert!(!goals.join("Release 1.0.yaml").exists());
+    delegate.fail.lock().unwrap().clear();
+    executor.resume("release", &mut |_| {}).await.unwrap();
+
+    // The second run picks up where the first stopped.
+    assert_eq!(delegate.calls.lock().unwrap().clone(), vec!["first", "second", "second"]);
+    let saved = executor.manager.load_goal("release").unwrap();
+    assert_eq!(saved.name, "Release 1.0");
+    assert!(saved.phases[0].tasks.iter().all(|t| t.status == Status::Done));
+}
+
+#[test]
+fn test_legacy_goal_files_still_load() {
+    let root = std::env::temp_dir().join("barqcoder_test_macro_goals_legacy");
+    let _ = fs::remove_dir_all(&root);
+    let manager = GoalManager::new(root.to_str().unwrap());
+    fs::write(
+        root.join(".barqcoder/goals/old.yaml"),
+        "name: old\ndescription: legacy\nphases:\n  - name: p\n    description: d\n    status: In Progress\n    tasks:\n      - id: t1\n        description: x\n        status: completed\n        command: null\n      - id: t2\n        description: y\n        status: todo\n        command: null\n",
+    )
+    .unwrap();
+
+    let goal = manager.load_goal("old").unwrap();
+    assert_eq!(goal.phases[0].status, Status::Running);
+    assert_eq!(goal.phases[0].tasks[0].status, Status::Done);
+    assert_eq!(goal.phases[0].tasks[1].status, Status::Pending);
+    assert!(goal.phases[0].tasks[1].depends_on.is_empty());
+}
+
+#[test]
+fn test_goal_validation_reports_lines() {
+    let root = std::env::temp_dir().join("barqcoder_test_macro_goals_invalid");
+    let _ = fs::remove_dir_all(&root);
+    let manager = GoalManager::new(root.to_str().unwrap());
+    let yaml = "name: bad
+phases:
+  - name: p
+    tasks:
+      - id: a
+        description: x
+        depends_on: [c]
+      - id: c
+        description: y
+        depends_on:
+          - a
+      - { id: d, description: z, depends_on: [missing] }
+";
+    fs::write(root.join(".barqcoder/goals/bad.yaml"), yaml).unwrap();
+
+    let err = manager.load_goal("bad").unwrap_err().to_string();
+    assert!(err.contains("line 12: task d depends on unknown task missing"), "{}", err);
+    assert!(err.contains("line 5: dependency cycle a -> c -> a"), "{}", err);
+}
@@ -10,9 +10,9 @@ use std::time::Duration;
 #[tokio::test]
 async fn test_max_iterations() {
     let config = Config::default();
-    let agent = OllamaClient::new("http://localhost:11434", "gemini-pro-3.1");
+    let agent = Arc::new(OllamaClient::new("http://localhost:11434", "gemini-pro-3.1"));
     let barq = Arc::new(BarqIndex::new(&config).unwrap());
-    let tools = ToolRegistry::new();
+    let tools = Arc::new(ToolRegistry::new(barq.clone()));
     
     let mut orchestrator = Orchestrator::new(agent, tools, barq, config);
     let mut rx = orchestrator.run("hello");
@@ -30,9 +30,9 @@ async fn test_max_iterations() {
 #[tokio::test]
 async fn test_final_answer() {
     let config = Config::default();
-    let agent = OllamaClient::new("http://localhost:11434", "gemini-pro-3.1");
+    let agent = Arc::new(OllamaClient::new("http://localhost:11434", "gemini-pro-3.1"));
     let barq = Arc::new(BarqIndex::new(&config).unwrap());
-    let tools = ToolRegistry::new();
+    let tools = Arc::new(ToolRegistry::new(barq.clone()));
     
     let mut orchestrator = Orchestrator::new(agent, tools, barq, config);
     let mut rx = orchestrator.run("hello");
@@ -0,0 +1,77 @@
+use barqcoder::agent::{LlmProvider, Message};
+use barqcoder::agents::planner::{parse_plan, validate_plan, PlanStep, PlannerAgent};
+use barqcoder::barq::BarqIndex;
+use barqcoder::config::Config;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Answers each request with the next scripted response.
+struct ScriptedProvider {
+    responses: Mutex<Vec<String>>,
+}
+
+impl LlmProvider for ScriptedProvider {
+    fn chat_stream(&self, _messages: Vec<Message>, _tools: Vec<Value>) -> anyhow::Result<mpsc::Receiver<String>> {
+        let (tx, rx) = mpsc::channel(1);
+        let response = self.responses.lock().unwrap().remove(0);
+        tokio::spawn(async move {
+            let _ = tx.send(response).await;
+        });
+        Ok(rx)
+    }
+}
+
+fn step(id: &str, depends_on: &[&str]) -> PlanStep {
+    PlanStep {
+        id: id.to_string(),
+        description: format!("do {}", id),
+        target_files: vec![],
+        acceptance_criteria: vec![],
+        depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+        role: None,
+    }
+}
+
+#[test]
+fn test_parse_plan_accepts_wrapped_json() {
+    let raw = r#"Here is the plan:
+```json
+{"steps": [
+  {"id": "a", "description": "add model", "target_files": ["src/model.rs"]},
+  {"id": "b", "description": "add handler", "depends_on": ["a"], "acceptance_criteria": ["returns 200"]}
+]}
+```"#;
+    let steps = parse_plan(raw).unwrap();
+    assert_eq!(steps.len(), 2);
+    assert_eq!(steps[1].depends_on, vec!["a".to_string()]);
+    assert_eq!(steps[0].target_files, vec!["src/model.rs".to_string()]);
+}
+
+#[test]
+fn test_validate_plan_rejects_bad_dependencies() {
+    let unknown = validate_plan(&[step("a", &["z"])]).unwrap_err();
+    assert!(unknown.to_string().contains("unknown step z"));
+
+    let cycle = validate_plan(&[step("a", &["c"]), step("b", &["a"]), step("c", &["b"])]).unwrap_err();
+    assert!(cycle.to_string().contains("a -> c -> b -> a"));
+
+    assert!(validate_plan(&[step("a", &[]), step("a", &[])]).is_err());
+    assert!(validate_plan(&[step("a", &[]), step("b", &["a"])]).is_ok());
+}
+
+#[tokio::test]
+async fn test_decompose_retries_malformed_output() {
+    let llm = Arc::new(ScriptedProvider {
+        responses: Mutex::new(vec![
+            "I think we should start with the model.".to_string(),
+            r#"{"final_answer": "[{\"id\": \"a\", \"description\": \"add model\"}]"}"#.to_string(),
+        ]),
+    });
+    let barq = Arc::new(BarqIndex::new(&Config::default()).unwrap());
+    let planner = PlannerAgent::new(llm, barq);
+
+    let steps = planner.decompose("add a model").await.unwrap();
+    assert_eq!(steps.len(), 1);
+    assert_eq!(steps[0].description, "add model");
+}
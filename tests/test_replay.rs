@@ -0,0 +1,58 @@
+use barqcoder::agent::{LlmProvider, Message};
+use barqcoder::replay::{RecordingProvider, ReplayProvider};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+struct ScriptedProvider;
+
+impl LlmProvider for ScriptedProvider {
+    fn chat_stream(&self, messages: Vec<Message>, _tools: Vec<Value>) -> anyhow::Result<mpsc::Receiver<String>> {
+        let (tx, rx) = mpsc::channel(10);
+        let last = messages.last().map(|m| m.content.clone()).unwrap_or_default();
+        tokio::spawn(async move {
+            let _ = tx.send("echo: ".to_string()).await;
+            let _ = tx.send(last).await;
+        });
+        Ok(rx)
+    }
+}
+
+fn user(content: &str) -> Vec<Message> {
+    vec![Message {
+        role: "user".to_string(),
+        content: content.to_string(),
+        tool_calls: None,
+        tool_call_id: None,
+    }]
+}
+
+async fn collect(mut rx: mpsc::Receiver<String>) -> String {
+    let mut out = String::new();
+    while let Some(chunk) = rx.recv().await {
+        out.push_str(&chunk);
+    }
+    out
+}
+
+#[tokio::test]
+async fn test_record_then_replay_offline() {
+    let path = std::env::temp_dir().join("barqcoder_test_replay.llm.jsonl");
+    let _ = std::fs::remove_file(&path);
+
+    let recorder = RecordingProvider::new(Arc::new(ScriptedProvider), &path);
+    assert_eq!(collect(recorder.chat_stream(user("hello"), vec![]).unwrap()).await, "echo: hello");
+    assert_eq!(collect(recorder.chat_stream(user("again"), vec![]).unwrap()).await, "echo: again");
+    // Each exchange is on disk by the time its stream ends.
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(recorded.lines().count(), 2);
+
+    let replay = ReplayProvider::load(&path).unwrap();
+    assert_eq!(collect(replay.chat_stream(user("again"), vec![]).unwrap()).await, "echo: again");
+    assert_eq!(collect(replay.chat_stream(user("hello"), vec![]).unwrap()).await, "echo: hello");
+    assert_eq!(replay.misses(), 0);
+
+    let miss = replay.chat_stream(user("unseen"), vec![]).unwrap_err();
+    assert!(miss.to_string().contains("No recorded response"));
+    assert_eq!(replay.misses(), 1);
+}
@@ -0,0 +1,70 @@
+use barqcoder::agents::reviewer::{parse_verdict, ReviewEvidence, Severity};
+use barqcoder::config::{Config, RejectionPolicy};
+use barqcoder::verifier::VerifyResult;
+
+#[test]
+fn test_parse_verdict_reads_issues_and_suggestions() {
+    let raw = r#"{"final_answer": "{\"approved\": false, \"issues\": [{\"file\": \"src/lib.rs\", \"line\": 12, \"severity\": \"warn\", \"message\": \"unwrap on user input\"}], \"suggested_changes\": [\"return a Result\"]}"}"#;
+    let verdict = parse_verdict(raw);
+
+    assert!(!verdict.approved);
+    assert_eq!(verdict.issues.len(), 1);
+    assert_eq!(verdict.issues[0].line, Some(12));
+    assert_eq!(verdict.issues[0].severity, Severity::Warning);
+
+    let feedback = verdict.feedback();
+    assert!(feedback.contains("src/lib.rs:12: unwrap on user input"));
+    assert!(feedback.contains("Suggested: return a Result"));
+}
+
+#[test]
+fn test_parse_verdict_treats_garbage_as_rejection() {
+    let verdict = parse_verdict("Looks good to me!");
+    assert!(!verdict.approved);
+    assert_eq!(verdict.issues[0].severity, Severity::Error);
+
+    assert!(parse_verdict("```json\n{\"approved\": true}\n```").approved);
+}
+
+#[test]
+fn test_review_config_defaults() {
+    let config: Config = toml::from_str("on_review_rejected = \"rollback\"").unwrap();
+    assert_eq!(config.max_review_rounds, 3);
+    assert_eq!(config.on_review_rejected, RejectionPolicy::Rollback);
+    assert_eq!(Config::default().on_review_rejected, RejectionPolicy::Escalate);
+}
+
+#[test]
+fn test_review_evidence_keeps_sources_in_separate_sections() {
+    let evidence = ReviewEvidence {
+        diff: "+fn a() {}\n".to_string(),
+        verification: Some(VerifyResult {
+            cargo_check_pass: true,
+            cargo_test_pass: false,
+            semantic_score: 0.9,
+            errors: vec!["test a ... FAILED".to_string()],
+            warnings: vec![],
+            symbolic_errors: vec!["src/lib.rs: unsafe block".to_string()],
+            symbolic_warnings: vec![],
+            symbolic_infos: vec![],
+            symbolic_diagnostics: vec![],
+            tests_run: vec!["cargo test --lib -- a::".to_string()],
+            full_suite: false,
+            timings: Default::default(),
+            should_revert: true,
+        }),
+        test_report: "1 added, 0 passing".to_string(),
+    };
+    let prompt = evidence.to_prompt_sections();
+
+    let diff = prompt.find("## Diff").unwrap();
+    let verification = prompt.find("## Verification").unwrap();
+    let symbolic = prompt.find("## Symbolic findings").unwrap();
+    let tests = prompt.find("## Test results").unwrap();
+    assert!(diff < verification && verification < symbolic && symbolic < tests);
+
+    assert!(prompt[verification..symbolic].contains("cargo test: FAIL (affected: cargo test --lib -- a::)"));
+    assert!(prompt[verification..symbolic].contains("test a ... FAILED"));
+    assert!(prompt[symbolic..tests].contains("unsafe block"));
+    assert!(prompt[tests..].contains("1 added"));
+}
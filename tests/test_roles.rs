@@ -0,0 +1,54 @@
+use barqcoder::agents::roles::RoleRegistry;
+use barqcoder::barq::BarqIndex;
+use barqcoder::config::Config;
+use barqcoder::tools::ToolRegistry;
+use std::fs;
+use std::sync::Arc;
+
+#[test]
+fn test_roles_from_config_and_directory() {
+    let dir = std::env::temp_dir().join("barqcoder_test_roles");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("docs.toml"),
+        "description = \"writes documentation\"\nsystem_prompt = \"You write docs.\"\ntools = [\"read_file\", \"create_file\"]\nmax_iterations = 4\n",
+    )
+    .unwrap();
+    fs::write(dir.join("coder.toml"), "temperature = 0.2\n").unwrap();
+
+    let config: Config = toml::from_str(
+        "ollama_model = \"small\"\n\n[[agents]]\nname = \"planner\"\nmodel = \"big\"\n",
+    )
+    .unwrap();
+    let roles = RoleRegistry::load(&config, &dir).unwrap();
+
+    let planner = roles.get("planner").unwrap();
+    assert_eq!(planner.model, "big");
+    assert!(planner.system_prompt.contains("Planner"));
+
+    let coder = roles.get("coder").unwrap();
+    assert_eq!(coder.model, "small");
+    assert_eq!(coder.temperature, Some(0.2));
+
+    let docs = roles.get("docs").unwrap();
+    assert_eq!(docs.system_prompt, "You write docs.");
+    assert_eq!(docs.max_iterations, Some(4));
+    assert_eq!(docs.model, "small");
+
+    let workers: Vec<&str> = roles.workers().map(|r| r.name.as_str()).collect();
+    assert_eq!(workers, vec!["coder", "docs"]);
+
+    let registry = ToolRegistry::new(Arc::new(BarqIndex::new(&Config::default()).unwrap()));
+    let tools = registry.subset(docs.tools.as_ref().unwrap()).unwrap();
+    assert_eq!(tools.schemas().len(), 2);
+    assert!(registry.subset(&["no_such_tool".to_string()]).is_err());
+}
+
+#[test]
+fn test_roles_reject_unknown_provider() {
+    let config: Config =
+        toml::from_str("[[agents]]\nname = \"migration\"\nprovider = \"nope\"\n").unwrap();
+    let dir = std::env::temp_dir().join("barqcoder_test_roles_missing");
+    assert!(RoleRegistry::load(&config, &dir).is_err());
+}
@@ -0,0 +1,220 @@
+use barqcoder::session::export::ExportFormat;
+use barqcoder::session::retention::{self, GcAction, RetentionPolicy, StoredSession};
+use barqcoder::session::{Session, SessionEvent, SessionStore};
+use serde_json::json;
+
+#[test]
+fn test_session_rebuilds_conversation() {
+    let mut session = Session::new("rebuild", "./");
+    session.push(SessionEvent::UserInput("fix the build".to_string()));
+    session.push(SessionEvent::AgentToken("Checking ".to_string()));
+    session.push(SessionEvent::AgentToken("first.".to_string()));
+    session.push(SessionEvent::ToolCalled {
+        id: "call_a".to_string(),
+        name: "cargo_check".to_string(),
+        args: json!({ "dir": "." }),
+        result: json!({ "success": true }),
+    });
+    session.push(SessionEvent::AgentToken("All green.".to_string()));
+
+    let conversation = session.to_conversation();
+    let roles: Vec<&str> = conversation.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, vec!["user", "assistant", "tool", "assistant"]);
+    assert_eq!(conversation[1].content, "Checking first.");
+    assert_eq!(conversation[1].tool_calls.as_ref().unwrap()[0].id, "call_a");
+    assert_eq!(conversation[2].tool_call_id.as_deref(), Some("call_a"));
+    assert_eq!(conversation[3].content, "All green.");
+}
+
+#[test]
+fn test_session_loads_legacy_format() {
+    let root = std::env::temp_dir().join("barqcoder_test_session_legacy");
+    let store = SessionStore::new(root.to_str().unwrap());
+    let legacy = r#"{
+        "id": "legacy",
+        "created_at": 1,
+        "workspace": "./",
+        "events": [
+            { "UserInput": "hi" },
+            { "ToolCalled": { "name": "read_file", "args": {}, "result": null } }
+        ]
+    }"#;
+    std::fs::write(root.join(".barqcoder/sessions/legacy.json"), legacy).unwrap();
+
+    let session = store.load("legacy").unwrap();
+    let conversation = session.to_conversation();
+    assert_eq!(conversation[2].tool_call_id.as_deref(), Some("call_1"));
+}
+
+#[test]
+fn test_session_export_formats() {
+    let mut session = Session::new("export", "./");
+    session.push(SessionEvent::UserInput("tweak main".to_string()));
+    session.push(SessionEvent::EditApplied {
+        file: "./src/main.rs".to_string(),
+        patch: "--- src/main.rs\n+++ src/main.rs\n@@ -1,1 +1,1 @@\n-fn main() {}\n+fn main() { run(); }\n".to_string(),
+    });
+    session.push(SessionEvent::AgentToken("Done.".to_string()));
+
+    let md = session.export(ExportFormat::Markdown).unwrap();
+    assert!(md.contains("**You:** tweak main"));
+    assert!(md.contains("```diff\n--- src/main.rs"));
+    assert!(md.contains("**Agent:** Done."));
+
+    let jsonl = session.export(ExportFormat::JsonLines).unwrap();
+    assert_eq!(jsonl.lines().count(), 3);
+
+    let patch = session.export(ExportFormat::Patch).unwrap();
+    assert!(patch.starts_with("diff --git a/src/main.rs b/src/main.rs\n--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,1 +1,1 @@\n"));
+    assert!(!patch.contains("--- src/main.rs"));
+}
+
+#[test]
+fn test_session_patch_folds_edits_per_file() {
+    let mut session = Session::new("fold", "./");
+    let edit = |file: &str, patch: &str| SessionEvent::EditApplied {
+        file: file.to_string(),
+        patch: patch.to_string(),
+    };
+    session.push(edit("src/new.rs", "--- /dev/null\n+++ src/new.rs\n@@ -0,0 +1,3 @@\n+fn a() {}\n+fn b() {}\n+fn c() {}\n"));
+    session.push(edit("src/lib.rs", "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"));
+    session.push(edit("src/new.rs", "@@ -1,3 +1,3 @@\n fn a() {}\n-fn b() {}\n+fn b() { a(); }\n fn c() {}\n"));
+    session.push(edit("src/lib.rs", "@@ -3,2 +3,3 @@\n c\n d\n+e\n"));
+
+    let patch = session.export(ExportFormat::Patch).unwrap();
+    assert_eq!(
+        patch,
+        "diff --git a/src/new.rs b/src/new.rs\nnew file mode 100644\n--- /dev/null\n+++ b/src/new.rs\n\
+         @@ -0,0 +1,3 @@\n+fn a() {}\n+fn b() { a(); }\n+fn c() {}\n\
+         diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n\
+         @@ -1,4 +1,5 @@\n a\n-b\n+B\n c\n d\n+e\n"
+    );
+}
+
+#[test]
+fn test_session_search_uses_index() {
+    let root = std::env::temp_dir().join("barqcoder_test_session_search");
+    let _ = std::fs::remove_dir_all(&root);
+    let store = SessionStore::new(root.to_str().unwrap());
+
+    let mut auth = Session::new("auth", "./");
+    auth.push(SessionEvent::UserInput("add token refresh".to_string()));
+    auth.push(SessionEvent::EditApplied {
+        file: "src/auth/jwt.rs".to_string(),
+        patch: "@@ -1,1 +1,1 @@\n-old\n+new\n".to_string(),
+    });
+    store.save(&auth).unwrap();
+
+    let mut lint = Session::new("lint", "./");
+    lint.push(SessionEvent::UserInput("make the build pass".to_string()));
+    lint.push(SessionEvent::Error("clippy failed: needless_borrow".to_string()));
+    store.save(&lint).unwrap();
+
+    assert!(root.join(".barqcoder/sessions/index.json").exists());
+    let titles: Vec<String> = store.list().into_iter().map(|m| m.title).collect();
+    assert!(titles.contains(&"add token refresh".to_string()));
+
+    let hits = store.search("when did we last touch the auth module", 5);
+    assert_eq!(hits[0].session_id, "auth");
+    assert_eq!(hits[0].event_index, 1);
+
+    let hits = store.search("the session where clippy kept failing", 5);
+    assert_eq!(hits[0].session_id, "lint");
+    assert_eq!(hits[0].event_index, 1);
+}
+
+#[test]
+fn test_session_search_snippet_survives_non_ascii() {
+    // "İ" grows from two bytes to three when lowercased.
+    let mut session = Session::new("turkish", "./");
+    session.push(SessionEvent::Error(format!("{} clippy failed", "İ".repeat(60))));
+
+    let hits = session.keyword_hits(&["clippy".to_string()]);
+    assert_eq!(hits.len(), 1);
+    assert!(hits[0].snippet.contains("clippy failed"), "{}", hits[0].snippet);
+}
+
+#[test]
+fn test_session_coalesces_tokens() {
+    let mut session = Session::new("coalesce", "./");
+    session.push(SessionEvent::UserInput("hi".to_string()));
+    session.push(SessionEvent::AgentToken("Hel".to_string()));
+    session.push(SessionEvent::AgentToken("lo".to_string()));
+    session.coalesce_tokens();
+
+    assert_eq!(session.events.len(), 2);
+    assert!(matches!(&session.events[1], SessionEvent::AgentMessage(m) if m == "Hello"));
+    assert_eq!(session.to_conversation()[1].content, "Hello");
+}
+
+#[test]
+fn test_session_gc_plan_and_compression() {
+    let root = std::env::temp_dir().join("barqcoder_test_session_gc");
+    let _ = std::fs::remove_dir_all(&root);
+    let store = SessionStore::new(root.to_str().unwrap());
+    for id in ["s1", "s2", "s3"] {
+        let mut session = Session::new(id, "./");
+        session.push(SessionEvent::UserInput(format!("task {}", id)));
+        store.save(&session).unwrap();
+    }
+
+    let keep_two = RetentionPolicy { keep_last: 2, ..Default::default() };
+    let plan = store.plan_gc(&keep_two);
+    assert_eq!(plan.actions.iter().filter(|a| matches!(a, GcAction::Delete { .. })).count(), 1);
+    store.apply_gc(&plan).unwrap();
+    assert_eq!(store.list().len(), 2);
+
+    let compress_all = RetentionPolicy { compress_after_days: 1, ..Default::default() };
+    let sessions = store.list();
+    let stored = sessions
+        .into_iter()
+        .map(|mut meta| {
+            meta.updated_at = 0;
+            meta.created_at = 0;
+            StoredSession { meta, size_bytes: 10, compressed: false }
+        })
+        .collect();
+    let plan = retention::plan(&compress_all, stored, 3 * 24 * 60 * 60);
+    assert_eq!(plan.actions.len(), 2);
+    store.apply_gc(&plan).unwrap();
+
+    let id = &store.list()[0].id;
+    assert!(root.join(format!(".barqcoder/sessions/{}.json.gz", id)).exists());
+    assert!(store.load(id).unwrap().events.len() == 1);
+}
+
+#[test]
+fn test_session_gc_spares_protected_sessions() {
+    let stored = |id: &str| StoredSession {
+        meta: barqcoder::session::SessionMeta {
+            id: id.to_string(),
+            created_at: 0,
+            event_count: 1,
+            workspace: "./".to_string(),
+            updated_at: 0,
+            title: String::new(),
+        },
+        size_bytes: 10,
+        compressed: false,
+    };
+    let policy = RetentionPolicy { max_age_days: 1, compress_after_days: 1, ..Default::default() }
+        .with_protected(["replayed".to_string()]);
+    let plan = retention::plan(&policy, vec![stored("replayed"), stored("old")], 3 * 24 * 60 * 60);
+
+    assert_eq!(plan.actions.len(), 1);
+    assert!(matches!(&plan.actions[0], GcAction::Delete { id, .. } if id == "old"));
+}
+
+#[test]
+fn test_session_finish_turn_stores_reply_once() {
+    let mut session = Session::new("finish", "./");
+    session.push(SessionEvent::UserInput("hi".to_string()));
+    session.push(SessionEvent::AgentToken("Think".to_string()));
+    session.push(SessionEvent::AgentToken("ing...".to_string()));
+    session.finish_turn("Hello".to_string());
+
+    assert_eq!(session.events.len(), 2);
+    let conversation = session.to_conversation();
+    let replies: Vec<&str> = conversation.iter().filter(|m| m.role == "assistant").map(|m| m.content.as_str()).collect();
+    assert_eq!(replies, vec!["Hello"]);
+}
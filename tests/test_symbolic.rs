@@ -0,0 +1,393 @@
+use barqcoder::symbolic::analyze;
+use barqcoder::symbolic::rules::{RuleSet, Severity};
+
+#[test]
+fn test_analyze_caches_by_content() {
+    let source = "pub fn f(s: &String) {\n    loop {\n        let _ = s.clone();\n        break;\n    }\n    unsafe {}\n}\n";
+    let (first, cached) = analyze("src/cache_probe.rs", source, &RuleSet::builtin());
+    assert!(!cached);
+    assert!(first.with_severity(Severity::Error).next().is_some());
+    assert!(first.with_severity(Severity::Warn).next().is_some());
+
+    let (second, cached) = analyze("src/cache_probe.rs", source, &RuleSet::builtin());
+    assert!(cached);
+    assert_eq!(first, second);
+
+    let (_, cached) = analyze("src/cache_probe.rs", &source.replace("unsafe {}", ""), &RuleSet::builtin());
+    assert!(!cached);
+
+    // The cache is bounded: enough other files push the first one out.
+    for i in 0..300 {
+        analyze(&format!("src/cache_filler_{}.rs", i), "pub fn f() {}\n", &RuleSet::builtin());
+    }
+    let (_, cached) = analyze("src/cache_probe.rs", source, &RuleSet::builtin());
+    assert!(!cached);
+}
+
+#[test]
+fn test_rules_yaml_layers_over_builtins() {
+    use barqcoder::symbolic::rules::glob_match;
+
+    let root = std::env::temp_dir().join("barqcoder_test_rules_yaml");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join(".barqcoder")).unwrap();
+    assert_eq!(RuleSet::load(&root).unwrap(), RuleSet::builtin());
+
+    std::fs::write(
+        root.join(".barqcoder/rules.yaml"),
+        "rules:\n  UNSAFE_BLOCK: off\n  CLONE_IN_LOOP:\n    severity: error\n    paths: [\"src/engine/**\"]\n    exclude: [\"**/bench_*.rs\"]\n",
+    )
+    .unwrap();
+    let rules = RuleSet::load(&root).unwrap();
+    assert_eq!(rules.severity("UNSAFE_BLOCK", "src/lib.rs"), Severity::Off);
+    assert_eq!(rules.severity("CLONE_IN_LOOP", "src/engine/core/mod.rs"), Severity::Error);
+    assert_eq!(rules.severity("CLONE_IN_LOOP", "src/engine/bench_hot.rs"), Severity::Off);
+    assert_eq!(rules.severity("CLONE_IN_LOOP", "src/lib.rs"), Severity::Off);
+    assert_eq!(rules.severity("O_N_SQUARED", "src/lib.rs"), Severity::Warn);
+
+    let source = "pub fn f(s: &String) {\n    loop {\n        let _ = s.clone();\n        break;\n    }\n    unsafe {}\n}\n";
+    let (report, _) = analyze("src/engine/hot.rs", source, &rules);
+    assert!(
+        report.diagnostics.iter().any(|d| d.rule_id == "CLONE_IN_LOOP" && d.severity == Severity::Error),
+        "{:?}",
+        report
+    );
+    assert!(!report.diagnostics.iter().any(|d| d.rule_id == "UNSAFE_BLOCK"));
+
+    // A bad entry is skipped on its own; the rest of the file still applies.
+    std::fs::write(
+        root.join(".barqcoder/rules.yaml"),
+        "rules:\n  NO_SUCH_RULE: warn\n  O_N_SQUARED: loud\n  UNSAFE_BLOCK: off\n",
+    )
+    .unwrap();
+    let rules = RuleSet::load(&root).unwrap();
+    assert_eq!(rules.severity("UNSAFE_BLOCK", "src/lib.rs"), Severity::Off);
+    assert_eq!(rules.severity("O_N_SQUARED", "src/lib.rs"), Severity::Warn);
+    assert_eq!(rules.warnings().len(), 2, "{:?}", rules.warnings());
+    assert!(rules.warnings()[0].contains("unknown rule NO_SUCH_RULE"));
+    assert!(rules.warnings()[1].contains("rule O_N_SQUARED"));
+
+    assert!(glob_match("src/*.rs", "src/lib.rs"));
+    assert!(!glob_match("src/*.rs", "src/a/lib.rs"));
+    assert!(glob_match("**/mod.rs", "mod.rs"));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_barq_allow_comments_suppress_rules() {
+    use barqcoder::symbolic::rules::Suppressions;
+
+    let source = "//! barq-allow(UNSAFE_BLOCK): FFI shim, audited\nuse std::ptr;\n\nfn f() {\n    // barq-allow(CLONE_IN_LOOP, O_N_SQUARED): small input\n\n    for x in v {}\n    let y = x.clone(); // barq-allow(EXCESSIVE_CLONE)\n}\n";
+    let suppressions = Suppressions::parse(source);
+    assert!(suppressions.allows("UNSAFE_BLOCK", None));
+    assert!(suppressions.allows("CLONE_IN_LOOP", Some(7)));
+    assert!(suppressions.allows("O_N_SQUARED", Some(7)));
+    assert!(!suppressions.allows("CLONE_IN_LOOP", Some(8)));
+    assert!(suppressions.allows("EXCESSIVE_CLONE", Some(8)));
+    assert!(!suppressions.allows("EXCESSIVE_CLONE", None));
+
+    // A plain comment before the first line of code covers only that line,
+    // and a `//` inside a string literal is not a comment.
+    let leading = "// barq-allow(UNSAFE_BLOCK): checked\npub fn f() {\n    let url = \"http://x\"; // barq-allow(O_N_SQUARED)\n    let s = \"// barq-allow(EXCESSIVE_CLONE)\";\n}\n";
+    let suppressions = Suppressions::parse(leading);
+    assert!(!suppressions.allows("UNSAFE_BLOCK", None));
+    assert!(suppressions.allows("UNSAFE_BLOCK", Some(2)));
+    assert!(!suppressions.allows("UNSAFE_BLOCK", Some(3)));
+    assert!(suppressions.allows("O_N_SQUARED", Some(3)));
+    assert!(!suppressions.allows("EXCESSIVE_CLONE", Some(4)));
+
+    let unsafe_source = "//! barq-allow(UNSAFE_BLOCK): checked\npub fn f() {\n    unsafe {}\n}\n";
+    let (report, _) = analyze("src/ffi.rs", unsafe_source, &RuleSet::builtin());
+    assert!(!report.diagnostics.iter().any(|d| d.rule_id == "UNSAFE_BLOCK"));
+}
+
+#[test]
+fn test_diagnostics_carry_rule_and_span() {
+    let source = "pub fn f() {\n    let x = 1;\n    unsafe { g() }\n}\n";
+    let (report, _) = analyze("src/spans.rs", source, &RuleSet::builtin());
+    let unsafe_block = report.diagnostics.iter().find(|d| d.rule_id == "UNSAFE_BLOCK").expect("unsafe finding");
+    assert_eq!(unsafe_block.severity, Severity::Error);
+    assert_eq!(unsafe_block.file.as_deref(), Some("src/spans.rs"));
+    let span = unsafe_block.span.expect("span");
+    assert_eq!((span.start.line, span.start.column), (3, 4));
+    assert_eq!((span.end.line, span.end.column), (3, 10));
+    assert!(unsafe_block.to_string().starts_with("src/spans.rs:3:5: [UNSAFE_BLOCK] "));
+
+    // A suppression on the line before the finding now applies to it.
+    let allowed = source.replace("    unsafe", "    // barq-allow(UNSAFE_BLOCK): g is sound\n    unsafe");
+    let (report, _) = analyze("src/spans.rs", &allowed, &RuleSet::builtin());
+    assert!(!report.diagnostics.iter().any(|d| d.rule_id == "UNSAFE_BLOCK"), "{:?}", report);
+}
+
+#[test]
+fn test_perf_rules_follow_loop_bodies() {
+    use barqcoder::symbolic::perf::lint_perf;
+
+    let source = r#"
+use std::collections::HashSet;
+
+pub fn report(users: &[User], banned: &[u64], seen: &HashSet<u64>, name: &String) -> Vec<String> {
+    let owned = name.clone();
+    let mut out = Vec::new();
+    for user in users {
+        let label = format!("{}: {}", owned, user.id);
+        if banned.contains(&user.id) || seen.contains(&user.id) {
+            continue;
+        }
+        for group in &user.groups {
+            out.push(group.name.clone());
+        }
+        for i in 0..3 {
+            out.push(i.to_string());
+        }
+        if users.iter().any(|u| u.id == user.id + 1) {
+            out.push(label);
+        }
+    }
+    let names: Vec<String> = users.iter().map(|u| u.name.chars().rev().collect()).collect();
+    out.extend(names);
+    out
+}
+"#;
+    let file = syn::parse_file(source).unwrap();
+    let found: Vec<(String, usize)> = lint_perf(&file)
+        .iter()
+        .map(|d| (d.rule_id.clone(), d.line().unwrap()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("ALLOC_IN_LOOP".to_string(), 8),
+            ("O_N_SQUARED".to_string(), 9),
+            ("O_N_SQUARED".to_string(), 12),
+            ("CLONE_IN_LOOP".to_string(), 13),
+            ("ALLOC_IN_LOOP".to_string(), 16),
+            ("O_N_SQUARED".to_string(), 18),
+            ("ALLOC_IN_LOOP".to_string(), 22),
+        ]
+    );
+
+    let clone = lint_perf(&file).into_iter().find(|d| d.rule_id == "CLONE_IN_LOOP").unwrap();
+    assert_eq!(clone.span.unwrap().start.column, 32);
+    assert!(clone.message.contains("group.name.clone()"), "{}", clone.message);
+
+    // `contains` on a string is a substring search, not a scan of a collection.
+    let strings = r#"
+pub fn matches(lines: &[String], title: &str, word: &String) -> usize {
+    let lower = title.to_lowercase();
+    let mut n = 0;
+    for line in lines {
+        if title.contains(word) || lower.contains(word) || line.trim().contains(word) {
+            n += 1;
+        }
+    }
+    n
+}
+"#;
+    let file = syn::parse_file(strings).unwrap();
+    assert!(lint_perf(&file).is_empty(), "{:?}", lint_perf(&file));
+}
+
+#[test]
+fn test_unsafe_audit_classifies_and_diffs() {
+    use barqcoder::symbolic::analyze_edit;
+    use barqcoder::symbolic::unsafe_check::{audit, UnsafeKind, UnsafeOp};
+
+    let original = r#"
+extern "C" {
+    fn abs(x: i32) -> i32;
+}
+
+static mut COUNTER: u32 = 0;
+
+pub fn bump() -> u32 {
+    // SAFETY: only called from the main thread.
+    unsafe {
+        COUNTER += 1;
+        COUNTER
+    }
+}
+"#;
+    let patched = format!(
+        "{}{}",
+        original,
+        r#"
+pub fn first(v: &[u8], p: *const u8) -> u8 {
+    let a = unsafe { *v.get_unchecked(0) };
+    let b = unsafe { *p };
+    let c = unsafe { abs(-1) } as u8;
+    let d: u8 = unsafe { std::mem::transmute(a) };
+    a + b + c + d
+}
+
+pub struct Handle(*mut u8);
+
+// SAFETY: the pointer is never shared.
+unsafe impl Send for Handle {}
+
+unsafe impl Sync for Handle {}
+
+pub unsafe trait Zeroable {}
+"#
+    );
+
+    let file = syn::parse_file(&patched).unwrap();
+    let sites = audit(&file, &patched);
+    let blocks: Vec<&[UnsafeOp]> = sites.iter().filter(|s| s.kind == UnsafeKind::Block).map(|s| &s.ops[..]).collect();
+    assert_eq!(
+        blocks,
+        vec![
+            &[UnsafeOp::StaticMutAccess][..],
+            &[UnsafeOp::UncheckedAccess][..],
+            &[UnsafeOp::RawPointerDeref][..],
+            &[UnsafeOp::FfiCall][..],
+            &[UnsafeOp::Transmute][..],
+        ]
+    );
+    assert!(sites[0].has_safety_comment);
+    assert_eq!(sites.iter().filter(|s| s.kind == UnsafeKind::Impl).count(), 2);
+    assert_eq!(sites.iter().filter(|s| s.kind == UnsafeKind::Trait).count(), 1);
+
+    // Only what the edit introduced is new; bump's block was already there.
+    let (report, _) = analyze_edit("src/ffi.rs", Some(original), &patched, &RuleSet::builtin());
+    let new: Vec<usize> = report
+        .diagnostics
+        .iter()
+        .filter(|d| d.rule_id == "UNSAFE_BLOCK")
+        .map(|d| d.line().unwrap())
+        .collect();
+    assert_eq!(new, vec![17, 18, 19, 20, 27, 29, 31]);
+    assert!(report.diagnostics.iter().all(|d| d.severity != Severity::Error || d.rule_id == "UNSAFE_BLOCK"));
+    let uncommented: Vec<usize> = report
+        .diagnostics
+        .iter()
+        .filter(|d| d.rule_id == "MISSING_SAFETY_COMMENT")
+        .map(|d| d.line().unwrap())
+        .collect();
+    assert_eq!(uncommented, vec![17, 18, 19, 20, 29]);
+    assert!(report.diagnostics[0].message.contains("(unchecked access)"), "{}", report.diagnostics[0]);
+
+    // Re-verifying the unchanged file reports no new unsafe code.
+    let (report, _) = analyze_edit("src/ffi.rs", Some(&patched), &patched, &RuleSet::builtin());
+    assert!(!report.diagnostics.iter().any(|d| d.rule_id == "UNSAFE_BLOCK"));
+
+    // Renaming the function or editing the block without changing what it
+    // does keeps it the same site.
+    let renamed = original.replace("bump()", "increment()").replace("COUNTER += 1;", "COUNTER += 2;");
+    let (report, _) = analyze_edit("src/ffi.rs", Some(original), &renamed, &RuleSet::builtin());
+    assert!(!report.diagnostics.iter().any(|d| d.rule_id == "UNSAFE_BLOCK"));
+
+    // Code between a SAFETY comment and the block breaks the justification.
+    let interrupted = r#"
+pub fn bump(count: &mut u32, p: *const u32) -> u32 {
+    // SAFETY: p is valid.
+    *count += 1;
+    unsafe { *p }
+}
+"#;
+    let file = syn::parse_file(interrupted).unwrap();
+    let sites = audit(&file, interrupted);
+    assert_eq!(sites[0].ops, vec![UnsafeOp::RawPointerDeref]);
+    assert!(!sites[0].has_safety_comment);
+}
+
+#[test]
+fn test_find_dead_code_walks_the_crate() {
+    use barqcoder::symbolic::dead_code::{find_dead_code, find_new_dead_code};
+
+    let root = std::env::temp_dir().join("barqcoder_test_dead_code");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("src/shapes")).unwrap();
+    std::fs::write(
+        root.join("src/main.rs"),
+        r#"mod shapes;
+mod legacy;
+
+fn main() {
+    let shape = shapes::Shape::Circle(shapes::radius());
+    println!("{}", shape);
+}
+
+pub fn exported_but_unused() {}
+
+fn helper_for_tests() -> u32 {
+    7
+}
+
+#[cfg(test)]
+mod tests {
+    fn fixture() -> u32 {
+        super::helper_for_tests()
+    }
+
+    #[test]
+    fn uses_helper() {
+        assert_eq!(fixture(), 7);
+    }
+}
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("src/shapes/mod.rs"),
+        r#"use std::fmt;
+
+pub enum Shape {
+    Circle(f64),
+    Square(f64),
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", describe())
+    }
+}
+
+fn describe() -> &'static str {
+    "shape"
+}
+
+pub fn radius() -> f64 {
+    1.0
+}
+"#,
+    )
+    .unwrap();
+    std::fs::write(root.join("src/legacy.rs"), "pub fn old() {}\n\npub struct Old;\n").unwrap();
+
+    let dead = find_dead_code(&root).unwrap();
+    let messages: Vec<String> = dead.iter().map(|d| format!("{}:{} {}", d.file.as_deref().unwrap(), d.line().unwrap(), d.message)).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "src/main.rs:2 Module `legacy` is never used.",
+            "src/main.rs:9 Function `exported_but_unused` is never used.",
+            "src/main.rs:11 Function `helper_for_tests` is only used by tests.",
+            "src/shapes/mod.rs:5 Variant `Shape::Square` is never used.",
+        ]
+    );
+
+    // Only what the patched crate adds is new, and an original that cannot
+    // be analysed is no baseline at all.
+    let patched = std::env::temp_dir().join("barqcoder_test_dead_code_patched");
+    let _ = std::fs::remove_dir_all(&patched);
+    std::fs::create_dir_all(&patched).unwrap();
+    for file in ["src/main.rs", "src/legacy.rs", "src/shapes/mod.rs"] {
+        let target = patched.join(file);
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        std::fs::copy(root.join(file), &target).unwrap();
+    }
+    let main = std::fs::read_to_string(patched.join("src/main.rs")).unwrap();
+    std::fs::write(patched.join("src/main.rs"), format!("{}\nfn orphan() {{}}\n", main)).unwrap();
+    let new: Vec<String> = find_new_dead_code(&root, &patched).unwrap().into_iter().map(|d| d.message).collect();
+    assert_eq!(new, vec!["Function `orphan` is never used."]);
+
+    // Once the original changes, its cached analysis is not reused.
+    std::fs::write(root.join("src/main.rs"), format!("{}\nfn orphan() {{}}\n", main)).unwrap();
+    assert!(find_new_dead_code(&root, &patched).unwrap().is_empty());
+    assert!(find_new_dead_code(&std::env::temp_dir().join("barqcoder_test_no_crate"), &patched).is_err());
+    let _ = std::fs::remove_dir_all(&patched);
+
+    let _ = std::fs::remove_dir_all(&root);
+}
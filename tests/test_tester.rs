@@ -0,0 +1,99 @@
+use barqcoder::agent::{LlmProvider, Message};
+use barqcoder::agents::tester::{FailureCause, TestReport, TesterAgent};
+use barqcoder::barq::BarqIndex;
+use barqcoder::config::Config;
+use barqcoder::tools::cargo_test::TestRun;
+use barqcoder::tools::ToolRegistry;
+use serde_json::{json, Value};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Answers each request with the next scripted response.
+struct ScriptedProvider {
+    responses: Mutex<Vec<String>>,
+}
+
+impl LlmProvider for ScriptedProvider {
+    fn chat_stream(&self, _messages: Vec<Message>, _tools: Vec<Value>) -> anyhow::Result<mpsc::Receiver<String>> {
+        let (tx, rx) = mpsc::channel(1);
+        let response = self.responses.lock().unwrap().remove(0);
+        tokio::spawn(async move {
+            let _ = tx.send(response).await;
+        });
+        Ok(rx)
+    }
+}
+
+#[test]
+fn test_report_prefers_observed_results() {
+    let run = TestRun {
+        success: false,
+        compiled: true,
+        passed: 3,
+        failed: 1,
+        failures: vec!["parser::tests::empty".to_string()],
+        compile_errors: vec![],
+    };
+    let answer = r#"{"tests_added": [], "failures": [{"name": "empty", "message": "panics on empty input", "cause": "implementation_bug"}], "summary": "all good"}"#;
+    let report = TestReport::from_run(answer, Some(&run), vec!["src/parser.rs".to_string()]);
+
+    assert!(report.ran && report.compiled);
+    assert_eq!((report.passed, report.failed), (3, 1));
+    assert_eq!(report.tests_added, vec!["src/parser.rs".to_string()]);
+    assert_eq!(report.failures[0].name, "parser::tests::empty");
+    assert_eq!(report.failures[0].cause, FailureCause::ImplementationBug);
+    assert!(report.has_implementation_bugs());
+
+    let unrun = TestReport::from_run(r#"{"summary": "tests pass", "failures": []}"#, None, vec![]);
+    assert!(!unrun.ran);
+    assert!(!unrun.all_passed());
+}
+
+#[tokio::test]
+async fn test_tester_writes_runs_and_reports() {
+    let root = std::env::temp_dir().join("barqcoder_test_tester_crate");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"tiny\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    fs::write(root.join("src/lib.rs"), "pub fn add(a: i32, b: i32) -> i32 { a - b }\n").unwrap();
+
+    let responses = vec![
+        json!({
+            "reasoning": "add a test for add",
+            "tool_calls": [{"name": "create_file", "arguments": {
+                "path": "tests/add.rs",
+                "content": "#[test]\nfn adds() { assert_eq!(tiny::add(2, 2), 4); }\n"
+            }}],
+            "final_answer": null
+        }),
+        json!({
+            "reasoning": "run it",
+            "tool_calls": [{"name": "cargo_test", "arguments": {}}],
+            "final_answer": null
+        }),
+        json!({
+            "reasoning": "add subtracts",
+            "tool_calls": [],
+            "final_answer": "{\"tests_added\": [\"tests/add.rs::adds\"], \"failures\": [{\"name\": \"adds\", \"message\": \"left: 0, right: 4\", \"cause\": \"implementation_bug\"}], \"summary\": \"add subtracts instead of adding\"}"
+        }),
+    ];
+    let llm = Arc::new(ScriptedProvider {
+        responses: Mutex::new(responses.into_iter().map(|r| r.to_string()).collect()),
+    });
+    let barq = Arc::new(BarqIndex::new(&Config::default()).unwrap());
+    let tester = TesterAgent::new(llm, barq.clone(), Arc::new(ToolRegistry::new(barq))).in_dir(root.to_str().unwrap());
+
+    let report = tester.test_step("s1", "implemented add").await.unwrap();
+
+    assert!(root.join("tests/add.rs").exists());
+    assert!(report.ran && report.compiled);
+    assert_eq!((report.passed, report.failed), (0, 1));
+    assert_eq!(report.failures[0].name, "adds");
+    assert_eq!(report.failures[0].cause, FailureCause::ImplementationBug);
+    assert_eq!(report.tests_added, vec!["tests/add.rs::adds".to_string()]);
+}
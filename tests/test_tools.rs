@@ -4,9 +4,13 @@ use barqcoder::tools::{Tool, ToolRegistry};
 use serde_json::json;
 use std::sync::Arc;
 
+fn registry() -> ToolRegistry {
+    ToolRegistry::new(Arc::new(BarqIndex::new(&Config::default()).unwrap()))
+}
+
 #[tokio::test]
 async fn test_cargo_check_valid() {
-    let registry = ToolRegistry::new();
+    let registry = registry();
     let tool = registry.get("cargo_check").unwrap();
     let args = json!({ "dir": "testdata" }); // We'll just run on barq-coder root since testdata isn't a crate, wait actually we can run on .
     // Running on current repo directory
@@ -17,7 +21,7 @@ async fn test_cargo_check_valid() {
 
 #[tokio::test]
 async fn test_cargo_check_invalid() {
-    let registry = ToolRegistry::new();
+    let registry = registry();
     let tool = registry.get("cargo_check").unwrap();
     let args = json!({ "dir": "testdata/bad_rust" });
     let res = tool.call(args).await.unwrap();
@@ -27,7 +31,7 @@ async fn test_cargo_check_invalid() {
 
 #[tokio::test]
 async fn test_edit_file_preview() {
-    let registry = ToolRegistry::new();
+    let registry = registry();
     let tool = registry.get("edit_file").unwrap();
     let args = json!({
         "file_path": "testdata/sample.rs",
@@ -49,3 +53,72 @@ async fn test_barq_search_returns() {
     let results = res["results"].as_array().unwrap();
     assert!(!results.is_empty());
 }
+
+#[test]
+fn test_parse_test_output_sums_binaries_and_failures() {
+    use barqcoder::tools::cargo_test::parse_test_output;
+
+    let stdout = "running 2 tests\ntest a ... ok\ntest m::b ... FAILED\n\ntest result: FAILED. 1 passed; 1 failed; 0 ignored; 0 measured; 0 filtered out\n\nrunning 1 test\ntest c ... ok\n\ntest result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out\n";
+    let run = parse_test_output(false, stdout, "");
+    assert!(run.compiled);
+    assert_eq!((run.passed, run.failed), (2, 1));
+    assert_eq!(run.failures, vec!["m::b".to_string()]);
+
+    let stderr = "error[E0425]: cannot find function `nope` in this scope\n --> tests/x.rs:1:13\n\nerror: could not compile `tiny` (test \"x\") due to 1 previous error\n";
+    let run = parse_test_output(false, "", stderr);
+    assert!(!run.compiled);
+    assert_eq!(run.compile_errors.len(), 1);
+    assert!(run.compile_errors[0].contains("tests/x.rs:1:13"));
+}
+
+#[tokio::test]
+async fn test_edit_file_rolls_back_failing_edit() {
+    let root = std::env::temp_dir().join("barqcoder_test_edit_rollback");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"tiny\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    let lib = root.join("src/lib.rs");
+    let original = "pub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n";
+    std::fs::write(&lib, original).unwrap();
+
+    let registry = registry();
+    let tool = registry.get("edit_file").unwrap();
+    let edit = |body: &str| {
+        json!({
+            "file_path": lib.to_string_lossy(),
+            "dir": root.to_string_lossy(),
+            "patch": format!("--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,3 @@\n pub fn add(a: i32, b: i32) -> i32 {{\n-    a + b\n+{}\n }}\n", body),
+        })
+    };
+
+    let res = tool.call(edit("    a +")).await.unwrap();
+    assert_eq!(res["applied"], false);
+    assert_eq!(res["reverted"], true);
+    assert_eq!(res["verification"]["cargo_check_pass"], false);
+    assert_eq!(res["verification"]["should_revert"], true);
+    assert_eq!(std::fs::read_to_string(&lib).unwrap(), original);
+
+    let res = tool.call(edit("    b + a")).await.unwrap();
+    assert_eq!(res["applied"], true, "{}", res);
+    assert_eq!(res["verification"]["cargo_test_pass"], true);
+    assert!(std::fs::read_to_string(&lib).unwrap().contains("b + a"));
+
+    // A relative path is resolved against `dir`, and a rejected new file is never created.
+    std::fs::write(&lib, "pub mod extra;\n").unwrap();
+    let res = tool
+        .call(json!({
+            "file_path": "src/extra.rs",
+            "dir": root.to_string_lossy(),
+            "patch": "--- /dev/null\n+++ b/src/extra.rs\n@@ -0,0 +1 @@\n+pub fn broken( {}\n",
+        }))
+        .await
+        .unwrap();
+    assert_eq!(res["reverted"], true, "{}", res);
+    assert!(!root.join("src/extra.rs").exists());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
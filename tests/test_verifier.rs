@@ -0,0 +1,224 @@
+use barqcoder::barq::BarqIndex;
+use barqcoder::config::Config;
+use barqcoder::verifier::{remove_mirrors, FileChange, ScratchCopy, Verifier};
+use std::fs;
+use std::sync::Arc;
+
+fn tiny_crate(name: &str) -> std::path::PathBuf {
+    let root = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"tiny\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    fs::write(root.join("src/lib.rs"), "pub fn add(a: i32, b: i32) -> i32 { a + b }\n").unwrap();
+    root
+}
+
+#[tokio::test]
+async fn test_scratch_copy_applies_changes_without_touching_workspace() {
+    let root = tiny_crate("barqcoder_test_scratch_copy");
+    fs::create_dir_all(root.join("target/debug")).unwrap();
+    fs::write(root.join("target/debug/junk"), "x").unwrap();
+    fs::write(root.join("README.md"), "readme").unwrap();
+
+    let changes = vec![
+        FileChange {
+            path: root.join("src/lib.rs").to_string_lossy().to_string(),
+            original: "pub fn add(a: i32, b: i32) -> i32 { a + b }\n".to_string(),
+            patched: "pub fn add(a: i32, b: i32) -> i32 { b + a }\n".to_string(),
+        },
+        FileChange {
+            path: "README.md".to_string(),
+            original: "readme".to_string(),
+            patched: String::new(),
+        },
+        FileChange {
+            path: "src/new.rs".to_string(),
+            original: String::new(),
+            patched: "pub struct New;\n".to_string(),
+        },
+    ];
+    let scratch = ScratchCopy::create(&root, &changes).await.unwrap();
+    let dir = scratch.path().to_path_buf();
+
+    assert!(fs::read_to_string(dir.join("src/lib.rs")).unwrap().contains("b + a"));
+    assert!(fs::read_to_string(root.join("src/lib.rs")).unwrap().contains("a + b"));
+    assert!(!dir.join("README.md").exists());
+    assert!(root.join("README.md").exists());
+    assert!(dir.join("src/new.rs").exists());
+    assert!(!dir.join("target").exists());
+    // The mirror stays inside the workspace it copies.
+    assert!(dir.starts_with(root.canonicalize().unwrap().join("target")));
+
+    // The next verification reuses the mirror, undoing the earlier changes
+    // and picking up the workspace's own.
+    drop(scratch);
+    fs::write(root.join("src/lib.rs"), "pub fn add(a: i32, b: i32) -> i32 { a + b + 0 }\n").unwrap();
+    let scratch = ScratchCopy::create(&root, &[]).await.unwrap();
+    assert_eq!(scratch.path(), dir);
+    assert!(fs::read_to_string(dir.join("src/lib.rs")).unwrap().contains("a + b + 0"));
+    assert!(dir.join("README.md").exists());
+    assert!(!dir.join("src/new.rs").exists());
+
+    // Mirrors in use survive cleanup; the rest are deleted.
+    remove_mirrors();
+    assert!(dir.exists());
+    drop(scratch);
+    remove_mirrors();
+    assert!(!dir.exists());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_verifier_checks_candidate_not_disk() {
+    let root = tiny_crate("barqcoder_test_verifier_candidate");
+    let barq = Arc::new(BarqIndex::new(&Config::default()).unwrap());
+    let verifier = Verifier::new(barq, root.to_str().unwrap());
+    let original = fs::read_to_string(root.join("src/lib.rs")).unwrap();
+
+    let broken = verifier
+        .verify_edit("src/lib.rs", &original, "pub fn add(a: i32, b: i32) -> i32 { a + }\n")
+        .await;
+    assert!(!broken.cargo_check_pass);
+    assert!(broken.should_revert);
+    assert_eq!(fs::read_to_string(root.join("src/lib.rs")).unwrap(), original);
+
+    // The workspace on disk is broken, the candidate is fine.
+    fs::write(root.join("src/lib.rs"), "pub fn add(a: i32, b: i32) -> i32 { a + }\n").unwrap();
+    let fixed = verifier.verify_edit("src/lib.rs", "", &original).await;
+    assert!(fixed.cargo_check_pass, "{:?}", fixed.errors);
+    assert!(fixed.cargo_test_pass, "{:?}", fixed.errors);
+    assert!(!fixed.should_revert);
+    assert!(root.join("target/barqcoder-verify").exists());
+    assert!(fixed.timings.total_ms >= fixed.timings.check_ms + fixed.timings.test_ms);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_verifier_resolves_relative_path_dependencies() {
+    let parent = std::env::temp_dir().join("barqcoder_test_verifier_path_deps");
+    let _ = fs::remove_dir_all(&parent);
+    for (name, manifest, lib) in [
+        ("dep", "", "pub fn one() -> i32 { 1 }\n"),
+        ("app", "\n[dependencies]\ndep = { path = \"../dep\" }\n", "pub fn two() -> i32 { dep::one() + 1 }\n"),
+    ] {
+        fs::create_dir_all(parent.join(name).join("src")).unwrap();
+        fs::write(
+            parent.join(name).join("Cargo.toml"),
+            format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n{}\n[workspace]\n", name, manifest),
+        )
+        .unwrap();
+        fs::write(parent.join(name).join("src/lib.rs"), lib).unwrap();
+    }
+
+    let app = parent.join("app");
+    let barq = Arc::new(BarqIndex::new(&Config::default()).unwrap());
+    let verifier = Verifier::new(barq, app.to_str().unwrap());
+    let original = fs::read_to_string(app.join("src/lib.rs")).unwrap();
+    let result = verifier
+        .verify_edit("src/lib.rs", &original, "pub fn two() -> i32 { dep::one() * 2 }\n")
+        .await;
+    assert!(result.cargo_check_pass, "{:?}", result.errors);
+
+    fs::remove_dir_all(&parent).unwrap();
+}
+
+#[test]
+fn test_affected_selection_follows_changed_items() {
+    use barqcoder::verifier::affected::select;
+
+    let root = tiny_crate("barqcoder_test_affected_selection");
+    fs::write(root.join("src/lib.rs"), "pub mod math;\npub mod text;\n").unwrap();
+    let math = "/// ```\n/// assert_eq!(tiny::math::add(1, 2), 3);\n/// ```\npub fn add(a: i32, b: i32) -> i32 { a + b }\n\npub fn sub(a: i32, b: i32) -> i32 { a - b }\n";
+    fs::write(root.join("src/math.rs"), math).unwrap();
+    fs::write(root.join("src/text.rs"), "pub fn shout(s: &str) -> String { s.to_uppercase() }\n").unwrap();
+    fs::create_dir_all(root.join("tests")).unwrap();
+    fs::write(root.join("tests/adding.rs"), "use tiny::math::add;\n#[test]\nfn adds() { assert_eq!(add(1, 1), 2); }\n").unwrap();
+    fs::write(root.join("tests/shouting.rs"), "#[test]\nfn shouts() { assert_eq!(tiny::text::shout(\"a\"), \"A\"); }\n").unwrap();
+
+    let edit = |path: &str, original: &str, patched: &str| FileChange {
+        path: path.to_string(),
+        original: original.to_string(),
+        patched: patched.to_string(),
+    };
+    let no_graph = |_: &str| Vec::new();
+
+    let selection = select(&root, &[edit("src/math.rs", math, &math.replace("a + b", "b + a"))], no_graph);
+    let runs: Vec<String> = selection.runs.iter().map(|r| r.to_string()).collect();
+    assert_eq!(
+        runs,
+        vec!["cargo test --lib -- math::", "cargo test --test adding", "cargo test --doc -- add"]
+    );
+    assert!(!selection.is_full());
+
+    // Changing `sub` reaches no integration test and no doc example.
+    let selection = select(&root, &[edit("src/math.rs", math, &math.replace("a - b", "b - a"))], no_graph);
+    let runs: Vec<String> = selection.runs.iter().map(|r| r.to_string()).collect();
+    assert_eq!(runs, vec!["cargo test --lib -- math::"]);
+
+    // The code graph can link further tests to a file.
+    let graph = |path: &str| if path == "src/math.rs" { vec!["tests/shouting.rs".to_string()] } else { vec![] };
+    let selection = select(&root, &[edit("src/math.rs", math, &math.replace("a - b", "b - a"))], graph);
+    assert!(selection.runs.iter().any(|r| r.to_string() == "cargo test --test shouting"));
+
+    let selection = select(&root, &[edit("Cargo.toml", "", "[package]")], no_graph);
+    assert!(selection.is_full());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_affected_selection_tells_same_named_methods_apart() {
+    use barqcoder::verifier::affected::select;
+
+    let root = tiny_crate("barqcoder_test_affected_same_names");
+    let lib = "pub struct A;\nimpl A {\n    pub fn new() -> Self { A }\n}\n\npub struct B;\nimpl B {\n    pub fn new() -> Self { B }\n}\n";
+    fs::write(root.join("src/lib.rs"), lib).unwrap();
+    fs::create_dir_all(root.join("tests")).unwrap();
+    fs::write(root.join("tests/building.rs"), "#[test]\nfn builds() { let _ = tiny::A::new(); }\n").unwrap();
+
+    // Only the first `new` changes; it used to be shadowed by the second.
+    let change = FileChange {
+        path: "src/lib.rs".to_string(),
+        original: lib.to_string(),
+        patched: lib.replacen("Self { A }", "Self { let a = A; a }", 1),
+    };
+    let selection = select(&root, &[change], |_| Vec::new());
+    assert!(selection.runs.iter().any(|r| r.to_string() == "cargo test --test building"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_semantic_score_flags_unrelated_items() {
+    use barqcoder::barq::embed_text;
+    use barqcoder::verifier::semantic::score;
+
+    let original = "pub fn add(a: i32, b: i32) -> i32 { a + b }\n\npub fn shout(s: &str) -> String { s.to_uppercase() }\n";
+    let edit = |patched: &str| FileChange {
+        path: "src/lib.rs".to_string(),
+        original: original.to_string(),
+        patched: patched.to_string(),
+    };
+    let with_multiply = format!("{}\npub fn multiply(a: i32, b: i32) -> i32 {{ a * b }}\n", original);
+    let intent = Some("add a multiply function for two integers");
+
+    let (related, warnings) = score(intent, &[edit(&with_multiply)], embed_text);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+
+    let with_shout = with_multiply.replace("s.to_uppercase()", "format!(\"{}!\", s.to_uppercase())");
+    let (mixed, warnings) = score(intent, &[edit(&with_shout)], embed_text);
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("`shout`"));
+    assert!(mixed < related);
+
+    // Without an intent the score measures drift between versions.
+    assert_eq!(score(None, &[edit(original)], embed_text).0, 1.0);
+    let (drift, warnings) = score(None, &[edit(&original.replace("a + b", "a.wrapping_add(b)"))], embed_text);
+    assert!(warnings.is_empty());
+    assert!(drift > 0.8 && drift < 1.0, "{}", drift);
+}
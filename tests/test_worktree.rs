@@ -0,0 +1,111 @@
+use barqcoder::agents::worktree::{is_git_repo, MergeOutcome, Worktree};
+use std::fs;
+use std::process::Command;
+
+fn git(dir: &str, args: &[&str]) {
+    let status = Command::new("git").args(args).current_dir(dir).status().unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[tokio::test]
+async fn test_worktrees_merge_and_detect_conflicts() {
+    let root = std::env::temp_dir().join("barqcoder_test_worktree_repo");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let repo = root.to_str().unwrap();
+    git(repo, &["init", "-q"]);
+    git(repo, &["config", "user.email", "test@example.com"]);
+    git(repo, &["config", "user.name", "test"]);
+    fs::write(root.join("lib.rs"), "fn a() {}\n").unwrap();
+    git(repo, &["add", "-A"]);
+    git(repo, &["commit", "-q", "-m", "init"]);
+    assert!(is_git_repo(repo).await);
+
+    let first = Worktree::create(repo, "step 1").await.unwrap();
+    let second = Worktree::create(repo, "step 2").await.unwrap();
+    fs::write(first.path.join("lib.rs"), "fn a() { one() }\n").unwrap();
+    fs::write(second.path.join("lib.rs"), "fn a() { two() }\n").unwrap();
+    assert!(first.commit("one").await.unwrap());
+    assert!(second.commit("two").await.unwrap());
+
+    assert_eq!(first.merge_into(repo).await.unwrap(), MergeOutcome::Merged);
+    assert_eq!(
+        second.merge_into(repo).await.unwrap(),
+        MergeOutcome::Conflict(vec!["lib.rs".to_string()])
+    );
+    assert_eq!(fs::read_to_string(root.join("lib.rs")).unwrap(), "fn a() { one() }\n");
+
+    first.remove(repo).await.unwrap();
+    second.remove(repo).await.unwrap();
+    assert!(!first.path.exists());
+}
+
+#[tokio::test]
+async fn test_worktree_diff_and_changes_include_new_files() {
+    let root = std::env::temp_dir().join("barqcoder_test_worktree_diff_repo");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let repo = root.to_str().unwrap();
+    git(repo, &["init", "-q"]);
+    git(repo, &["config", "user.email", "test@example.com"]);
+    git(repo, &["config", "user.name", "test"]);
+    fs::write(root.join("lib.rs"), "fn a() {}\n").unwrap();
+    git(repo, &["add", "-A"]);
+    git(repo, &["commit", "-q", "-m", "init"]);
+
+    let wt = Worktree::create(repo, "diff").await.unwrap();
+    fs::write(wt.path.join("lib.rs"), "fn a() { b() }\n").unwrap();
+    fs::write(wt.path.join("new.rs"), "fn b() {}\n").unwrap();
+
+    let diff = wt.diff().await.unwrap();
+    assert!(diff.contains("+fn a() { b() }"));
+    assert!(diff.contains("diff --git a/new.rs b/new.rs"));
+
+    let changes = wt.changes().await.unwrap();
+    assert_eq!(changes.len(), 2);
+    let edited = changes.iter().find(|c| c.path == "lib.rs").unwrap();
+    assert_eq!(edited.original, "fn a() {}\n");
+    assert_eq!(edited.patched, "fn a() { b() }\n");
+    assert!(changes.iter().any(|c| c.path == "new.rs" && c.original.is_empty()));
+
+    wt.remove(repo).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_worktree_builds_with_relative_path_dependencies() {
+    let parent = std::env::temp_dir().join("barqcoder_test_worktree_path_deps");
+    let _ = fs::remove_dir_all(&parent);
+    for (name, manifest, lib) in [
+        ("dep", "", "pub fn one() -> i32 { 1 }\n"),
+        ("app", "\n[dependencies]\ndep = { path = \"../dep\" }\n", "pub fn two() -> i32 { dep::one() + 1 }\n"),
+    ] {
+        fs::create_dir_all(parent.join(name).join("src")).unwrap();
+        fs::write(
+            parent.join(name).join("Cargo.toml"),
+            format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n{}\n[workspace]\n", name, manifest),
+        )
+        .unwrap();
+        fs::write(parent.join(name).join("src/lib.rs"), lib).unwrap();
+    }
+    let app = parent.join("app");
+    let repo = app.to_str().unwrap();
+    git(repo, &["init", "-q"]);
+    git(repo, &["config", "user.email", "test@example.com"]);
+    git(repo, &["config", "user.name", "test"]);
+    git(repo, &["add", "-A"]);
+    git(repo, &["commit", "-q", "-m", "init"]);
+
+    let wt = Worktree::create(repo, "build").await.unwrap();
+    assert_eq!(wt.path.parent(), app.canonicalize().unwrap().parent());
+    let status = Command::new("cargo")
+        .args(["check", "--offline", "--quiet"])
+        .env("CARGO_TARGET_DIR", parent.join("target"))
+        .current_dir(&wt.path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    wt.remove(repo).await.unwrap();
+    assert!(!wt.path.exists());
+    fs::remove_dir_all(&parent).unwrap();
+}